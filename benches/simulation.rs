@@ -0,0 +1,119 @@
+// Times the simulation step and its passes for a few room sizes, run with `cargo bench`.
+// Each sample starts from the same world, so eaten players and food don't skew later samples.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use block_explorer::accounts::Accounts;
+use block_explorer::config::GameConfig;
+use block_explorer::game_manager::{Food, GameManager};
+use block_explorer::leaderboard::Leaderboard;
+use block_explorer::map::Map;
+use block_explorer::metrics::{ConnectionMetrics, TickMetrics};
+use block_explorer::player::Player;
+use block_explorer::room::{GameMode, RoomInfo};
+use block_explorer::vector::Vector2D;
+use rand::Rng;
+use tokio::sync::broadcast;
+
+const SAMPLES: u32 = 50;
+const WIDTH: f32 = 4000.0;
+const HEIGHT: f32 = 4000.0;
+
+fn game_manager(players: usize, food: u32) -> GameManager {
+    let info = Arc::new(RoomInfo::new(
+        0,
+        GameMode::FreeForAll,
+        vec![Arc::new(Map::open(WIDTH, HEIGHT))],
+        players as u32,
+    ));
+
+    let config = GameConfig {
+        food_density: food as f32 * 100.0 * 100.0 / (WIDTH * HEIGHT),
+        food_per_player: 0.0,
+        ..GameConfig::default()
+    };
+
+    let (broadcast_channel, _) = broadcast::channel(1);
+    let mut game_manager = GameManager::new(
+        info,
+        config,
+        broadcast_channel,
+        Arc::new(TickMetrics::default()),
+        Arc::new(ConnectionMetrics::default()),
+        Arc::new(std::sync::Mutex::new(Leaderboard::default())),
+        Arc::new(std::sync::Mutex::new(Accounts::default())),
+    );
+
+    let mut rng = rand::thread_rng();
+    for id in 0..players {
+        let mut player = Player::new(id as u32, format!("player-{}", id));
+        player.position = Vector2D::new(rng.gen_range(0.0..WIDTH), rng.gen_range(0.0..HEIGHT));
+        player.radius = rng.gen_range(10.0..40.0);
+        game_manager.players.insert(player);
+    }
+
+    game_manager
+}
+
+fn bench<F>(name: &str, game_manager: &mut GameManager, mut pass: F)
+where
+    F: FnMut(&mut GameManager),
+{
+    let players = game_manager.players.clone();
+    let food: Vec<Food> = game_manager.food.to_vec();
+    let mut total = Duration::ZERO;
+
+    for _ in 0..SAMPLES {
+        game_manager.players = players.clone();
+        // Copies the players shared with `players` now, instead of on the first write of the pass
+        game_manager.players.as_mut_slice();
+        game_manager.food = Arc::new(food.clone());
+
+        let start = Instant::now();
+        pass(game_manager);
+        total += start.elapsed();
+    }
+
+    println!(
+        "{:<50} {:>10.3}ms",
+        name,
+        total.as_secs_f64() * 1000.0 / SAMPLES as f64
+    );
+}
+
+fn main() {
+    // Dead players are removed through spawned tasks, so the passes need a runtime
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start runtime");
+    let _guard = runtime.enter();
+
+    for players in [10, 100, 500] {
+        for food in [1_000, 10_000] {
+            let mut game_manager = game_manager(players, food);
+            let suffix = format!("{} players, {} food", players, food);
+
+            bench(
+                &format!("update ({})", suffix),
+                &mut game_manager,
+                |game_manager| game_manager.update(),
+            );
+            bench(
+                &format!("check_collision ({})", suffix),
+                &mut game_manager,
+                |game_manager| {
+                    game_manager.check_collision();
+                },
+            );
+            bench(
+                &format!("check_food_collision ({})", suffix),
+                &mut game_manager,
+                |game_manager| game_manager.check_food_collision(),
+            );
+            bench(
+                &format!("remove_dead_players ({})", suffix),
+                &mut game_manager,
+                |game_manager| game_manager.remove_dead_players(&[]),
+            );
+        }
+    }
+}
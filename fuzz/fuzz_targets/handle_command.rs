@@ -0,0 +1,60 @@
+// Parses arbitrary JSON into player commands and runs them through a real game manager,
+// followed by a few ticks, to find panics reachable from untrusted clients.
+// cargo +nightly fuzz run handle_command
+
+#![no_main]
+
+use std::sync::{Arc, OnceLock};
+
+use block_explorer::accounts::Accounts;
+use block_explorer::config::GameConfig;
+use block_explorer::game_manager::{GameManager, PlayerCommand, PlayerMessage};
+use block_explorer::leaderboard::Leaderboard;
+use block_explorer::map::Map;
+use block_explorer::metrics::{ConnectionMetrics, TickMetrics};
+use block_explorer::room::{GameMode, RoomInfo};
+use libfuzzer_sys::fuzz_target;
+use tokio::sync::broadcast;
+
+// The game manager spawns tasks to send messages, so it needs a runtime around it
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().expect("failed to start runtime"))
+}
+
+fn game_manager() -> GameManager {
+    let info = Arc::new(RoomInfo::new(
+        0,
+        GameMode::FreeForAll,
+        vec![Arc::new(Map::open(800.0, 600.0))],
+        50,
+    ));
+
+    let (broadcast_channel, _) = broadcast::channel(16);
+    GameManager::new(
+        info,
+        GameConfig::default(),
+        broadcast_channel,
+        Arc::new(TickMetrics::default()),
+        Arc::new(ConnectionMetrics::default()),
+        Arc::new(std::sync::Mutex::new(Leaderboard::default())),
+        Arc::new(std::sync::Mutex::new(Accounts::default())),
+    )
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _guard = runtime().enter();
+    let mut game_manager = game_manager();
+
+    // One command per line, sent alternately by two players
+    let text = String::from_utf8_lossy(data);
+    for (index, line) in text.lines().enumerate() {
+        if let Ok(command) = serde_json::from_str::<PlayerCommand>(line) {
+            game_manager.execute_player_command(PlayerMessage {
+                id: (index % 2) as u32,
+                command,
+            });
+            game_manager.update();
+        }
+    }
+});
@@ -0,0 +1,13 @@
+// Feeds arbitrary bytes to the same deserialization the websocket handler uses.
+// cargo +nightly fuzz run parse_command
+
+#![no_main]
+
+use block_explorer::game_manager::PlayerCommand;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = serde_json::from_str::<PlayerCommand>(text);
+    }
+});
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use rand::Rng;
+use sha1::{Digest, Sha1};
+
+use crate::bans::Bans;
+use crate::storage::{self, Stored, Writer};
+
+pub const ACCOUNTS_PATH: &str = "accounts.json";
+
+const MAX_USERNAME_LENGTH: usize = 16;
+const MAX_TAG_LENGTH: usize = 4;
+const MAX_CLAN_NAME_LENGTH: usize = 32;
+
+pub const STARTING_RATING: f32 = 1000.0;
+// Most rating points a single ranked result can move
+const RATING_K: f32 = 32.0;
+
+#[derive(Debug)]
+pub enum AccountError {
+    InvalidUsername,
+    UsernameTaken,
+    InvalidToken,
+    AccountNotFound,
+    InvalidClan,
+    ClanTaken,
+    ClanNotFound,
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountError::InvalidUsername => write!(
+                f,
+                "usernames are 1 to {} letters, digits or underscores",
+                MAX_USERNAME_LENGTH
+            ),
+            AccountError::UsernameTaken => write!(f, "username already taken"),
+            AccountError::InvalidToken => write!(f, "invalid token"),
+            AccountError::AccountNotFound => write!(f, "account not found"),
+            AccountError::InvalidClan => write!(
+                f,
+                "clan tags are 1 to {} letters or digits and names 1 to {} characters",
+                MAX_TAG_LENGTH, MAX_CLAN_NAME_LENGTH
+            ),
+            AccountError::ClanTaken => write!(f, "clan tag already taken"),
+            AccountError::ClanNotFound => write!(f, "clan not found"),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Account {
+    pub username: String,
+    // Only a hash of the token handed out at registration is kept
+    token_hash: String,
+    // Tag of the clan the account belongs to
+    pub clan: Option<String>,
+    pub best_mass: f32,
+    #[serde(default)]
+    pub games_played: u32,
+    #[serde(default)]
+    pub total_eats: u32,
+    #[serde(default)]
+    pub playtime_seconds: u64,
+    // ELO-style rating, only changed by results in ranked rooms
+    #[serde(default = "default_rating")]
+    pub rating: f32,
+    // Skins and colors the account may use, granted by admins
+    #[serde(default)]
+    pub skins: Vec<String>,
+}
+
+// What a single session adds to the lifetime stats of an account
+#[derive(Debug, Clone)]
+pub struct SessionStats {
+    pub best_mass: f32,
+    pub eats: u32,
+    pub playtime: Duration,
+}
+
+// Public view of an account, without its token
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub username: String,
+    pub clan: Option<String>,
+    pub games_played: u32,
+    pub best_mass: f32,
+    pub total_eats: u32,
+    pub playtime_seconds: u64,
+    pub rating: f32,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Clan {
+    pub tag: String,
+    pub name: String,
+    pub owner: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ClanScore {
+    pub tag: String,
+    pub name: String,
+    pub members: u32,
+    // Sum of the best masses of the members
+    pub mass: f32,
+}
+
+// Registered users, their clans and the bans, persisted like the leaderboard
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Accounts {
+    accounts: HashMap<String, Account>,
+    clans: HashMap<String, Clan>,
+    #[serde(default)]
+    pub bans: Bans,
+    // Saves to the accounts file, in-memory accounts have none
+    #[serde(skip)]
+    writer: Option<Writer>,
+}
+
+impl Stored for Accounts {
+    fn writer(&self) -> Option<&Writer> {
+        self.writer.as_ref()
+    }
+}
+
+impl Accounts {
+    // Written by `storage::keep_saved` once shared
+    pub fn load(path: &str) -> Accounts {
+        let mut accounts: Accounts = storage::load(path, "accounts");
+        accounts.writer = Some(Writer::new(path, "accounts"));
+        accounts
+    }
+
+    // Only marks the accounts as changed, cheap enough for the game loop
+    pub fn save(&self) {
+        if let Some(writer) = &self.writer {
+            writer.mark_changed();
+        }
+    }
+
+    // Creates the account and returns its token, the only way to act as this user afterwards
+    pub fn register(&mut self, username: &str) -> Result<String, AccountError> {
+        let valid = !username.is_empty()
+            && username.chars().count() <= MAX_USERNAME_LENGTH
+            && username
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric() || character == '_');
+        if !valid {
+            return Err(AccountError::InvalidUsername);
+        }
+
+        if self.accounts.contains_key(username) {
+            return Err(AccountError::UsernameTaken);
+        }
+
+        let token: String = (0..32)
+            .map(|_| format!("{:x}", rand::thread_rng().gen_range(0..16)))
+            .collect();
+
+        self.accounts.insert(
+            String::from(username),
+            Account {
+                username: String::from(username),
+                token_hash: hash(&token),
+                clan: None,
+                best_mass: 0.0,
+                games_played: 0,
+                total_eats: 0,
+                playtime_seconds: 0,
+                rating: STARTING_RATING,
+                skins: Vec::new(),
+            },
+        );
+
+        Ok(token)
+    }
+
+    pub fn authenticate(&self, token: &str) -> Option<&Account> {
+        let token_hash = hash(token);
+        self.accounts
+            .values()
+            .find(|account| account.token_hash == token_hash)
+    }
+
+    fn authenticate_mut(&mut self, token: &str) -> Result<&mut Account, AccountError> {
+        let token_hash = hash(token);
+        self.accounts
+            .values_mut()
+            .find(|account| account.token_hash == token_hash)
+            .ok_or(AccountError::InvalidToken)
+    }
+
+    pub fn get(&self, username: &str) -> Option<&Account> {
+        self.accounts.get(username)
+    }
+
+    pub fn clan(&self, tag: &str) -> Option<&Clan> {
+        self.clans.get(tag)
+    }
+
+    // Creates a clan owned by the account, which joins it
+    pub fn create_clan(&mut self, token: &str, tag: &str, name: &str) -> Result<(), AccountError> {
+        let valid = !tag.is_empty()
+            && tag.chars().count() <= MAX_TAG_LENGTH
+            && tag
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric())
+            && !name.trim().is_empty()
+            && name.chars().count() <= MAX_CLAN_NAME_LENGTH;
+        if !valid {
+            return Err(AccountError::InvalidClan);
+        }
+
+        if self.clans.contains_key(tag) {
+            return Err(AccountError::ClanTaken);
+        }
+
+        let account = self.authenticate_mut(token)?;
+        account.clan = Some(String::from(tag));
+        let owner = account.username.clone();
+
+        self.clans.insert(
+            String::from(tag),
+            Clan {
+                tag: String::from(tag),
+                name: String::from(name.trim()),
+                owner,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn join_clan(&mut self, token: &str, tag: &str) -> Result<(), AccountError> {
+        if !self.clans.contains_key(tag) {
+            return Err(AccountError::ClanNotFound);
+        }
+
+        self.authenticate_mut(token)?.clan = Some(String::from(tag));
+        Ok(())
+    }
+
+    pub fn leave_clan(&mut self, token: &str) -> Result<(), AccountError> {
+        self.authenticate_mut(token)?.clan = None;
+        Ok(())
+    }
+
+    pub fn record(&mut self, username: &str, session: &SessionStats) {
+        if let Some(account) = self.accounts.get_mut(username) {
+            account.best_mass = account.best_mass.max(session.best_mass);
+            account.games_played += 1;
+            account.total_eats += session.eats;
+            account.playtime_seconds += session.playtime.as_secs();
+        }
+    }
+
+    pub fn profile(&self, username: &str) -> Option<Profile> {
+        self.accounts.get(username).map(|account| Profile {
+            username: account.username.clone(),
+            clan: account.clan.clone(),
+            games_played: account.games_played,
+            best_mass: account.best_mass,
+            total_eats: account.total_eats,
+            playtime_seconds: account.playtime_seconds,
+            rating: account.rating,
+        })
+    }
+
+    pub fn rating(&self, username: &str) -> Option<f32> {
+        self.accounts.get(username).map(|account| account.rating)
+    }
+
+    // Moves rating points from the loser to the winner, fewer the more the winner was expected to win
+    pub fn rate_match(&mut self, winner: &str, loser: &str) {
+        self.apply_ratings(rating_changes(self, &[(winner, loser, 1.0)]));
+    }
+
+    // Rates the final standings of a round, best first, as every player beating everyone below them.
+    // Each pairing counts for a fraction so a round is worth about as much as a single match
+    pub fn rate_standings(&mut self, standings: &[String]) {
+        if standings.len() < 2 {
+            return;
+        }
+
+        let weight = 1.0 / (standings.len() - 1) as f32;
+        let mut matches = Vec::new();
+        for (index, winner) in standings.iter().enumerate() {
+            for loser in &standings[index + 1..] {
+                matches.push((winner.as_str(), loser.as_str(), weight));
+            }
+        }
+
+        self.apply_ratings(rating_changes(self, &matches));
+    }
+
+    fn apply_ratings(&mut self, changes: Vec<(String, f32)>) {
+        for (username, change) in changes {
+            if let Some(account) = self.accounts.get_mut(&username) {
+                account.rating += change;
+            }
+        }
+    }
+
+    pub fn grant_skin(&mut self, username: &str, skin: &str) -> Result<(), AccountError> {
+        let account = self
+            .accounts
+            .get_mut(username)
+            .ok_or(AccountError::AccountNotFound)?;
+
+        if !account.skins.iter().any(|owned| owned == skin) {
+            account.skins.push(String::from(skin));
+        }
+        Ok(())
+    }
+
+    pub fn revoke_skin(&mut self, username: &str, skin: &str) -> Result<(), AccountError> {
+        let account = self
+            .accounts
+            .get_mut(username)
+            .ok_or(AccountError::AccountNotFound)?;
+
+        account.skins.retain(|owned| owned != skin);
+        Ok(())
+    }
+
+    // Clans ordered by the combined best masses of their members
+    pub fn clan_leaderboard(&self) -> Vec<ClanScore> {
+        let mut scores: Vec<ClanScore> = self
+            .clans
+            .values()
+            .map(|clan| {
+                let members = self
+                    .accounts
+                    .values()
+                    .filter(|account| account.clan.as_deref() == Some(clan.tag.as_str()));
+
+                let mut score = ClanScore {
+                    tag: clan.tag.clone(),
+                    name: clan.name.clone(),
+                    members: 0,
+                    mass: 0.0,
+                };
+                for member in members {
+                    score.members += 1;
+                    score.mass += member.best_mass;
+                }
+                score
+            })
+            .collect();
+
+        scores.sort_by(|a, b| b.mass.total_cmp(&a.mass));
+        scores
+    }
+}
+
+// Changes computed from the ratings before any of the matches, so their order doesn't matter
+fn rating_changes(accounts: &Accounts, matches: &[(&str, &str, f32)]) -> Vec<(String, f32)> {
+    let mut changes = Vec::new();
+    for &(winner, loser, weight) in matches {
+        let (winner_rating, loser_rating) = match (accounts.rating(winner), accounts.rating(loser))
+        {
+            (Some(winner_rating), Some(loser_rating)) => (winner_rating, loser_rating),
+            _ => continue,
+        };
+
+        let expected = 1.0 / (1.0 + 10f32.powf((loser_rating - winner_rating) / 400.0));
+        let change = RATING_K * weight * (1.0 - expected);
+        changes.push((String::from(winner), change));
+        changes.push((String::from(loser), -change));
+    }
+    changes
+}
+
+fn default_rating() -> f32 {
+    STARTING_RATING
+}
+
+fn hash(token: &str) -> String {
+    Sha1::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,97 @@
+use std::io::Write;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::game_manager::AdminCommand;
+
+pub const AUDIT_LOG_PATH: &str = "audit.log";
+
+// One admin command, as it was sent
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    // Unix timestamp in seconds
+    pub timestamp: u64,
+    pub room: u32,
+    // Connection of the admin and the address it came from
+    pub actor: u32,
+    pub ip: Option<IpAddr>,
+    pub command: AdminCommand,
+}
+
+// Append-only record of the admin commands of every room, one JSON entry per line. In-memory
+// logs, used by tests, keep the entries in a list instead
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    path: Option<String>,
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    pub fn open(path: Option<String>) -> AuditLog {
+        AuditLog {
+            path,
+            entries: Vec::new(),
+        }
+    }
+
+    // Written right away, admin commands are rare enough not to slow the tick down
+    pub fn record(&mut self, room: u32, actor: u32, ip: Option<IpAddr>, command: AdminCommand) {
+        let entry = AuditEntry {
+            timestamp: now(),
+            room,
+            actor,
+            ip,
+            command,
+        };
+
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                self.entries.push(entry);
+                return;
+            }
+        };
+
+        let mut line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(error) => {
+                println!("Error serializing audit entry: {}", error);
+                return;
+            }
+        };
+        line.push('\n');
+
+        let result = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+        if let Err(error) = result {
+            println!("Error writing audit log: {}", error);
+        }
+    }
+
+    // The latest entries, oldest first. Lines that don't parse are skipped
+    pub fn recent(&self, limit: usize) -> Vec<AuditEntry> {
+        let entries = match &self.path {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(contents) => contents
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            None => self.entries.clone(),
+        };
+
+        let skip = entries.len().saturating_sub(limit);
+        entries.into_iter().skip(skip).collect()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,90 @@
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum BanTarget {
+    Ip { ip: IpAddr },
+    // A registered account, whatever address it connects from
+    Account { username: String },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Ban {
+    pub target: BanTarget,
+    pub reason: String,
+    // Unix timestamps in seconds, bans without an expiry are permanent
+    pub banned_at: u64,
+    pub expires_at: Option<u64>,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+
+    // What the banned player is told
+    pub fn message(&self) -> String {
+        format!("You are banned: {}", self.reason)
+    }
+}
+
+// Banned addresses and accounts, saved with the accounts so they survive restarts
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Bans {
+    bans: Vec<Ban>,
+}
+
+impl Bans {
+    // Replaces any earlier ban of the same target
+    pub fn ban(&mut self, target: BanTarget, reason: &str, duration: Option<Duration>, now: u64) {
+        self.bans
+            .retain(|ban| ban.target != target && ban.is_active(now));
+        self.bans.push(Ban {
+            target,
+            reason: String::from(reason),
+            banned_at: now,
+            expires_at: duration.map(|duration| now + duration.as_secs()),
+        });
+    }
+
+    // Returns false if the target wasn't banned
+    pub fn unban(&mut self, target: &BanTarget) -> bool {
+        let count = self.bans.len();
+        self.bans.retain(|ban| &ban.target != target);
+        self.bans.len() != count
+    }
+
+    pub fn find(&self, target: &BanTarget, now: u64) -> Option<&Ban> {
+        self.bans
+            .iter()
+            .find(|ban| &ban.target == target && ban.is_active(now))
+    }
+
+    pub fn find_ip(&self, ip: IpAddr, now: u64) -> Option<&Ban> {
+        self.find(&BanTarget::Ip { ip }, now)
+    }
+
+    pub fn find_account(&self, username: &str, now: u64) -> Option<&Ban> {
+        self.find(
+            &BanTarget::Account {
+                username: String::from(username),
+            },
+            now,
+        )
+    }
+
+    pub fn active(&self, now: u64) -> Vec<Ban> {
+        self.bans
+            .iter()
+            .filter(|ban| ban.is_active(now))
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
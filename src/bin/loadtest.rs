@@ -0,0 +1,181 @@
+// Opens many bot connections against a running server and reports the throughput they observe,
+// together with the tick timings exported by the server on `/metrics`.
+//
+// cargo run --release --bin loadtest -- --clients 200 --rate 20 --seconds 30
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use block_explorer::client::GameClient;
+use block_explorer::vector::Vector2D;
+use rand::Rng;
+use tokio::time::{self, Duration, Instant};
+
+struct Options {
+    url: String,
+    metrics_url: String,
+    clients: u32,
+    rate: u32,
+    seconds: u64,
+}
+
+impl Options {
+    fn parse() -> Options {
+        let mut options = Options {
+            url: String::from("ws://127.0.0.1:3000/game"),
+            metrics_url: String::from("http://127.0.0.1:3000/metrics"),
+            clients: 50,
+            rate: 10,
+            seconds: 10,
+        };
+
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        for pair in args.chunks(2) {
+            let (flag, value) = match pair {
+                [flag, value] => (flag.as_str(), value.as_str()),
+                _ => panic!("Missing value for {}", pair[0]),
+            };
+
+            match flag {
+                "--url" => options.url = String::from(value),
+                "--metrics-url" => options.metrics_url = String::from(value),
+                "--clients" => options.clients = value.parse().expect("Invalid --clients"),
+                "--rate" => options.rate = value.parse().expect("Invalid --rate"),
+                "--seconds" => options.seconds = value.parse().expect("Invalid --seconds"),
+                _ => panic!("Unknown flag {}", flag),
+            }
+        }
+
+        options
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    connected: AtomicU64,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    errors: AtomicU64,
+}
+
+async fn run_bot(index: u32, options: Arc<Options>, counters: Arc<Counters>, deadline: Instant) {
+    let mut client = match GameClient::connect(&options.url).await {
+        Ok(client) => client,
+        Err(error) => {
+            println!("Bot {} failed to connect: {}", index, error);
+            counters.errors.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    };
+    counters.connected.fetch_add(1, Ordering::Relaxed);
+
+    if client.join(&format!("bot-{}", index)).await.is_err() {
+        counters.errors.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
+    let mut interval = time::interval(Duration::from_secs_f64(1.0 / options.rate.max(1) as f64));
+
+    loop {
+        tokio::select! {
+            _ = time::sleep_until(deadline) => break,
+            _ = interval.tick() => {
+                let position = {
+                    let mut rng = rand::thread_rng();
+                    Vector2D::new(rng.gen_range(0.0..800.0), rng.gen_range(0.0..600.0))
+                };
+
+                if client.move_towards(position).await.is_err() {
+                    counters.errors.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                counters.messages_sent.fetch_add(1, Ordering::Relaxed);
+            }
+            message = client.next_message() => match message {
+                Some(Ok(_)) => {
+                    counters.messages_received.fetch_add(1, Ordering::Relaxed);
+                }
+                Some(Err(_)) | None => {
+                    counters.errors.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+            },
+        }
+    }
+
+    let _ = client.close().await;
+}
+
+async fn fetch_metrics(url: &str) -> Option<String> {
+    let uri = url.parse().ok()?;
+    let response = hyper::Client::new().get(uri).await.ok()?;
+    let body = hyper::body::to_bytes(response.into_body()).await.ok()?;
+    String::from_utf8(body.to_vec()).ok()
+}
+
+fn metric(metrics: &str, name: &str) -> Option<f64> {
+    metrics.lines().find_map(|line| {
+        let value = line.strip_prefix(name)?.strip_prefix(' ')?;
+        value.parse().ok()
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let options = Arc::new(Options::parse());
+    let counters = Arc::new(Counters::default());
+
+    println!(
+        "Starting {} bots at {} moves per second for {} seconds against {}",
+        options.clients, options.rate, options.seconds, options.url
+    );
+
+    let before = fetch_metrics(&options.metrics_url).await;
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(options.seconds);
+
+    let bots: Vec<_> = (0..options.clients)
+        .map(|index| tokio::spawn(run_bot(index, options.clone(), counters.clone(), deadline)))
+        .collect();
+
+    for bot in bots {
+        let _ = bot.await;
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let after = fetch_metrics(&options.metrics_url).await;
+
+    let sent = counters.messages_sent.load(Ordering::Relaxed);
+    let received = counters.messages_received.load(Ordering::Relaxed);
+
+    println!(
+        "Connected bots: {}",
+        counters.connected.load(Ordering::Relaxed)
+    );
+    println!("Errors: {}", counters.errors.load(Ordering::Relaxed));
+    println!("Moves sent: {} ({:.0}/s)", sent, sent as f64 / elapsed);
+    println!(
+        "Messages received: {} ({:.0}/s)",
+        received,
+        received as f64 / elapsed
+    );
+
+    match (before, after) {
+        (Some(before), Some(after)) => {
+            let delta = |name: &str| {
+                metric(&after, name).unwrap_or(0.0) - metric(&before, name).unwrap_or(0.0)
+            };
+
+            let ticks = delta("game_tick_duration_seconds_count");
+            let tick_seconds = delta("game_tick_duration_seconds_sum");
+            let slow_ticks = delta("game_slow_ticks_total");
+
+            println!("Server ticks: {} ({:.0}/s)", ticks, ticks / elapsed);
+            if ticks > 0.0 {
+                println!("Average tick: {:.3}ms", tick_seconds / ticks * 1000.0);
+            }
+            println!("Slow ticks: {}", slow_ticks);
+        }
+        _ => println!("Could not read server metrics from {}", options.metrics_url),
+    }
+}
@@ -0,0 +1,188 @@
+use crate::map::Map;
+use crate::player::Player;
+use crate::vector::Vector2D;
+
+// First byte of every binary frame
+pub const STATE_DELTA: u8 = 1;
+
+// Bytes of each player in a binary `StateDelta`
+const PLAYER_SIZE: usize = 10;
+
+// How clients want the state deltas, picked with the `luisgar.bin.v1` websocket subprotocol or
+// `/game?encoding=binary`. Every other message stays JSON
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl Encoding {
+    // `Sec-WebSocket-Protocol` names, a negotiated one wins over the query
+    pub const SUBPROTOCOLS: [&'static str; 2] = ["luisgar.json.v1", "luisgar.bin.v1"];
+
+    pub fn subprotocol(&self) -> &'static str {
+        match self {
+            Encoding::Json => Encoding::SUBPROTOCOLS[0],
+            Encoding::Binary => Encoding::SUBPROTOCOLS[1],
+        }
+    }
+
+    pub fn from_subprotocol(name: &str) -> Option<Encoding> {
+        [Encoding::Json, Encoding::Binary]
+            .into_iter()
+            .find(|encoding| encoding.subprotocol() == name)
+    }
+}
+
+// How binary frames pack positions and radii into u16, each value is the u16 times its step
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Quantization {
+    pub x_step: f32,
+    pub y_step: f32,
+    pub radius_step: f32,
+}
+
+impl Quantization {
+    // Positions cover the map bounds and radii go up to its longest side
+    pub fn for_map(map: &Map) -> Quantization {
+        let max = u16::MAX as f32;
+        Quantization {
+            x_step: map.width / max,
+            y_step: map.height / max,
+            radius_step: map.width.max(map.height) / max,
+        }
+    }
+
+    pub fn position(&self, position: Vector2D) -> (u16, u16) {
+        (
+            quantize(position.x, self.x_step),
+            quantize(position.y, self.y_step),
+        )
+    }
+
+    pub fn radius(&self, radius: f32) -> u16 {
+        quantize(radius, self.radius_step)
+    }
+}
+
+fn quantize(value: f32, step: f32) -> u16 {
+    (value / step).round().clamp(0.0, u16::MAX as f32) as u16
+}
+
+// Where the u32 sequence number of a frame starts, written by each connection as it sends the frame
+const SEQUENCE_OFFSET: usize = 9;
+
+// `StateDelta` for binary clients, little endian: the tag, the u64 tick, the u32 sequence number, a u16
+// count of players each as u32 id, u16 x, u16 y and u16 radius, then a u16 count of removed players each
+// as u32 id. The sequence number is left at 0 for `set_sequence`
+pub fn state_delta(
+    quantization: &Quantization,
+    tick: u64,
+    players: &[&Player],
+    removed_players: &[u32],
+) -> Vec<u8> {
+    let players = &players[..players.len().min(u16::MAX as usize)];
+    let removed_players = &removed_players[..removed_players.len().min(u16::MAX as usize)];
+
+    let mut bytes =
+        Vec::with_capacity(17 + players.len() * PLAYER_SIZE + removed_players.len() * 4);
+    bytes.push(STATE_DELTA);
+    bytes.extend_from_slice(&tick.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+
+    bytes.extend_from_slice(&(players.len() as u16).to_le_bytes());
+    for player in players {
+        let (x, y) = quantization.position(player.position);
+        bytes.extend_from_slice(&player.id.to_le_bytes());
+        bytes.extend_from_slice(&x.to_le_bytes());
+        bytes.extend_from_slice(&y.to_le_bytes());
+        bytes.extend_from_slice(&quantization.radius(player.radius).to_le_bytes());
+    }
+
+    bytes.extend_from_slice(&(removed_players.len() as u16).to_le_bytes());
+    for id in removed_players {
+        bytes.extend_from_slice(&id.to_le_bytes());
+    }
+
+    bytes
+}
+
+pub fn set_sequence(bytes: &mut [u8], sequence: u32) {
+    if let Some(slot) = bytes.get_mut(SEQUENCE_OFFSET..SEQUENCE_OFFSET + 4) {
+        slot.copy_from_slice(&sequence.to_le_bytes());
+    }
+}
+
+// A decoded binary `StateDelta` entry: id, position and radius
+pub type PlayerUpdate = (u32, Vector2D, f32);
+
+#[derive(Debug, Clone)]
+pub struct DecodedDelta {
+    pub tick: u64,
+    pub sequence: u32,
+    pub players: Vec<PlayerUpdate>,
+    pub removed_players: Vec<u32>,
+}
+
+// Reverses `state_delta`, None when the frame is malformed
+pub fn decode_state_delta(quantization: &Quantization, bytes: &[u8]) -> Option<DecodedDelta> {
+    let mut reader = Reader { bytes };
+    if reader.u8()? != STATE_DELTA {
+        return None;
+    }
+    let tick = reader.u64()?;
+    let sequence = reader.u32()?;
+
+    let count = reader.u16()?;
+    let mut players = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let id = reader.u32()?;
+        let x = reader.u16()? as f32 * quantization.x_step;
+        let y = reader.u16()? as f32 * quantization.y_step;
+        let radius = reader.u16()? as f32 * quantization.radius_step;
+        players.push((id, Vector2D::new(x, y), radius));
+    }
+
+    let count = reader.u16()?;
+    let mut removed_players = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        removed_players.push(reader.u32()?);
+    }
+
+    Some(DecodedDelta {
+        tick,
+        sequence,
+        players,
+        removed_players,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.bytes.split_first_chunk::<N>()?;
+        self.bytes = rest;
+        Some(*head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::vector::Vector2D;
+
+// Mass the boss sheds as a chunk of food every time a player bumps into it
+pub const CHUNK_MASS: f32 = 500.0;
+// Ticks a player has to wait before knocking off another chunk
+pub const CHUNK_TICKS: u64 = 20;
+// The boss is beaten once it is down to this fraction of the mass it spawned with
+pub const DEFEAT_FRACTION: f32 = 0.2;
+
+// Blob spawned by the room every `boss_seconds` that chases the players it can eat
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Boss {
+    pub position: Vector2D,
+    pub radius: f32,
+    // Units per second, clients move the boss along it between updates
+    pub velocity: Vector2D,
+    #[serde(skip)]
+    pub starting_mass: f32,
+    // Mass of the chunks each player ate
+    #[serde(skip)]
+    pub damage: HashMap<u32, f32>,
+    // Food ids of the chunks nobody ate yet
+    #[serde(skip)]
+    pub chunks: Vec<u32>,
+    // Tick each player last knocked a chunk off
+    #[serde(skip)]
+    hits: HashMap<u32, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BossReward {
+    pub id: u32,
+    pub mass: f32,
+}
+
+impl Boss {
+    pub fn new(position: Vector2D, mass: f32) -> Boss {
+        let mut boss = Boss {
+            position,
+            radius: 0.0,
+            velocity: Vector2D::new(0.0, 0.0),
+            starting_mass: mass,
+            damage: HashMap::new(),
+            chunks: Vec::new(),
+            hits: HashMap::new(),
+        };
+        boss.set_mass(mass);
+        boss
+    }
+
+    // Same scale as `Player::mass`
+    pub fn mass(&self) -> f32 {
+        2.0 * self.radius.powf(2.0) * std::f32::consts::PI
+    }
+
+    pub fn set_mass(&mut self, mass: f32) {
+        self.radius = (mass.max(0.0) / (2.0 * std::f32::consts::PI)).sqrt();
+    }
+
+    pub fn is_beaten(&self) -> bool {
+        self.mass() <= self.starting_mass * DEFEAT_FRACTION
+    }
+
+    // Whether `id` may knock off a chunk this tick, remembering it if so
+    pub fn hit(&mut self, id: u32, tick: u64) -> bool {
+        if self.is_beaten() {
+            return false;
+        }
+        if let Some(&last) = self.hits.get(&id) {
+            if tick < last + CHUNK_TICKS {
+                return false;
+            }
+        }
+        self.hits.insert(id, tick);
+        true
+    }
+
+    // Splits `mass` between the players by the share of the chunks they ate, in id order
+    pub fn rewards(&self, mass: f32) -> Vec<BossReward> {
+        let total: f32 = self.damage.values().sum();
+        if total <= 0.0 {
+            return Vec::new();
+        }
+
+        let mut rewards: Vec<BossReward> = self
+            .damage
+            .iter()
+            .map(|(&id, damage)| BossReward {
+                id,
+                mass: mass * damage / total,
+            })
+            .collect();
+        rewards.sort_by_key(|reward| reward.id);
+        rewards
+    }
+}
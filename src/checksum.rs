@@ -0,0 +1,40 @@
+use crate::game_manager::Food;
+use crate::vector::Vector2D;
+
+const FNV_OFFSET: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+// Ticks between two state deltas carrying a checksum, full states always have one
+pub const CHECKSUM_TICKS: u64 = 10;
+
+// Summary of the world that clients recompute from the states and food events they received, a different
+// result means they drifted and should ask for a snapshot. Every player and food is hashed on its own and the
+// hashes are summed, so the order they are listed in doesn't matter. Positions and radii are hashed as their
+// f32 bits, binary clients only see them quantized and can't check it
+pub fn world<'a>(
+    players: impl IntoIterator<Item = (u32, Vector2D, f32)>,
+    food: impl IntoIterator<Item = &'a Food>,
+) -> u32 {
+    let players = players
+        .into_iter()
+        .map(|(id, position, radius)| entity(b'p', id, position, radius));
+    let food = food
+        .into_iter()
+        .map(|food| entity(b'f', food.id, food.position, food.radius));
+
+    players.chain(food).fold(0, u32::wrapping_add)
+}
+
+// FNV-1a of the kind, id, position and radius
+fn entity(kind: u8, id: u32, position: Vector2D, radius: f32) -> u32 {
+    let bytes = [kind]
+        .into_iter()
+        .chain(id.to_le_bytes())
+        .chain(position.x.to_bits().to_le_bytes())
+        .chain(position.y.to_bits().to_le_bytes())
+        .chain(radius.to_bits().to_le_bytes());
+
+    bytes.fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::game_manager::{MessageToClient, PlayerCommand};
+use crate::vector::Vector2D;
+
+#[derive(Debug)]
+pub enum ClientError {
+    WebSocket(tungstenite::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::WebSocket(error) => write!(f, "websocket error: {}", error),
+            ClientError::Json(error) => write!(f, "json error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<tungstenite::Error> for ClientError {
+    fn from(error: tungstenite::Error) -> ClientError {
+        ClientError::WebSocket(error)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(error: serde_json::Error) -> ClientError {
+        ClientError::Json(error)
+    }
+}
+
+// Websocket client speaking the game protocol, used to write bots, load tests and integration tests
+pub struct GameClient {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    // Sequence number of the last state frame, to notice lost ones
+    last_sequence: Option<u32>,
+}
+
+impl GameClient {
+    // Connects to a game endpoint such as `ws://127.0.0.1:3000/game`
+    pub async fn connect(url: &str) -> Result<GameClient, ClientError> {
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+        Ok(GameClient {
+            socket,
+            last_sequence: None,
+        })
+    }
+
+    pub async fn send(&mut self, command: PlayerCommand) -> Result<(), ClientError> {
+        let text = serde_json::to_string(&command)?;
+        self.socket.send(Message::Text(text)).await?;
+        Ok(())
+    }
+
+    pub async fn join(&mut self, name: &str) -> Result<(), ClientError> {
+        self.send(PlayerCommand::Join {
+            name: String::from(name),
+            token: None,
+            skin: None,
+            update_rate: None,
+        })
+        .await
+    }
+
+    pub async fn move_towards(&mut self, position: Vector2D) -> Result<(), ClientError> {
+        self.send(PlayerCommand::Move {
+            position,
+            client_time: None,
+        })
+        .await
+    }
+
+    pub async fn rename(&mut self, name: &str) -> Result<(), ClientError> {
+        self.send(PlayerCommand::Rename {
+            name: String::from(name),
+        })
+        .await
+    }
+
+    // Waits for the next message from the server, `None` once the connection is closed.
+    // Asks for a snapshot when state frames went missing
+    pub async fn next_message(&mut self) -> Option<Result<MessageToClient, ClientError>> {
+        loop {
+            let message = match self.socket.next().await? {
+                Ok(message) => message,
+                Err(error) => return Some(Err(error.into())),
+            };
+
+            match message {
+                Message::Text(text) => {
+                    let message = serde_json::from_str(&text).map_err(ClientError::from);
+                    if let Ok(
+                        MessageToClient::State { sequence, .. }
+                        | MessageToClient::StateDelta { sequence, .. },
+                    ) = &message
+                    {
+                        let gap = self
+                            .last_sequence
+                            .is_some_and(|last| *sequence != last.wrapping_add(1));
+                        self.last_sequence = Some(*sequence);
+                        if gap {
+                            if let Err(error) = self.send(PlayerCommand::RequestSnapshot).await {
+                                return Some(Err(error));
+                            }
+                        }
+                    }
+                    return Some(message);
+                }
+                Message::Close(_) => return None,
+                _ => continue,
+            }
+        }
+    }
+
+    pub async fn close(mut self) -> Result<(), ClientError> {
+        self.socket.close(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use tokio::sync::mpsc::Sender;
+use tokio::time::{self, Duration};
+
+use crate::game_manager::{Command, InternalCommand};
+use crate::schedule::ScheduledEvent;
+
+pub const CONFIG_PATH: &str = "config.json";
+
+// How often the config file is checked for changes
+const WATCH_INTERVAL_MILLISECONDS: u64 = 2000;
+
+// Tunable values that can be changed while the rooms are running. Debug output hides `admin_token`
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    // Food each room tries to keep on the map per 100x100 area, plus some for every player
+    pub food_density: f32,
+    pub food_per_player: f32,
+    // Most food spawned in a single tick, so eaten food grows back gradually instead of in bursts
+    pub food_spawn_per_tick: u32,
+    // Patches food is spawned in, spread out over the food regions, and how far food lands from their center.
+    // Food is spread uniformly when 0
+    pub food_clusters: u32,
+    pub food_cluster_radius: f32,
+    // Fraction of the spawned food that wanders around at `wandering_food_speed` per second, worth more than
+    // the food that stays put
+    pub wandering_food_chance: f32,
+    pub wandering_food_speed: f32,
+    // Mass players join with, the mass of a starting radius of 10 when not set
+    pub starting_mass: Option<f32>,
+    // Players who were eaten join again with this fraction of their best mass in the previous life, at most
+    // `respawn_mass_cap` and never less than the starting mass. 0 for no handicap
+    pub respawn_mass_fraction: f32,
+    pub respawn_mass_cap: f32,
+    // Fraction of mass lost per second by players bigger than the starting size
+    pub decay_rate: f32,
+    // Windows with more mass from eating or without decay, announced when they start and end
+    pub events: Vec<ScheduledEvent>,
+    // Ticks between two keyframes, the full states sent instead of deltas so clients can't drift for long
+    pub keyframe_ticks: u64,
+    // States per second the broadcasts may drop to while the ticks take most of their budget, the simulation
+    // keeps its rate. 100, the tick rate, turns this off
+    pub min_broadcast_rate: u32,
+    // Longest delay made up for moves carrying a `client_time`. Their eats are checked against where the other
+    // players were that long ago, so a high ping doesn't lose close chases. Never more than the round trip
+    // measured with the connection's keyframe acks. 0 turns it off
+    pub max_rewind_milliseconds: u64,
+    // Speed is `speed_factor / sqrt(mass)`
+    pub speed_factor: f32,
+    // Multiplies the simulated time of each tick (movement, decay, cooldowns) without changing the tick rate,
+    // below 1 for slow motion and above to fast-forward
+    pub time_scale: f32,
+    // Fraction of the overlap between two players that can't eat each other undone every tick, so they bump
+    // into each other instead of passing through. 0 lets them overlap
+    pub collision_stiffness: f32,
+    // Players never grow past this radius
+    pub max_radius: f32,
+    // Above this radius the growth from eating is scaled down by `growth_falloff_radius / radius`
+    pub growth_falloff_radius: f32,
+    // Players per squad in squad rooms, between 2 and 4
+    pub squad_size: u32,
+    // Urls that receive a POST for notable game events
+    pub webhook_urls: Vec<String>,
+    // Player counts that trigger a webhook when a room reaches them
+    pub player_count_thresholds: Vec<u32>,
+    // Discord webhook that receives record-breaking scores
+    pub discord_webhook_url: Option<String>,
+    // Message of the day, sent to every player when they join
+    pub motd: Option<String>,
+    // Length of a round, after which every player is sent back to the lobby and the next map is played.
+    // Rounds never end on their own when it is 0
+    pub round_seconds: u64,
+    // Players a round without respawns waits for before it starts
+    pub last_man_players: u32,
+    // Seconds the biggest player has to stay on top before a bounty of `bounty_mass` is put on them,
+    // 0 for no bounties
+    pub bounty_seconds: u64,
+    pub bounty_mass: f32,
+    // Seconds between a boss being beaten and the next one spawning, 0 for no bosses. The players who ate its
+    // chunks share `boss_reward_mass` when it is beaten
+    pub boss_seconds: u64,
+    pub boss_mass: f32,
+    pub boss_reward_mass: f32,
+    // Smallest fraction of the map width and height a room plays on, it grows to the whole map as
+    // `players_for_full_map` join. 1 always plays on the whole map
+    pub min_map_scale: f32,
+    pub players_for_full_map: u32,
+    // Length of the day and night phases, there is no night when `night_seconds` is 0
+    pub day_seconds: u64,
+    pub night_seconds: u64,
+    // How far players see during the day, scaled by `night_vision_factor` at night
+    pub vision_radius: f32,
+    pub night_vision_factor: f32,
+    // Proxies allowed to tell the client address with `Forwarded` or `X-Forwarded-For`, as IPs or CIDR ranges.
+    // Read once at startup
+    pub trusted_proxies: Vec<String>,
+    // Registry the server reports itself to every `heartbeat_seconds`, for public server lists. `public_address` is
+    // the websocket url players should use, the listen address when it is not set. Read once at startup
+    pub master_server_url: Option<String>,
+    pub public_address: Option<String>,
+    pub region: Option<String>,
+    pub heartbeat_seconds: u64,
+    // Websocket upgrades accepted from one address per minute before answering 429, 0 for no limit.
+    // Read once at startup
+    pub upgrades_per_minute: u32,
+    // Tokio workers for the connections and HTTP routes, one per core when 0. Above 0, `simulation_threads` runs
+    // the rooms on a runtime of their own so a busy room can't starve the connections. Read once at startup
+    pub worker_threads: usize,
+    pub simulation_threads: usize,
+    // Token that turns a connection into an admin with `PlayerCommand::Login`, admin commands are disabled without it
+    pub admin_token: Option<String>,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            food_density: 1.0,
+            food_per_player: 2.0,
+            food_spawn_per_tick: 5,
+            food_clusters: 0,
+            food_cluster_radius: 150.0,
+            wandering_food_chance: 0.0,
+            wandering_food_speed: 30.0,
+            starting_mass: None,
+            respawn_mass_fraction: 0.0,
+            respawn_mass_cap: 2000.0,
+            decay_rate: 0.0,
+            events: Vec::new(),
+            max_rewind_milliseconds: 100,
+            speed_factor: 100.0,
+            time_scale: 1.0,
+            collision_stiffness: 0.5,
+            max_radius: 200.0,
+            growth_falloff_radius: 100.0,
+            squad_size: 2,
+            webhook_urls: Vec::new(),
+            player_count_thresholds: Vec::new(),
+            discord_webhook_url: None,
+            motd: None,
+            round_seconds: 0,
+            last_man_players: 2,
+            bounty_seconds: 0,
+            bounty_mass: 500.0,
+            boss_seconds: 0,
+            boss_mass: 20000.0,
+            boss_reward_mass: 5000.0,
+            min_map_scale: 1.0,
+            players_for_full_map: 50,
+            day_seconds: 120,
+            night_seconds: 0,
+            vision_radius: 1000.0,
+            night_vision_factor: 0.5,
+            trusted_proxies: Vec::new(),
+            keyframe_ticks: 100,
+            min_broadcast_rate: 30,
+            upgrades_per_minute: 60,
+            worker_threads: 0,
+            simulation_threads: 0,
+            master_server_url: None,
+            public_address: None,
+            region: None,
+            heartbeat_seconds: 30,
+            admin_token: None,
+        }
+    }
+}
+
+impl GameConfig {
+    // For startup, falls back to the defaults when the file is missing or invalid
+    pub fn load(path: &str) -> GameConfig {
+        match GameConfig::try_load(path) {
+            Ok(config) => config,
+            Err(error) => {
+                println!("Error reading config: {}", error);
+                GameConfig::default()
+            }
+        }
+    }
+
+    // A missing file is the defaults, an unreadable or invalid one is an error
+    pub fn try_load(path: &str) -> Result<GameConfig, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(GameConfig::default())
+            }
+            Err(error) => return Err(error.to_string()),
+        };
+
+        serde_json::from_str::<GameConfig>(&contents).map_err(|error| error.to_string())
+    }
+}
+
+// Fields holding credentials, webhook and master server URLs carry their tokens in the path
+const SECRET_FIELDS: [&str; 4] = [
+    "admin_token",
+    "webhook_urls",
+    "discord_webhook_url",
+    "master_server_url",
+];
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Null => {}
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+        value => *value = serde_json::Value::from("<redacted>"),
+    }
+}
+
+// Printed as its JSON so it never falls behind the fields, with the secrets redacted
+impl fmt::Debug for GameConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut value = serde_json::to_value(self).map_err(|_| fmt::Error)?;
+        for field in SECRET_FIELDS {
+            if let Some(secret) = value.get_mut(field) {
+                redact(secret);
+            }
+        }
+        write!(f, "GameConfig {}", value)
+    }
+}
+
+fn modified_at(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Polls the config file and sends the new values to every room when it changes
+pub fn watch(path: &'static str, rooms: Vec<Sender<Command>>) {
+    tokio::spawn(async move {
+        let mut last_modified = modified_at(path);
+
+        loop {
+            time::sleep(Duration::from_millis(WATCH_INTERVAL_MILLISECONDS)).await;
+
+            let modified = modified_at(path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            // A file being replaced is briefly gone, the rooms keep their config until it is back
+            if modified.is_none() {
+                continue;
+            }
+
+            // A half edited or broken file keeps the rooms on the config they have
+            let config = match GameConfig::try_load(path) {
+                Ok(config) => config,
+                Err(error) => {
+                    println!("Error reading config, keeping the current one: {}", error);
+                    continue;
+                }
+            };
+            println!("Reloading config: {:?}", config);
+
+            for command_tx in &rooms {
+                if let Err(error) = command_tx
+                    .send(Command::InternalCommand(InternalCommand::ReloadConfig {
+                        config: Box::new(config.clone()),
+                    }))
+                    .await
+                {
+                    println!("Error sending reload config command: {}", error);
+                }
+            }
+        }
+    });
+}
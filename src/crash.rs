@@ -0,0 +1,76 @@
+use std::cell::Cell;
+use std::sync::{atomic::Ordering, Arc, Mutex};
+
+use crate::accounts::Accounts;
+use crate::leaderboard::Leaderboard;
+use crate::room::RoomInfo;
+
+thread_local! {
+    // Room and tick of the game manager running on this thread, reported with its panics
+    static CONTEXT: Cell<Option<(u32, u64)>> = const { Cell::new(None) };
+}
+
+// Logs every panic as a single line, with the room and tick when it happened in a game manager,
+// then lets the default hook print the usual message and backtrace
+pub fn install_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info.payload_as_str().unwrap_or("unknown");
+        let location = match info.location() {
+            Some(location) => location.to_string(),
+            None => String::from("unknown"),
+        };
+
+        match CONTEXT.with(Cell::get) {
+            Some((room, tick)) => println!(
+                "event=panic room={} tick={} location={} message={:?}",
+                room, tick, location, message
+            ),
+            None => println!("event=panic location={} message={:?}", location, message),
+        }
+
+        default_hook(info);
+    }));
+}
+
+// Runs `f` with the room and tick attached to any panic it causes
+pub fn in_room<T>(room: u32, tick: u64, f: impl FnOnce() -> T) -> T {
+    struct Reset(Option<(u32, u64)>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            CONTEXT.with(|context| context.set(self.0));
+        }
+    }
+
+    let _reset = Reset(CONTEXT.with(|context| context.replace(Some((room, tick)))));
+    f()
+}
+
+// Room and tick set by the `in_room` running on this thread, if any
+pub fn context() -> Option<(u32, u64)> {
+    CONTEXT.with(Cell::get)
+}
+
+// Held by the task of a game manager. When the task dies from a panic the room is marked as crashed
+// so nobody is sent there, and the locks it may have poisoned are cleared so the other rooms keep
+// saving accounts and scores
+pub struct CrashGuard {
+    pub info: Arc<RoomInfo>,
+    pub accounts: Arc<Mutex<Accounts>>,
+    pub leaderboard: Arc<Mutex<Leaderboard>>,
+}
+
+impl Drop for CrashGuard {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            return;
+        }
+
+        println!("event=room_crashed room={}", self.info.id);
+        self.info.crashed.store(true, Ordering::Relaxed);
+        self.info.player_count.store(0, Ordering::Relaxed);
+        self.accounts.clear_poison();
+        self.leaderboard.clear_poison();
+    }
+}
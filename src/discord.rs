@@ -0,0 +1,20 @@
+use crate::webhooks;
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct DiscordMessage {
+    content: String,
+}
+
+// Posts a message to the Discord webhook configured in `discord_webhook_url`, if any.
+// Discord only accepts https, so the url has to point to a forwarding proxy until the server has a TLS client.
+pub fn announce(webhook_url: &Option<String>, content: String) {
+    let webhook_url = match webhook_url {
+        Some(webhook_url) => webhook_url.clone(),
+        None => return,
+    };
+
+    match serde_json::to_string(&DiscordMessage { content }) {
+        Ok(body) => webhooks::post(webhook_url, body),
+        Err(error) => println!("Error serializing Discord message: {}", error),
+    }
+}
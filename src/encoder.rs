@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, mpsc};
+
+use crate::binary::{self, Quantization};
+use crate::game_manager::{Category, Food, Frame};
+use crate::player::Player;
+
+// States with more players and food than this are encoded on the blocking pool
+pub const BLOCKING_ENTITIES: usize = 2000;
+
+// Borrowing version of `MessageToClient::State`, serialized the same way without cloning the world
+#[derive(serde::Serialize)]
+enum StateMessage<'a> {
+    State {
+        tick: u64,
+        checksum: u32,
+        players: &'a [Player],
+        food: &'a [Food],
+    },
+    StateDelta {
+        tick: u64,
+        checksum: Option<u32>,
+        players: Vec<&'a Player>,
+        removed_players: Vec<u32>,
+    },
+}
+
+// What the game manager shares with every connection, in the order they should get it. States over
+// `BLOCKING_ENTITIES` carry a snapshot of the world, shared with the room until its next write, so the
+// next tick can run while they are encoded. Smaller ones are encoded during the tick and sent as frames
+#[derive(Debug)]
+pub enum Broadcast {
+    Frame(Frame),
+    State {
+        tick: u64,
+        checksum: u32,
+        players: Arc<Vec<Player>>,
+        food: Arc<Vec<Food>>,
+    },
+    StateDelta {
+        tick: u64,
+        checksum: Option<u32>,
+        quantization: Quantization,
+        players: Arc<Vec<Player>>,
+        // Indices in `players` of the players that changed
+        changed: Vec<usize>,
+        removed_players: Vec<u32>,
+    },
+}
+
+impl Broadcast {
+    fn entities(&self) -> usize {
+        match self {
+            Broadcast::Frame(_) => 0,
+            Broadcast::State { players, food, .. } => players.len() + food.len(),
+            Broadcast::StateDelta { changed, .. } => changed.len(),
+        }
+    }
+
+    pub fn encode(self) -> Option<Frame> {
+        match self {
+            Broadcast::Frame(frame) => Some(frame),
+            Broadcast::State {
+                tick,
+                checksum,
+                players,
+                food,
+            } => full_state(tick, checksum, &players, &food),
+            Broadcast::StateDelta {
+                tick,
+                checksum,
+                quantization,
+                players,
+                changed,
+                removed_players,
+            } => state_delta(
+                tick,
+                checksum,
+                &quantization,
+                changed.iter().map(|&index| &players[index]).collect(),
+                removed_players,
+            ),
+        }
+    }
+}
+
+pub fn full_state(tick: u64, checksum: u32, players: &[Player], food: &[Food]) -> Option<Frame> {
+    let state = StateMessage::State {
+        tick,
+        checksum,
+        players,
+        food,
+    };
+    Frame::serialize(&state, Some(Category::State)).map(|frame| Frame {
+        state: true,
+        ..frame
+    })
+}
+
+// As text and packed for binary clients
+pub fn state_delta(
+    tick: u64,
+    checksum: Option<u32>,
+    quantization: &Quantization,
+    players: Vec<&Player>,
+    removed_players: Vec<u32>,
+) -> Option<Frame> {
+    let binary = binary::state_delta(quantization, tick, &players, &removed_players);
+    let delta = StateMessage::StateDelta {
+        tick,
+        checksum,
+        players,
+        removed_players,
+    };
+    Frame::serialize(&delta, Some(Category::State)).map(|frame| Frame {
+        binary: Some(Arc::from(binary)),
+        state: true,
+        ..frame
+    })
+}
+
+// Encodes the broadcasts of a room on a task of their own, one at a time so they keep their order.
+// The task ends once the game manager drops the sender
+pub fn spawn(channel: broadcast::Sender<Frame>) -> mpsc::UnboundedSender<Broadcast> {
+    let (encoder, mut broadcasts) = mpsc::unbounded_channel::<Broadcast>();
+
+    tokio::spawn(async move {
+        while let Some(broadcast) = broadcasts.recv().await {
+            let frame = if broadcast.entities() > BLOCKING_ENTITIES {
+                match tokio::task::spawn_blocking(move || broadcast.encode()).await {
+                    Ok(frame) => frame,
+                    Err(error) => {
+                        println!("Error encoding state: {}", error);
+                        None
+                    }
+                }
+            } else {
+                broadcast.encode()
+            };
+
+            if let Some(frame) = frame {
+                if let Err(error) = channel.send(frame) {
+                    println!("Error broadcasting message: {}", error);
+                }
+            }
+        }
+    });
+
+    encoder
+}
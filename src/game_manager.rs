@@ -1,21 +1,201 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::Arc;
 
-use axum::extract::ws::{Message, WebSocket};
-use futures::stream::SplitSink;
-use futures::SinkExt;
-use tokio::time::{self, Duration};
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
 
-use crate::player::Player;
+use crate::accounts::{AccountError, Accounts, SessionStats};
+use crate::audit::{AuditEntry, AuditLog};
+use crate::bans::{self, Ban, BanTarget};
+use crate::binary::{self, Quantization};
+use crate::boss::{self, Boss, BossReward};
+use crate::checksum::{self, CHECKSUM_TICKS};
+use crate::config::GameConfig;
+use crate::crash::{self, CrashGuard};
+use crate::discord;
+use crate::encoder::{self, Broadcast, BLOCKING_ENTITIES};
+use crate::leaderboard::Leaderboard;
+use crate::map::{Map, Rect};
+use crate::metrics::{ConnectionMetrics, TickMetrics};
+use crate::parallel;
+use crate::player::{Player, PlayerPrivate, PlayerPublic, Players, DASH_SPEED};
+use crate::protocol::PROTOCOL_VERSION;
+use crate::room::{BiggestPlayer, GameMode, RoomInfo};
+use crate::rules::GameRules;
+use crate::schedule::ScheduledEvent;
+use crate::snapshot::{PlayerSnapshot, WorldSnapshot, SNAPSHOT_VERSION};
 use crate::vector::Vector2D;
+use crate::webhooks::{self, RoundScore, WebhookEvent};
 use rand::Rng;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+
+pub const TICK_MILLISECONDS: u64 = 10;
+pub const TICKS_PER_SECOND: u64 = 1000 / TICK_MILLISECONDS;
+
+// Ticks over which the load is averaged before the broadcast rate changes
+const LOAD_WINDOW_TICKS: u64 = 100;
+// Fractions of the tick budget above which the states are sent less often, and below which more often again
+const HIGH_LOAD: f64 = 0.8;
+const LOW_LOAD: f64 = 0.4;
+
+// How often the squad standings are sent in squad rooms
+const STANDINGS_TICKS: u64 = 100;
+
+// Streaks announced to the room
+pub const STREAK_MILESTONES: [u32; 3] = [3, 5, 10];
+
+// How often the room checks whether its population calls for other bounds
+const BOUNDS_CHECK_TICKS: u64 = 100;
+// Steps the map scale moves in, so a single player joining or leaving doesn't resize the room
+const MAP_SCALE_STEP: f32 = 0.1;
+// How far per second players outside shrunk bounds are pushed back in
+const BOUNDS_PUSH_SPEED: f32 = 100.0;
+
+// How often wandering food changes direction, and by how many radians at most
+const WANDER_TICKS: u64 = 50;
+const WANDER_TURN: f32 = 1.0;
+// Wandering food is worth this many times the mass of still food of the same size
+const WANDERING_FOOD_MASS: f32 = 3.0;
+
+// How often the position and velocity of the boss are sent
+const BOSS_UPDATE_TICKS: u64 = 10;
+
+// How long players restored from a snapshot wait for their owner to join again with the same name
+const DETACHED_PLAYER_SECONDS: u64 = 60;
+
+// Messages waiting to be written to a connection, beyond this the connection is too slow and messages are dropped
+pub const PLAYER_QUEUE_LENGTH: usize = 32;
+
+// Players listed in the round results sent to the webhooks
+const ROUND_TOP_SCORES: usize = 3;
+
+// Queue read by the writer task of a connection
+pub type PlayerSocket = mpsc::Sender<Frame>;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum PlayerCommand {
-    Move { position: Vector2D },
-    Join { name: String },
+    // `client_time` is when the move was sent, in server unix milliseconds as estimated with
+    // `TimeSyncRequest`, see `GameConfig::max_rewind_milliseconds`
+    Move {
+        position: Vector2D,
+        #[serde(default)]
+        client_time: Option<f64>,
+    },
+    // `token` is the one handed out when registering an account, guests leave it out.
+    // `skin` has to be in that account's inventory
+    Join {
+        name: String,
+        #[serde(default)]
+        token: Option<String>,
+        #[serde(default)]
+        skin: Option<String>,
+        // State updates per second, for clients on slow networks. Anything at or above the tick rate
+        // gets every frame
+        #[serde(default)]
+        update_rate: Option<u32>,
+    },
+    Rename {
+        name: String,
+    },
+    // Moves towards `direction` every tick at `throttle` times the full speed, from 0 to 1, instead of
+    // towards a point. For joysticks and gamepads, a `Move` goes back to targeting points
+    Steer {
+        direction: Vector2D,
+        throttle: f32,
+    },
+    // A short burst of speed for some mass, see `player::DASH_COOLDOWN`
+    Dash,
+    // Cheats, only accepted in sandbox rooms
+    SetMass {
+        mass: f32,
+    },
+    Teleport {
+        position: Vector2D,
+    },
+    // Makes this connection an admin when the token matches `admin_token` in the config
+    Login {
+        token: String,
+    },
+    Admin {
+        command: AdminCommand,
+    },
+    // Follows another player, for connections that aren't playing
+    Spectate {
+        target_id: u32,
+    },
+    // Confirms that the keyframe of `tick` was applied
+    AckKeyframe {
+        tick: u64,
+    },
+    // Asks for a keyframe right away, for clients that noticed a gap in the ticks of the deltas
+    RequestKeyframe,
+    // Asks for everything needed to rebuild the view from scratch: the welcome with the current map,
+    // the phase, the spectated player and a keyframe. For clients that lost messages, like a tab that
+    // was in the background. Limited like `RequestKeyframe`
+    RequestSnapshot,
+    // Starts a clock sync round, `client_time` is echoed back in the response
+    TimeSyncRequest {
+        client_time: f64,
+    },
+    // Chooses which broadcasts reach this connection, every category is on by default.
+    // Replies to the connection's own commands always get through
+    Subscribe {
+        categories: Vec<Category>,
+    },
+    Unsubscribe {
+        categories: Vec<Category>,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum AdminCommand {
+    Kick {
+        id: u32,
+    },
+    Announce {
+        text: String,
+        severity: Severity,
+    },
+    // Warns the players every few seconds, then ends the round and refuses joins until `EndMaintenance`
+    StartMaintenance {
+        seconds: u64,
+    },
+    EndMaintenance,
+    Pause,
+    Resume,
+    // Adds or removes a cosmetic from a registered account's inventory
+    GrantSkin {
+        username: String,
+        skin: String,
+    },
+    RevokeSkin {
+        username: String,
+        skin: String,
+    },
+    // Bans an address or account for `minutes`, or for good without them, and kicks the matching
+    // connections of this room. The other rooms turn them away when they next connect or join
+    Ban {
+        target: BanTarget,
+        minutes: Option<u64>,
+        reason: String,
+    },
+    Unban {
+        target: BanTarget,
+    },
+    // Answered with `MessageToClient::BanList`
+    ListBans,
+    // Answered with `MessageToClient::AuditLog`, the latest `limit` admin commands of every room
+    AuditLog {
+        limit: u32,
+    },
+    // Changes a single value of this room's config, `key` is the name of the field in `config.json`
+    SetConfig {
+        key: String,
+        value: serde_json::Value,
+    },
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -27,8 +207,47 @@ pub struct PlayerMessage {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum InternalCommand {
     Update,
-    AddPlayer { id: u32, name: String },
-    RemovePlayer { id: u32 },
+    AddPlayer {
+        id: u32,
+        name: String,
+        token: Option<String>,
+        skin: Option<String>,
+    },
+    RemovePlayer {
+        id: u32,
+    },
+    // Registers the socket of a new connection so messages can be sent directly to that player.
+    // `ip` is the client's address, behind trusted proxies the one they forwarded
+    #[serde(skip)]
+    Connect {
+        id: u32,
+        socket: PlayerSocket,
+        ip: Option<IpAddr>,
+        // What the writer of the connection skips
+        filter: Arc<FrameFilter>,
+    },
+    // Tears down a closed connection, removing its player if it joined
+    Disconnect {
+        id: u32,
+    },
+    // Sends a full state to a connection that missed broadcasts
+    Resync {
+        id: u32,
+    },
+    ReloadConfig {
+        config: Box<GameConfig>,
+    },
+    // Freezes the simulation while connections keep being served
+    Pause,
+    Resume,
+    // Saves the world to disk and stops the room
+    Shutdown,
+    // Replies with the world as JSON for `/debug/state`, or None when `token` isn't the admin token
+    #[serde(skip)]
+    DumpState {
+        token: String,
+        reply: mpsc::Sender<Option<serde_json::Value>>,
+    },
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -42,82 +261,735 @@ pub enum MessageToClient {
     JoinSuccess {
         id: u32,
     },
+    LoginSuccess,
+    // Sent after `JoinSuccess` with what clients need to know about the server and the room
+    Welcome {
+        protocol_version: u32,
+        tick_milliseconds: u64,
+        width: f32,
+        height: f32,
+        // Static layout, sent once since it never changes during a round
+        map: Box<Map>,
+        // How binary state deltas are packed on this map
+        quantization: Quantization,
+        // A player can eat another whose radius times this ratio is smaller than its own
+        eat_ratio: f32,
+        // Part of the map played on, smaller than the map while the room is quiet
+        bounds: Rect,
+        // None for the welcome sent on connect, before joining
+        player: Option<PlayerPublic>,
+    },
     PlayerEaten {
         id: u32,
+        by: u32,
+    },
+    // The room grew or shrank with its population, players outside are pushed inside
+    BoundsChanged {
+        bounds: Rect,
+    },
+    // Whoever eats `id` gains `mass` on top of what eating them gives
+    BountyPlaced {
+        id: u32,
+        mass: f32,
+    },
+    // `by` ate the target, or is null when the target left
+    BountyClaimed {
+        id: u32,
+        by: Option<u32>,
+    },
+    // Also sent to players joining while the boss is around
+    BossSpawned {
+        boss: Boss,
+    },
+    BossMoved {
+        boss: Boss,
+    },
+    // The mass each player got for the chunks of the boss they ate
+    BossDefeated {
+        rewards: Vec<BossReward>,
+    },
+    // The player reached one of the `STREAK_MILESTONES` eats without dying
+    KillStreak {
+        id: u32,
+        streak: u32,
+    },
+    // The player disconnected
+    PlayerLeft {
+        id: u32,
+    },
+    // Combined mass of every squad, sent periodically in squad rooms
+    SquadStandings {
+        squads: Vec<SquadScore>,
+    },
+    // The last member of the squad was removed
+    SquadEliminated {
+        squad: u32,
+    },
+    // Maintenance warnings, event notices and the message of the day
+    Announcement {
+        text: String,
+        severity: Severity,
+    },
+    // The connection is spectating, following `target` or with a free camera when it's `null`
+    Spectating {
+        target: Option<u32>,
+    },
+    // Sent on join and whenever day turns into night or back, clients should draw nothing beyond `vision_radius`
+    PhaseChanged {
+        phase: Phase,
+        vision_radius: f32,
+    },
+    // Every player is back in the lobby, the next round is played on `map`
+    RoundOver {
+        map: Box<Map>,
+        quantization: Quantization,
+    },
+    // A round without respawns got its players, nobody else joins until it is won
+    RoundStarted {
+        players: u32,
+    },
+    // The last player left, null when the last ones were eaten together or left
+    RoundWon {
+        id: Option<u32>,
+    },
+    // Sent when an admin pauses or resumes the room
+    GamePaused {
+        paused: bool,
+    },
+    // Sent only to the player whose command was rejected
+    // `command_ref` names the command that was rejected, None for errors that aren't replies to one
+    Error {
+        code: ErrorCode,
+        message: String,
+        command_ref: Option<String>,
+    },
+    // Active bans, for admins
+    BanList {
+        bans: Vec<Ban>,
+    },
+    AuditLog {
+        entries: Vec<AuditEntry>,
+    },
+    // Times are Unix milliseconds on the server clock. With the client's own send and receive times they
+    // give the offset and round trip like NTP, and `tick_time` says when the state of `tick` was sent
+    TimeSyncResponse {
+        client_time: f64,
+        server_receive_time: f64,
+        server_send_time: f64,
+        tick: u64,
+        tick_time: f64,
+    },
+    PlayerRenamed {
+        id: u32,
+        name: String,
     },
+    // Keyframe, the whole world at `tick`
+    // `sequence` counts the states and deltas sent to this connection, a gap means frames were lost
     State {
-        players: Vec<Player>,
+        #[serde(default)]
+        sequence: u32,
+        tick: u64,
+        // See `checksum::world`
+        checksum: u32,
+        players: Vec<PlayerPublic>,
+        food: Vec<Food>,
+    },
+    // Players that moved or changed size since the previous frame
+    StateDelta {
+        #[serde(default)]
+        sequence: u32,
+        tick: u64,
+        // Every `CHECKSUM_TICKS` ticks
+        #[serde(default)]
+        checksum: Option<u32>,
+        players: Vec<PlayerPublic>,
+        removed_players: Vec<u32>,
+    },
+    // Sent to the owner of a player alone, with every keyframe
+    PrivateState {
+        tick: u64,
+        player: PlayerPrivate,
+    },
+    // Food changes between full states, so clients don't need the whole list every time
+    FoodSpawned {
+        food: Vec<Food>,
+    },
+    FoodEaten {
+        id: u32,
+        by: u32,
+    },
+    // Wandering food that turned or bounced off the bounds, clients move it along its velocity in between
+    FoodMoved {
         food: Vec<Food>,
     },
 }
 
+// Why a command was rejected, for frontends to tell the user. `message` has the details
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ErrorCode {
+    // The message isn't a command at all
+    InvalidCommand,
+    InvalidName,
+    InvalidPosition,
+    InvalidValue,
+    NotJoined,
+    AlreadyJoined,
+    // Wrong or missing credentials, or the room needs an account
+    Unauthorized,
+    // Not allowed for this connection or in this room
+    Forbidden,
+    NotFound,
+    RateLimited,
+    RoomFull,
+    Maintenance,
+    Banned,
+    Kicked,
+    // The room plays without respawns and its round already started
+    RoundUnderway,
+}
+
+// Kinds of broadcasts a connection can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Category {
+    // Players and food
+    State,
+    // Squad standings
+    Leaderboard,
+    // Announcements
+    Chat,
+    // Eats, departures, renames, phases and rounds
+    Events,
+}
+
+impl Category {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl PlayerCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayerCommand::Move { .. } => "Move",
+            PlayerCommand::Join { .. } => "Join",
+            PlayerCommand::Rename { .. } => "Rename",
+            PlayerCommand::Steer { .. } => "Steer",
+            PlayerCommand::Dash => "Dash",
+            PlayerCommand::SetMass { .. } => "SetMass",
+            PlayerCommand::Teleport { .. } => "Teleport",
+            PlayerCommand::Login { .. } => "Login",
+            PlayerCommand::Admin { .. } => "Admin",
+            PlayerCommand::Spectate { .. } => "Spectate",
+            PlayerCommand::AckKeyframe { .. } => "AckKeyframe",
+            PlayerCommand::RequestKeyframe => "RequestKeyframe",
+            PlayerCommand::RequestSnapshot => "RequestSnapshot",
+            PlayerCommand::TimeSyncRequest { .. } => "TimeSyncRequest",
+            PlayerCommand::Subscribe { .. } => "Subscribe",
+            PlayerCommand::Unsubscribe { .. } => "Unsubscribe",
+        }
+    }
+}
+
+impl MessageToClient {
+    // None for replies to a single connection
+    pub fn category(&self) -> Option<Category> {
+        match self {
+            MessageToClient::State { .. }
+            | MessageToClient::StateDelta { .. }
+            | MessageToClient::PrivateState { .. }
+            | MessageToClient::FoodSpawned { .. }
+            | MessageToClient::FoodEaten { .. }
+            | MessageToClient::FoodMoved { .. }
+            | MessageToClient::BossMoved { .. } => Some(Category::State),
+            MessageToClient::SquadStandings { .. } => Some(Category::Leaderboard),
+            MessageToClient::Announcement { .. } => Some(Category::Chat),
+            MessageToClient::PlayerEaten { .. }
+            | MessageToClient::KillStreak { .. }
+            | MessageToClient::BountyPlaced { .. }
+            | MessageToClient::BoundsChanged { .. }
+            | MessageToClient::BountyClaimed { .. }
+            | MessageToClient::BossSpawned { .. }
+            | MessageToClient::BossDefeated { .. }
+            | MessageToClient::PlayerLeft { .. }
+            | MessageToClient::SquadEliminated { .. }
+            | MessageToClient::PhaseChanged { .. }
+            | MessageToClient::RoundStarted { .. }
+            | MessageToClient::RoundWon { .. }
+            | MessageToClient::RoundOver { .. }
+            | MessageToClient::GamePaused { .. }
+            | MessageToClient::PlayerRenamed { .. } => Some(Category::Events),
+            _ => None,
+        }
+    }
+}
+
+// Which frames the writer of a connection skips, changed by the game manager
+#[derive(Debug, Default)]
+pub struct FrameFilter {
+    // Set while the connection gets its own states at a lower rate, the broadcast ones are skipped then
+    pub own_states: AtomicBool,
+    // A bit per category the connection unsubscribed from
+    unsubscribed: AtomicU8,
+}
+
+impl FrameFilter {
+    pub fn set_subscribed(&self, category: Category, subscribed: bool) {
+        if subscribed {
+            self.unsubscribed
+                .fetch_and(!category.bit(), Ordering::Relaxed);
+        } else {
+            self.unsubscribed
+                .fetch_or(category.bit(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn allows(&self, frame: &Frame) -> bool {
+        frame
+            .category
+            .is_none_or(|category| self.unsubscribed.load(Ordering::Relaxed) & category.bit() == 0)
+    }
+
+    pub fn allows_broadcast(&self, frame: &Frame) -> bool {
+        !(frame.state && self.own_states.load(Ordering::Relaxed)) && self.allows(frame)
+    }
+}
+
+// A broadcast, serialized once for every connection. State deltas also come packed for binary clients
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub text: Arc<str>,
+    pub binary: Option<Arc<[u8]>>,
+    pub category: Option<Category>,
+    // Full states and deltas, which downsampled connections get separately
+    pub state: bool,
+}
+
+impl Frame {
+    pub fn text(text: String) -> Frame {
+        Frame {
+            text: Arc::from(text),
+            binary: None,
+            category: None,
+            state: false,
+        }
+    }
+
+    // A copy of a state frame carrying the sequence number of the connection it's sent to. The number is
+    // spliced into the shared text, `{"State":{...` becomes `{"State":{"sequence":1,...`
+    pub fn sequenced(&self, sequence: u32) -> Frame {
+        let text = match self.text.find(":{") {
+            Some(index) => {
+                let (head, tail) = self.text.split_at(index + 2);
+                Arc::from(format!("{}\"sequence\":{},{}", head, sequence, tail))
+            }
+            None => self.text.clone(),
+        };
+        let binary = self.binary.as_ref().map(|binary| {
+            let mut bytes = binary.to_vec();
+            binary::set_sequence(&mut bytes, sequence);
+            Arc::from(bytes)
+        });
+
+        Frame {
+            text,
+            binary,
+            category: self.category,
+            state: self.state,
+        }
+    }
+
+    // Errors are reported here, callers just skip the frame
+    pub fn serialize<T: serde::Serialize>(
+        message: &T,
+        category: Option<Category>,
+    ) -> Option<Frame> {
+        match serde_json::to_string(message) {
+            Ok(text) => Some(Frame {
+                text: Arc::from(text),
+                binary: None,
+                category,
+                state: false,
+            }),
+            Err(error) => {
+                println!("Error serializing message: {}", error);
+                None
+            }
+        }
+    }
+}
+
+// Keyframes of a connection, so requests for early ones can't flood the room
+#[derive(Debug, Clone, Copy, Default)]
+struct KeyframeAcks {
+    // Tick of the latest keyframe the client confirmed
+    acked: u64,
+    // Tick of the last keyframe sent only to this client, on request
+    requested: Option<u64>,
+    // Ticks between the latest keyframe and its ack, in milliseconds. Moves are never rewound further
+    rtt_milliseconds: Option<f64>,
+}
+
+// The state frames of a connection that asked for fewer updates than the tick rate
+#[derive(Debug)]
+struct UpdateRate {
+    // Ticks between two updates, 1 for connections following the broadcast
+    stride: u64,
+    // Position and radius of every player as of the last update this connection got
+    sent_players: HashMap<u32, (Vector2D, f32)>,
+    // A keyframe was broadcast since the last update, so the next one is a full state too
+    keyframe_due: bool,
+}
+
+// Where a connection is in its lifecycle, tracked from connect to disconnect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    // Connected but hasn't joined yet, or left the game without disconnecting
+    Lobby,
+    Playing,
+    // Eaten and watching the game until they join again, `target` is the player they follow or `None` for a free camera
+    Spectating { target: Option<u32> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Phase {
+    Day,
+    Night,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SquadScore {
+    pub squad: u32,
+    pub mass: f32,
+    pub players: Vec<u32>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Food {
+    // Snapshots written before food had ids don't have it, the ids are reassigned when restoring anyway
+    #[serde(default)]
+    pub id: u32,
     pub position: Vector2D,
     pub radius: f32,
+    // Units per second for wandering food, None for food that stays put
+    #[serde(default)]
+    pub velocity: Option<Vector2D>,
+}
+
+impl Food {
+    // Radius of still food with the same mass
+    fn worth(&self) -> f32 {
+        match self.velocity {
+            Some(_) => self.radius * WANDERING_FOOD_MASS.sqrt(),
+            None => self.radius,
+        }
+    }
 }
 
 pub struct GameManager {
-    pub food: Vec<Food>,
-    pub players: Vec<Player>,
-    // Send messages to all the players
-    pub broadcast_channel: tokio::sync::broadcast::Sender<MessageToClient>,
+    pub info: Arc<RoomInfo>,
+    pub config: GameConfig,
+    pub rules: Box<dyn GameRules>,
+    // Shared copy-on-write with the state snapshots like the players
+    pub food: Arc<Vec<Food>>,
+    pub players: Players,
+    // Players restored from a snapshot that haven't been claimed yet
+    pub detached_players: Vec<PlayerSnapshot>,
+    pub detached_until: Instant,
+    pub tick: u64,
+    // When the state of `tick` was sent, in Unix milliseconds
+    pub tick_time: f64,
+    // Position and radius of every player as of the last state frame
+    pub sent_players: HashMap<u32, (Vector2D, f32)>,
+    // Time spent in the ticks of the current load window
+    tick_load: Duration,
+    pub next_food_id: u32,
+    // Set when a player joins so they don't wait for the next periodic full state
+    pub full_state_pending: bool,
+    // Send messages to all the players, serialized once before being shared with every connection
+    pub broadcast_channel: tokio::sync::broadcast::Sender<Frame>,
+    // Encodes the broadcasts off the tick once the room runs, they are encoded in place without it
+    encoder: Option<mpsc::UnboundedSender<Broadcast>>,
     // Receive and transmit commands, either from the websocket or from the update loop
     // the commands can be either internal or player commands
     pub command_rx: Receiver<Command>,
     pub command_tx: Sender<Command>,
-    // Players sockets, used to send messages to specific players
-    pub players_sockets: Arc<Mutex<HashMap<u32, Arc<Mutex<SplitSink<WebSocket, Message>>>>>>,
+    // Players sockets, used to send messages to specific players.
+    // Only the game manager task touches it, so sending doesn't need a registry-wide lock
+    pub players_sockets: HashMap<u32, PlayerSocket>,
+    pub connections: HashMap<u32, ConnectionState>,
+    // Connections that logged in with the admin token
+    pub admins: HashSet<u32>,
+    // Client addresses by connection id, for the logs
+    pub addresses: HashMap<u32, IpAddr>,
+    // Best mass of the last life of connections whose player was eaten, for `GameConfig::respawn_mass_fraction`
+    pub respawn_masses: HashMap<u32, f32>,
+    keyframe_acks: HashMap<u32, KeyframeAcks>,
+    update_rates: HashMap<u32, UpdateRate>,
+    frame_filters: HashMap<u32, Arc<FrameFilter>>,
+    // Kicked connections, whose commands are ignored until their socket closes
+    kicked: HashSet<u32>,
+    // Messages dropped for connections whose queue is full, logged once when it fills up and once when it drains.
+    // Sending only borrows the game manager, hence the cell
+    slow_connections: RefCell<HashMap<u32, u64>>,
+    // Name of the player command being executed, referenced by the errors it causes
+    handling: Option<&'static str>,
+    // When the round ends for maintenance, and the last countdown second announced
+    pub maintenance_at: Option<Instant>,
+    pub maintenance_announced: Option<u64>,
+    // While set, ticks skip collisions, decay, movement and the timed checks
+    pub paused: bool,
+    // The timers are pushed back by the time spent paused on resume
+    pub paused_at: Instant,
+    pub map: Arc<Map>,
+    pub round_started_at: Instant,
+    // Set once a round without respawns started, see `GameRules::allows_respawns`
+    pub round_underway: bool,
+    pub phase: Phase,
+    pub phase_started_at: Instant,
+    // The biggest player and since when, a bounty is put on them once they stay on top for `bounty_seconds`
+    pub leader: Option<(u32, Instant)>,
+    // Fraction of the map width and height played on, see `GameConfig::min_map_scale`
+    pub map_scale: f32,
+    // Centers of the food patches, see `GameConfig::food_clusters`. Picked again when the map or the count changes
+    pub food_clusters: Vec<Vector2D>,
+    pub bounty: Option<u32>,
+    pub boss: Option<Boss>,
+    // Recent `(tick, [(id, position)])` of the players, as long as `max_rewind_milliseconds`
+    pub position_history: VecDeque<(u64, Vec<(u32, Vector2D)>)>,
+    // Names of the scheduled events under way and what they add up to
+    pub active_events: Vec<String>,
+    pub mass_multiplier: f32,
+    pub decay_disabled: bool,
+    // When the last boss spawned or was beaten
+    pub boss_changed_at: Instant,
+    // Tick duration histogram, shared by every room and the metrics endpoint
+    pub tick_metrics: Arc<TickMetrics>,
+    pub connection_metrics: Arc<ConnectionMetrics>,
+    // Best masses of finished sessions, shared with the leaderboard endpoint
+    pub leaderboard: Arc<std::sync::Mutex<Leaderboard>>,
+    pub accounts: Arc<std::sync::Mutex<Accounts>>,
+    pub audit_log: AuditLog,
 }
 
 impl GameManager {
-    pub fn new(broadcast_channel: broadcast::Sender<MessageToClient>) -> GameManager {
+    pub fn new(
+        info: Arc<RoomInfo>,
+        config: GameConfig,
+        broadcast_channel: broadcast::Sender<Frame>,
+        tick_metrics: Arc<TickMetrics>,
+        connection_metrics: Arc<ConnectionMetrics>,
+        leaderboard: Arc<std::sync::Mutex<Leaderboard>>,
+        accounts: Arc<std::sync::Mutex<Accounts>>,
+    ) -> GameManager {
         let (command_tx, command_rx) = mpsc::channel::<Command>(100);
-        let food = GameManager::generate_food(50);
 
-        GameManager {
-            food,
-            players: Vec::new(),
+        let mut game_manager = GameManager {
+            rules: info.mode.rules(),
+            map: info.map(),
+            round_started_at: Instant::now(),
+            round_underway: false,
+            phase: Phase::Day,
+            phase_started_at: Instant::now(),
+            leader: None,
+            map_scale: 1.0,
+            food_clusters: Vec::new(),
+            bounty: None,
+            boss: None,
+            position_history: VecDeque::new(),
+            active_events: Vec::new(),
+            mass_multiplier: 1.0,
+            decay_disabled: false,
+            boss_changed_at: Instant::now(),
+            audit_log: AuditLog::open(info.audit_log_path.clone()),
+            info,
+            config,
+            food: Arc::new(Vec::new()),
+            players: Players::new(),
+            detached_players: Vec::new(),
+            detached_until: Instant::now(),
+            tick: 0,
+            tick_time: unix_millis(),
+            sent_players: HashMap::new(),
+            tick_load: Duration::ZERO,
+            next_food_id: 0,
+            full_state_pending: true,
             broadcast_channel,
+            encoder: None,
             command_rx,
             command_tx,
-            players_sockets: Arc::new(Mutex::new(HashMap::new())),
+            players_sockets: HashMap::new(),
+            connections: HashMap::new(),
+            admins: HashSet::new(),
+            respawn_masses: HashMap::new(),
+            addresses: HashMap::new(),
+            keyframe_acks: HashMap::new(),
+            update_rates: HashMap::new(),
+            frame_filters: HashMap::new(),
+            kicked: HashSet::new(),
+            slow_connections: RefCell::new(HashMap::new()),
+            handling: None,
+            maintenance_at: None,
+            maintenance_announced: None,
+            paused: false,
+            paused_at: Instant::now(),
+            tick_metrics,
+            connection_metrics,
+            leaderboard,
+            accounts,
+        };
+
+        game_manager.map_scale = game_manager.target_map_scale();
+        let food_target = game_manager.food_target() as u32;
+        game_manager.food = Arc::new(game_manager.generate_food(food_target));
+        game_manager.restore_snapshot();
+        game_manager
+    }
+
+    fn restore_snapshot(&mut self) {
+        let snapshot_path = match &self.info.snapshot_path {
+            Some(snapshot_path) => snapshot_path,
+            None => return,
+        };
+
+        let snapshot = match WorldSnapshot::take(snapshot_path) {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        println!(
+            "Restoring room {}: players={} food={}",
+            self.info.id,
+            snapshot.players.len(),
+            snapshot.food.len()
+        );
+
+        let mut food = snapshot.food;
+        for food in food.iter_mut() {
+            food.id = self.next_food_id();
         }
+        self.food = Arc::new(food);
+        self.detached_players = snapshot.players;
+        self.detached_until = Instant::now() + Duration::from_secs(DETACHED_PLAYER_SECONDS);
     }
 
-    fn generate_food(amount: u32) -> Vec<Food> {
+    fn save_snapshot(&self) {
+        let snapshot_path = match &self.info.snapshot_path {
+            Some(snapshot_path) => snapshot_path,
+            None => return,
+        };
+
+        let players = self
+            .players
+            .iter()
+            .map(PlayerSnapshot::from_player)
+            .chain(self.detached_players.iter().cloned())
+            .collect();
+
+        let snapshot = WorldSnapshot {
+            version: SNAPSHOT_VERSION,
+            players,
+            food: self.food.to_vec(),
+        };
+
+        snapshot.save(snapshot_path);
+    }
+
+    fn next_food_id(&mut self) -> u32 {
+        let id = self.next_food_id;
+        self.next_food_id = self.next_food_id.wrapping_add(1);
+        id
+    }
+
+    fn generate_food(&mut self, amount: u32) -> Vec<Food> {
         let mut rng = rand::thread_rng();
 
         // generates a vector of food
         let mut food = Vec::new();
         for _ in 0..amount {
             let radius: f32 = rng.gen_range(2.0..6.0);
-            let x: f32 = rng.gen_range(radius..800.0 - radius);
-            let y: f32 = rng.gen_range(radius..600.0 - radius);
+            let position = self.food_position(radius);
+            let velocity = (rng.gen::<f32>() < self.config.wandering_food_chance).then(|| {
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                Vector2D::new(angle.cos(), angle.sin()) * self.config.wandering_food_speed
+            });
 
             food.push(Food {
-                position: Vector2D::new(x, y),
+                id: self.next_food_id(),
+                position,
                 radius,
+                velocity,
             });
         }
         food
     }
 
-    fn send_string_to_player(&self, id: u32, message: String) {
-        let players_sockets = self.players_sockets.clone();
+    fn food_position(&mut self, radius: f32) -> Vector2D {
+        let bounds = self.bounds();
+        if self.config.food_clusters > 0 {
+            if self.food_clusters.len() != self.config.food_clusters as usize {
+                self.food_clusters = self.map.cluster_centers(self.config.food_clusters);
+            }
 
-        tokio::spawn(async move {
-            let players_sockets = players_sockets.lock().await;
-            let player_socket = players_sockets.get(&id);
+            // Closer to the center more often, so patches fade out at their edges
+            let mut rng = rand::thread_rng();
+            let center = self.food_clusters[rng.gen_range(0..self.food_clusters.len())];
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let distance = rng.gen_range(0.0..=1.0f32) * self.config.food_cluster_radius.max(0.0);
+            let position = center + Vector2D::new(angle.cos(), angle.sin()) * distance;
+            if bounds.contains(position) {
+                return position;
+            }
+        }
+
+        let position = self.map.food_point(radius);
+        if bounds.contains(position) {
+            return position;
+        }
+        bounds.random_point(radius)
+    }
 
-            if let Some(player_socket) = player_socket {
-                let mut player_socket = player_socket.lock().await;
+    fn send_string_to_player(&self, id: u32, message: String) {
+        self.send_frame_to_player(id, Frame::text(message));
+    }
 
-                if let Err(error) = player_socket.send(Message::Text(message)).await {
-                    println!("Error sending message to player: {}", error);
+    fn send_frame_to_player(&self, id: u32, frame: Frame) {
+        let player_socket = match self.players_sockets.get(&id) {
+            Some(player_socket) => player_socket,
+            None => return,
+        };
+
+        match player_socket.try_send(frame) {
+            Ok(()) => {
+                let mut slow_connections = self.slow_connections.borrow_mut();
+                if let Some(dropped) = slow_connections.remove(&id) {
+                    println!(
+                        "event=slow_connection_recovered connection={} room={} dropped={}",
+                        id, self.info.id, dropped
+                    );
                 }
             }
-        });
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                let mut slow_connections = self.slow_connections.borrow_mut();
+                let dropped = slow_connections.entry(id).or_insert(0);
+                if *dropped == 0 {
+                    println!(
+                        "event=slow_connection connection={} room={}",
+                        id, self.info.id
+                    );
+                }
+                *dropped += 1;
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                println!("Error sending message to player {}: connection closed", id);
+            }
+        }
     }
 
     pub fn send_message_to_player(&self, id: u32, message: MessageToClient) {
@@ -125,7 +997,7 @@ impl GameManager {
 
         match msg_string {
             Ok(msg_string) => {
-                self.send_string_to_player(id, String::from(msg_string));
+                self.send_string_to_player(id, msg_string);
             }
             Err(error) => {
                 println!("Error serializing message: {}", error);
@@ -134,190 +1006,2465 @@ impl GameManager {
     }
 
     pub fn get_players(&self) -> Vec<Player> {
-        self.players.clone()
+        self.players.to_vec()
+    }
+
+    // Encodes the big states on a task of their own from now on, needs a tokio runtime
+    pub fn spawn_encoder(&mut self) {
+        self.encoder = Some(encoder::spawn(self.broadcast_channel.clone()));
     }
 
     pub fn start(self) {
-        self.start_update_loop(10);
         GameManager::listen_to_commands(self);
     }
 
+    // Runs the ticks on the same task that handles the commands, so updates don't wait behind the command queue
     pub fn listen_to_commands(mut game_manager: GameManager) {
+        game_manager.spawn_encoder();
         tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_millis(TICK_MILLISECONDS));
+            // Ticks missed because of a slow tick are dropped instead of firing in a burst to catch up
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            let _crash_guard = CrashGuard {
+                info: game_manager.info.clone(),
+                accounts: game_manager.accounts.clone(),
+                leaderboard: game_manager.leaderboard.clone(),
+            };
+            let room_id = game_manager.info.id;
+
             loop {
-                match game_manager.command_rx.recv().await {
-                    Some(command) => match command {
-                        Command::InternalCommand(internal_command) => {
-                            game_manager.execute_internal_command(internal_command);
+                let tick = game_manager.tick;
+                tokio::select! {
+                    _ = interval.tick() => crash::in_room(room_id, tick, || {
+                        game_manager.execute_internal_command(InternalCommand::Update);
+                    }),
+                    command = game_manager.command_rx.recv() => match command {
+                        Some(Command::InternalCommand(InternalCommand::Shutdown)) => {
+                            game_manager.save_snapshot();
+                            break;
                         }
-                        Command::PlayerCommand(player_command) => {
+                        Some(Command::InternalCommand(internal_command)) => crash::in_room(room_id, tick, || {
+                            game_manager.execute_internal_command(internal_command);
+                        }),
+                        Some(Command::PlayerCommand(player_command)) => crash::in_room(room_id, tick, || {
                             game_manager.execute_player_command(player_command);
+                        }),
+                        None => {
+                            println!("Error receiving command");
+                            break;
                         }
                     },
-                    None => {
-                        println!("Error receiving command");
-                        break;
-                    }
                 }
             }
         });
     }
 
-    pub fn start_update_loop(&self, milliseconds: u64) {
-        let command_tx_internal = self.command_tx.clone();
-        tokio::spawn(async move {
-            loop {
-                time::sleep(Duration::from_millis(milliseconds)).await;
-                if let Err(error) = command_tx_internal
-                    .send(Command::InternalCommand(InternalCommand::Update))
-                    .await
-                {
-                    println!("Error sending update command: {}", error);
-                    break;
-                }
-            }
-        });
-    }
+    // Serializes the message once and shares it with every connection
+    pub fn broadcast_message(&self, message: MessageToClient) {
+        // Sending only fails when nobody is connected, which isn't worth reporting
+        if self.broadcast_channel.receiver_count() == 0 {
+            return;
+        }
 
-    pub fn send_state(&self) {
-        let players = self.get_players();
-        if let Err(error) = self.broadcast_channel.send(MessageToClient::State {
-            players: players.clone(),
-            food: self.food.clone(),
-        }) {
-            println!("Error sending state: {}", error);
+        if let Some(frame) = Frame::serialize(&message, message.category()) {
+            self.broadcast_frame(frame);
         }
     }
 
-    pub fn execute_internal_command(&mut self, internal_command: InternalCommand) {
-        match internal_command {
-            InternalCommand::Update => {
-                self.update();
-                self.send_state();
-            }
-            InternalCommand::AddPlayer { id, name } => {
-                self.add_player(Player::new(id, name));
-            }
-            InternalCommand::RemovePlayer { id } => {
-                self.remove_player(id);
+    fn broadcast_frame(&self, frame: Frame) {
+        self.broadcast(Broadcast::Frame(frame));
+    }
+
+    fn broadcast(&self, broadcast: Broadcast) {
+        let broadcast = match &self.encoder {
+            Some(encoder) => match encoder.send(broadcast) {
+                Ok(()) => return,
+                Err(error) => error.0,
+            },
+            None => broadcast,
+        };
+
+        if let Some(frame) = broadcast.encode() {
+            if let Err(error) = self.broadcast_channel.send(frame) {
+                println!("Error broadcasting message: {}", error);
             }
         }
     }
 
-    pub fn execute_player_command(&mut self, player_message: PlayerMessage) {
-        match player_message.command {
-            PlayerCommand::Move { position } => {
-                self.move_player(player_message.id, position);
+    pub fn send_state(&mut self) {
+        self.tick += 1;
+        self.tick_time = unix_millis();
+
+        // A keyframe falling between two broadcasts goes out with the next one
+        let keyframe_ticks = self.config.keyframe_ticks.max(1);
+        self.full_state_pending |= self.tick.is_multiple_of(keyframe_ticks);
+        let stride = u64::from(self.info.broadcast_stride.load(Ordering::Relaxed)).max(1);
+        let broadcasting = self.tick.is_multiple_of(stride);
+        let keyframe = broadcasting && self.full_state_pending;
+
+        if broadcasting && self.broadcast_channel.receiver_count() > 0 {
+            if keyframe {
+                if self.players.len() + self.food.len() > BLOCKING_ENTITIES {
+                    self.broadcast(Broadcast::State {
+                        tick: self.tick,
+                        checksum: self.checksum(),
+                        players: self.players.snapshot(),
+                        food: self.food.clone(),
+                    });
+                } else if let Some(frame) = self.full_state_frame() {
+                    self.broadcast_frame(frame);
+                }
+            } else if let Some((players, removed_players)) = self.state_changes(&self.sent_players)
+            {
+                let quantization = Quantization::for_map(&self.map);
+                if players.len() > BLOCKING_ENTITIES {
+                    let changed = players
+                        .iter()
+                        .filter_map(|player| self.players.index_of(player.id))
+                        .collect();
+                    self.broadcast(Broadcast::StateDelta {
+                        tick: self.tick,
+                        checksum: self.delta_checksum(),
+                        quantization,
+                        players: self.players.snapshot(),
+                        changed,
+                        removed_players,
+                    });
+                } else if let Some(frame) = encoder::state_delta(
+                    self.tick,
+                    self.delta_checksum(),
+                    &quantization,
+                    players,
+                    removed_players,
+                ) {
+                    self.broadcast_frame(frame);
+                }
             }
-            PlayerCommand::Join { name } => {
-                self.execute_internal_command(InternalCommand::AddPlayer {
-                    id: player_message.id,
-                    name,
-                })
+        }
+
+        if broadcasting {
+            record_positions(&self.players, &mut self.sent_players);
+            self.full_state_pending = false;
+        }
+        self.send_downsampled_states(keyframe);
+        if keyframe {
+            for player in self.players.iter() {
+                self.send_private_state(player);
             }
         }
     }
 
-    pub fn add_player(&mut self, player: Player) {
-        self.send_message_to_player(player.id, MessageToClient::JoinSuccess { id: player.id });
-        self.players.push(player);
+    fn send_private_state(&self, player: &Player) {
+        let message = MessageToClient::PrivateState {
+            tick: self.tick,
+            player: player.private(self.time_scale()),
+        };
+        if let Some(frame) = Frame::serialize(&message, message.category()) {
+            self.send_frame_to_player(player.id, frame);
+        }
     }
 
-    pub fn remove_player(&mut self, id: u32) {
-        self.players.retain(|player| player.id != id);
-        self.send_message_to_player(id, MessageToClient::PlayerEaten { id });
+    fn full_state_frame(&self) -> Option<Frame> {
+        encoder::full_state(self.tick, self.checksum(), &self.players, &self.food)
     }
 
-    pub fn move_player(&mut self, id: u32, position: Vector2D) {
-        for player in &mut self.players {
-            if player.id == id {
-                player.move_towards(position);
-                return;
-            }
-        }
+    // Wandering food is left out, clients only estimate where it is between `FoodMoved` messages
+    pub fn checksum(&self) -> u32 {
+        checksum::world(
+            self.players
+                .iter()
+                .map(|player| (player.id, player.position, player.radius)),
+            self.food.iter().filter(|food| food.velocity.is_none()),
+        )
     }
 
-    pub fn check_collision(&mut self) {
-        for i in 0..self.players.len() {
-            for j in 0..self.players.len() {
-                let player = &self.players[i];
-                let other_player = &self.players[j];
+    // Only some deltas carry the checksum, hashing the food every tick would be wasted
+    fn delta_checksum(&self) -> Option<u32> {
+        self.tick
+            .is_multiple_of(CHECKSUM_TICKS)
+            .then(|| self.checksum())
+    }
 
-                if player.id != other_player.id {
-                    let distance = (player.position - other_player.position).magnitude();
-                    if distance < player.radius + other_player.radius {
-                        let radius_after_eat = Player::radius_after_eat(player, other_player);
-                        if player.radius > other_player.radius {
-                            self.players[i].radius = radius_after_eat;
-                            self.players[j].radius = 0.0;
-                        } else {
-                            self.players[j].radius = radius_after_eat;
-                            self.players[i].radius = 0.0;
-                        }
-                    }
-                }
-            }
+    fn send_full_state_to_player(&mut self, id: u32) {
+        if let Some(frame) = self.full_state_frame() {
+            self.send_frame_to_player(id, frame);
+        }
+        if let Some(player) = self.players.get(id) {
+            self.send_private_state(player);
+        }
+        if let Some(update_rate) = self.update_rates.get_mut(&id) {
+            record_positions(&self.players, &mut update_rate.sent_players);
+            update_rate.keyframe_due = false;
         }
     }
 
-    fn mass(radius: f32) -> f32 {
-        2.0 * radius.powf(2.0) * std::f32::consts::PI
+    // What changed since `sent_players`, as text and packed for binary clients. None if nothing did
+    fn state_delta_frame(&self, sent_players: &HashMap<u32, (Vector2D, f32)>) -> Option<Frame> {
+        let (players, removed_players) = self.state_changes(sent_players)?;
+        let quantization = Quantization::for_map(&self.map);
+        encoder::state_delta(
+            self.tick,
+            self.delta_checksum(),
+            &quantization,
+            players,
+            removed_players,
+        )
     }
 
-    fn radius_after_eat(radius1: f32, radius2: f32) -> f32 {
-        let combined_mass = GameManager::mass(radius2) + GameManager::mass(radius1);
-        (combined_mass / (2.0 * std::f32::consts::PI)).sqrt()
+    // The players that moved or changed size since `sent_players` and the ids of those that left
+    fn state_changes(
+        &self,
+        sent_players: &HashMap<u32, (Vector2D, f32)>,
+    ) -> Option<(Vec<&Player>, Vec<u32>)> {
+        let players: Vec<&Player> = self
+            .players
+            .iter()
+            .filter(|player| {
+                sent_players.get(&player.id) != Some(&(player.position, player.radius))
+            })
+            .collect();
+
+        let removed_players: Vec<u32> = sent_players
+            .keys()
+            .filter(|&&id| !self.players.contains(id))
+            .copied()
+            .collect();
+
+        if players.is_empty() && removed_players.is_empty() {
+            return None;
+        }
+        Some((players, removed_players))
     }
 
-    pub fn check_food_collision(&mut self) {
-        for i in (0..self.players.len()).rev() {
-            for j in (0..self.food.len()).rev() {
-                let player = &self.players[i];
-                let food = &self.food[j];
+    // Connections with a lower update rate get a full state or a delta against what they last saw
+    // every `stride` ticks, sent to them alone
+    fn send_downsampled_states(&mut self, keyframe: bool) {
+        let mut update_rates = std::mem::take(&mut self.update_rates);
 
-                let distance = (player.position - food.position).magnitude();
-                if distance < player.radius + food.radius {
-                    let combined = GameManager::radius_after_eat(player.radius, food.radius);
-                    self.players[i].radius = combined;
-                    self.food.remove(j);
-                }
+        for (&id, update_rate) in update_rates.iter_mut() {
+            if update_rate.stride <= 1 {
+                continue;
+            }
+            update_rate.keyframe_due |= keyframe;
+            if !self.tick.is_multiple_of(update_rate.stride) {
+                continue;
+            }
+
+            let frame = if update_rate.keyframe_due {
+                self.full_state_frame()
+            } else {
+                self.state_delta_frame(&update_rate.sent_players)
+            };
+            if let Some(frame) = frame {
+                self.send_frame_to_player(id, frame);
             }
+            record_positions(&self.players, &mut update_rate.sent_players);
+            update_rate.keyframe_due = false;
         }
+
+        self.update_rates = update_rates;
     }
 
-    pub fn update(&mut self) {
-        self.check_collision();
-        self.check_food_collision();
-        self.remove_dead_players();
-        self.check_food();
+    fn set_update_rate(&mut self, id: u32, updates_per_second: u32) {
+        let stride = (TICKS_PER_SECOND / u64::from(updates_per_second.max(1))).max(1);
+        if let Some(update_rate) = self.update_rates.get_mut(&id) {
+            update_rate.stride = stride;
+            update_rate.keyframe_due = true;
+        }
+        if let Some(filter) = self.frame_filters.get(&id) {
+            filter.own_states.store(stride > 1, Ordering::Relaxed);
+        }
     }
 
-    fn check_food(&mut self) {
-        // Check if there are enough food
-        if self.food.len() < 50 {
-            let difference: u32 = (50 - self.food.len()) as u32;
-            let extra_food = GameManager::generate_food(difference);
-            self.food.extend(extra_food);
-        }
-    }
-
-    pub fn remove_dead_players(&self) {
-        for index in 0..self.players.len() {
-            let player = &self.players[index];
-            if player.radius <= 0.01 {
-                let command_tx = self.command_tx.clone();
-                let id = player.id;
-                tokio::spawn(async move {
-                    if let Err(error) = command_tx
-                        .send(Command::InternalCommand(InternalCommand::RemovePlayer {
-                            id,
-                        }))
-                        .await
-                    {
-                        println!("Error sending remove player command: {}", error);
-                    }
-                });
+    fn set_subscriptions(&self, id: u32, categories: &[Category], subscribed: bool) {
+        if let Some(filter) = self.frame_filters.get(&id) {
+            for &category in categories {
+                filter.set_subscribed(category, subscribed);
             }
         }
     }
+
+    pub fn execute_internal_command(&mut self, internal_command: InternalCommand) {
+        match internal_command {
+            InternalCommand::Update => {
+                let tick_start = Instant::now();
+                if !self.paused {
+                    self.update();
+                    self.check_maintenance();
+                    self.check_round();
+                    self.check_last_player();
+                    self.check_phase();
+                    self.check_bounty();
+                    self.check_boss();
+                    self.check_events();
+                }
+                self.check_bounds();
+                self.send_state();
+                if self.info.mode == GameMode::Squads && self.tick.is_multiple_of(STANDINGS_TICKS) {
+                    self.send_squad_standings();
+                }
+                self.record_tick(tick_start.elapsed());
+            }
+            InternalCommand::AddPlayer { id, .. }
+                if self.info.maintenance.load(Ordering::Relaxed) =>
+            {
+                self.send_error(id, ErrorCode::Maintenance, "The room is under maintenance");
+            }
+            InternalCommand::AddPlayer { id, .. } if self.round_underway => {
+                self.send_error(
+                    id,
+                    ErrorCode::RoundUnderway,
+                    "The round already started, wait for the next one",
+                );
+            }
+            InternalCommand::AddPlayer {
+                id,
+                name,
+                token,
+                skin,
+            } => {
+                let mut player = Player::new(id, name);
+                let starting_mass = self.starting_mass(id, player.mass());
+                player.set_mass(starting_mass);
+                if let Some(spawn_point) = self.map.spawn_point() {
+                    player.position = spawn_point;
+                }
+                if self.map_scale < 1.0 && !self.bounds().contains(player.position) {
+                    player.position = self.bounds().random_point(player.radius);
+                }
+                if let Some(token) = token {
+                    if !self.attach_account(&mut player, &token) {
+                        self.send_error(id, ErrorCode::Unauthorized, "Invalid account token");
+                        return;
+                    }
+                }
+                if let Some(message) = self.ban_message(&player) {
+                    self.send_error(id, ErrorCode::Banned, &message);
+                    return;
+                }
+                if self.info.mode == GameMode::Ranked && player.account.is_none() {
+                    self.send_error(id, ErrorCode::Unauthorized, "Ranked rooms need an account");
+                    return;
+                }
+                if let Some(skin) = skin {
+                    if !self.owns_skin(&player, &skin) {
+                        self.send_error(id, ErrorCode::Forbidden, "Skin not in your inventory");
+                        return;
+                    }
+                    player.skin = Some(skin);
+                }
+                self.reattach_player(&mut player);
+                self.add_player(player);
+            }
+            InternalCommand::RemovePlayer { id } => {
+                self.remove_player(id, None);
+            }
+            InternalCommand::Connect {
+                id,
+                socket,
+                ip,
+                filter,
+            } => {
+                if let Some(ip) = ip {
+                    println!("Connection {} from {}", id, ip);
+                    self.addresses.insert(id, ip);
+                }
+                self.players_sockets.insert(id, socket);
+                self.update_rates.insert(
+                    id,
+                    UpdateRate {
+                        stride: 1,
+                        sent_players: HashMap::new(),
+                        keyframe_due: true,
+                    },
+                );
+                self.frame_filters.insert(id, filter);
+                self.connections.insert(id, ConnectionState::Lobby);
+
+                // Lobby connections watch the arena behind the name entry screen, so they get what
+                // they need to draw it right away instead of waiting for the next keyframe
+                self.send_message_to_player(id, self.welcome(None));
+                if self.config.night_seconds > 0 {
+                    self.send_message_to_player(id, self.phase_message());
+                }
+                if let Some(message) = self.bounty_message() {
+                    self.send_message_to_player(id, message);
+                }
+                if let Some(boss) = &self.boss {
+                    let boss = boss.clone();
+                    self.send_message_to_player(id, MessageToClient::BossSpawned { boss });
+                }
+                self.send_full_state_to_player(id);
+            }
+            InternalCommand::Disconnect { id } => {
+                self.forget_connection(id);
+                self.kicked.remove(&id);
+            }
+            InternalCommand::Resync { id } => {
+                self.send_full_state_to_player(id);
+            }
+            InternalCommand::ReloadConfig { config } => {
+                self.config = *config;
+            }
+            InternalCommand::Pause => {
+                if !self.paused {
+                    self.paused = true;
+                    self.paused_at = Instant::now();
+                }
+                self.broadcast_message(MessageToClient::GamePaused { paused: true });
+            }
+            InternalCommand::Resume => {
+                if self.paused {
+                    self.paused = false;
+                    self.delay_timers(self.paused_at.elapsed());
+                }
+                self.broadcast_message(MessageToClient::GamePaused { paused: false });
+            }
+            InternalCommand::Shutdown => {
+                self.save_snapshot();
+            }
+            InternalCommand::DumpState { token, reply } => {
+                let dump = if self.is_admin_token(&token) {
+                    println!("event=debug_state room={} tick={}", self.info.id, self.tick);
+                    Some(self.debug_state())
+                } else {
+                    None
+                };
+                let _ = reply.try_send(dump);
+            }
+        }
+    }
+
+    // Everything about the world, including what only admins and the owners of the players may see
+    fn debug_state(&self) -> serde_json::Value {
+        let time_scale = self.time_scale();
+        let private: Vec<PlayerPrivate> = self
+            .players
+            .iter()
+            .map(|player| player.private(time_scale))
+            .collect();
+        let config = GameConfig {
+            admin_token: None,
+            ..self.config.clone()
+        };
+
+        serde_json::json!({
+            "room": self.info.id,
+            "tick": self.tick,
+            "paused": self.paused,
+            "phase": self.phase,
+            "map": self.map.name,
+            "connections": self.connections.len(),
+            "players": &self.players[..],
+            "private": private,
+            "food": &self.food[..],
+            "config": config,
+        })
+    }
+
+    fn is_admin_token(&self, token: &str) -> bool {
+        self.config
+            .admin_token
+            .as_deref()
+            .is_some_and(|admin_token| !admin_token.is_empty() && admin_token == token)
+    }
+
+    fn record_tick(&mut self, duration: Duration) {
+        self.tick_metrics.observe(duration);
+        self.adapt_broadcast_rate(duration);
+
+        let budget = Duration::from_millis(TICK_MILLISECONDS);
+        if duration > budget {
+            self.tick_metrics.observe_slow_tick();
+            println!(
+                "Slow tick: duration_ms={:.3} budget_ms={} players={} food={}",
+                duration.as_secs_f64() * 1000.0,
+                TICK_MILLISECONDS,
+                self.players.len(),
+                self.food.len()
+            );
+        }
+    }
+
+    // Sends the states less often while the ticks take most of their budget, down to `min_broadcast_rate`,
+    // and more often again once the load drops
+    fn adapt_broadcast_rate(&mut self, duration: Duration) {
+        self.tick_load += duration;
+        if !self.tick.is_multiple_of(LOAD_WINDOW_TICKS) {
+            return;
+        }
+
+        let window = Duration::from_millis(LOAD_WINDOW_TICKS * TICK_MILLISECONDS);
+        let load = self.tick_load.as_secs_f64() / window.as_secs_f64();
+        self.tick_load = Duration::ZERO;
+
+        let max_stride =
+            (TICKS_PER_SECOND / u64::from(self.config.min_broadcast_rate.max(1))).max(1);
+        let current = u64::from(self.info.broadcast_stride.load(Ordering::Relaxed));
+        let stride = if load > HIGH_LOAD {
+            current + 1
+        } else if load < LOW_LOAD {
+            current.saturating_sub(1)
+        } else {
+            current
+        }
+        .clamp(1, max_stride);
+
+        if stride != current {
+            self.info
+                .broadcast_stride
+                .store(stride as u32, Ordering::Relaxed);
+            println!(
+                "Broadcast rate changed: room={} rate_hz={} load={:.2}",
+                self.info.id,
+                TICKS_PER_SECOND / stride,
+                load
+            );
+        }
+    }
+
+    pub fn execute_player_command(&mut self, player_message: PlayerMessage) {
+        if self.kicked.contains(&player_message.id) {
+            return;
+        }
+        self.handling = Some(player_message.command.name());
+        self.run_player_command(player_message);
+        self.handling = None;
+    }
+
+    fn run_player_command(&mut self, player_message: PlayerMessage) {
+        let joined = self.players.contains(player_message.id);
+
+        match player_message.command {
+            PlayerCommand::Join { .. } if joined => {
+                self.send_error(
+                    player_message.id,
+                    ErrorCode::AlreadyJoined,
+                    "Already joined",
+                );
+            }
+            PlayerCommand::Login { token } => {
+                self.login(player_message.id, &token);
+            }
+            PlayerCommand::Admin { .. } if !self.admins.contains(&player_message.id) => {
+                self.send_error(player_message.id, ErrorCode::Forbidden, "Not an admin");
+            }
+            PlayerCommand::Admin { command } => {
+                self.execute_admin_command(player_message.id, command);
+            }
+            PlayerCommand::Spectate { .. } if joined => {
+                self.send_error(
+                    player_message.id,
+                    ErrorCode::Forbidden,
+                    "Players can't spectate",
+                );
+            }
+            PlayerCommand::Spectate { target_id } if !self.players.contains(target_id) => {
+                self.send_error(
+                    player_message.id,
+                    ErrorCode::NotFound,
+                    "There's no player to spectate with that id",
+                );
+            }
+            PlayerCommand::Spectate { target_id } => {
+                self.set_connection_state(
+                    player_message.id,
+                    ConnectionState::Spectating {
+                        target: Some(target_id),
+                    },
+                );
+            }
+            PlayerCommand::AckKeyframe { tick } => {
+                let acks = self.keyframe_acks.entry(player_message.id).or_default();
+                if tick > acks.acked && tick <= self.tick {
+                    acks.rtt_milliseconds = Some(((self.tick - tick) * TICK_MILLISECONDS) as f64);
+                }
+                acks.acked = acks.acked.max(tick.min(self.tick));
+            }
+            PlayerCommand::RequestKeyframe => {
+                self.request_keyframe(player_message.id);
+            }
+            PlayerCommand::RequestSnapshot => {
+                self.request_snapshot(player_message.id);
+            }
+            PlayerCommand::Subscribe { categories } => {
+                self.set_subscriptions(player_message.id, &categories, true);
+            }
+            PlayerCommand::Unsubscribe { categories } => {
+                self.set_subscriptions(player_message.id, &categories, false);
+            }
+            PlayerCommand::TimeSyncRequest { client_time } => {
+                let server_receive_time = unix_millis();
+                self.send_message_to_player(
+                    player_message.id,
+                    MessageToClient::TimeSyncResponse {
+                        client_time,
+                        server_receive_time,
+                        server_send_time: unix_millis(),
+                        tick: self.tick,
+                        tick_time: self.tick_time,
+                    },
+                );
+            }
+            _ if !joined && !matches!(player_message.command, PlayerCommand::Join { .. }) => {
+                self.send_error(
+                    player_message.id,
+                    ErrorCode::NotJoined,
+                    "Join before sending commands",
+                );
+            }
+            PlayerCommand::SetMass { .. } | PlayerCommand::Teleport { .. }
+                if self.info.mode != GameMode::Sandbox =>
+            {
+                self.send_error(
+                    player_message.id,
+                    ErrorCode::Forbidden,
+                    "Cheats are only allowed in sandbox rooms",
+                );
+            }
+            PlayerCommand::SetMass { mass } => {
+                self.set_player_mass(player_message.id, mass);
+            }
+            PlayerCommand::Teleport { position } => {
+                self.teleport_player(player_message.id, position);
+            }
+            PlayerCommand::Move { .. } if self.paused => {}
+            PlayerCommand::Move {
+                position,
+                client_time,
+            } => {
+                self.move_player(player_message.id, position);
+                if let Some(client_time) = client_time {
+                    self.compensate_lag(player_message.id, client_time);
+                }
+            }
+            PlayerCommand::Rename { name } | PlayerCommand::Join { name, .. }
+                if Player::sanitize_name(&name).is_empty() =>
+            {
+                self.send_error(
+                    player_message.id,
+                    ErrorCode::InvalidName,
+                    "Names can't be empty",
+                );
+            }
+            PlayerCommand::Rename { name } => {
+                self.rename_player(player_message.id, Player::sanitize_name(&name));
+            }
+            PlayerCommand::Steer {
+                direction,
+                throttle,
+            } => {
+                self.steer_player(player_message.id, direction, throttle);
+            }
+            PlayerCommand::Dash if self.paused => {}
+            PlayerCommand::Dash => {
+                self.dash_player(player_message.id);
+            }
+            PlayerCommand::Join {
+                name,
+                token,
+                skin,
+                update_rate,
+            } => {
+                if let Some(update_rate) = update_rate {
+                    self.set_update_rate(player_message.id, update_rate);
+                }
+                self.execute_internal_command(InternalCommand::AddPlayer {
+                    id: player_message.id,
+                    name: Player::sanitize_name(&name),
+                    token,
+                    skin,
+                })
+            }
+        }
+    }
+
+    fn request_snapshot(&mut self, id: u32) {
+        if self.keyframe_in_flight(id) {
+            return;
+        }
+
+        let player = self.players.get(id).map(Player::public);
+        self.send_message_to_player(id, self.welcome(player));
+        if self.config.night_seconds > 0 {
+            self.send_message_to_player(id, self.phase_message());
+        }
+        if let Some(message) = self.bounty_message() {
+            self.send_message_to_player(id, message);
+        }
+        if let Some(boss) = &self.boss {
+            let boss = boss.clone();
+            self.send_message_to_player(id, MessageToClient::BossSpawned { boss });
+        }
+        if let Some(ConnectionState::Spectating { target }) = self.connections.get(&id) {
+            self.send_message_to_player(id, MessageToClient::Spectating { target: *target });
+        }
+        self.request_keyframe(id);
+    }
+
+    // A keyframe sent on request that isn't confirmed yet is still on its way, so asking again within
+    // a keyframe interval is ignored. The next scheduled keyframe reaches the client anyway
+    fn keyframe_in_flight(&self, id: u32) -> bool {
+        let keyframe_ticks = self.config.keyframe_ticks.max(1);
+        self.keyframe_acks.get(&id).is_some_and(|acks| {
+            acks.requested.is_some_and(|requested| {
+                requested > acks.acked && self.tick - requested < keyframe_ticks
+            })
+        })
+    }
+
+    fn request_keyframe(&mut self, id: u32) {
+        if self.keyframe_in_flight(id) {
+            return;
+        }
+
+        self.keyframe_acks.entry(id).or_default().requested = Some(self.tick);
+        self.send_full_state_to_player(id);
+    }
+
+    fn login(&mut self, id: u32, token: &str) {
+        if self.is_admin_token(token) {
+            println!("Connection {} logged in as admin", id);
+            self.admins.insert(id);
+            self.send_message_to_player(id, MessageToClient::LoginSuccess);
+        } else {
+            self.send_error(id, ErrorCode::Unauthorized, "Invalid token");
+        }
+    }
+
+    fn execute_admin_command(&mut self, admin_id: u32, command: AdminCommand) {
+        println!("Admin {} sent {:?}", admin_id, command);
+        self.audit_log.record(
+            self.info.id,
+            admin_id,
+            self.addresses.get(&admin_id).copied(),
+            command.clone(),
+        );
+
+        match command {
+            AdminCommand::Kick { id } => {
+                self.connection_metrics.observe_kick();
+                match self.addresses.get(&id) {
+                    Some(ip) => println!(
+                        "event=kick connection={} room={} ip={}",
+                        id, self.info.id, ip
+                    ),
+                    None => println!("event=kick connection={} room={}", id, self.info.id),
+                }
+                self.kick(id, ErrorCode::Kicked, "Kicked by an admin");
+            }
+            AdminCommand::StartMaintenance { seconds } => {
+                self.info.maintenance.store(true, Ordering::Relaxed);
+                self.maintenance_at = Some(Instant::now() + Duration::from_secs(seconds));
+                self.maintenance_announced = None;
+            }
+            AdminCommand::EndMaintenance => {
+                self.info.maintenance.store(false, Ordering::Relaxed);
+                self.maintenance_at = None;
+                self.broadcast_message(MessageToClient::Announcement {
+                    text: String::from("Maintenance is over, the room is open again"),
+                    severity: Severity::Info,
+                });
+            }
+            AdminCommand::Pause => {
+                self.execute_internal_command(InternalCommand::Pause);
+            }
+            AdminCommand::Resume => {
+                self.execute_internal_command(InternalCommand::Resume);
+            }
+            AdminCommand::Announce { text, severity } => {
+                self.broadcast_message(MessageToClient::Announcement { text, severity });
+            }
+            AdminCommand::GrantSkin { username, skin } => {
+                self.change_accounts(admin_id, |accounts| accounts.grant_skin(&username, &skin));
+            }
+            AdminCommand::RevokeSkin { username, skin } => {
+                self.change_accounts(admin_id, |accounts| accounts.revoke_skin(&username, &skin));
+            }
+            AdminCommand::Ban {
+                target,
+                minutes,
+                reason,
+            } => {
+                let duration = minutes.map(|minutes| Duration::from_secs(minutes * 60));
+                self.change_accounts(admin_id, |accounts| {
+                    accounts
+                        .bans
+                        .ban(target.clone(), &reason, duration, bans::now());
+                    Ok(())
+                });
+
+                let banned: Vec<u32> = self
+                    .players_sockets
+                    .keys()
+                    .copied()
+                    .filter(|&id| self.matches_ban_target(id, &target))
+                    .collect();
+                for id in banned {
+                    println!("event=ban connection={} room={}", id, self.info.id);
+                    self.kick(
+                        id,
+                        ErrorCode::Banned,
+                        &format!("You are banned: {}", reason),
+                    );
+                }
+            }
+            AdminCommand::Unban { target } => {
+                self.change_accounts(admin_id, |accounts| {
+                    accounts.bans.unban(&target);
+                    Ok(())
+                });
+            }
+            AdminCommand::ListBans => {
+                let bans = match self.accounts.lock() {
+                    Ok(accounts) => accounts.bans.active(bans::now()),
+                    Err(error) => {
+                        println!("Error locking accounts: {}", error);
+                        return;
+                    }
+                };
+                self.send_message_to_player(admin_id, MessageToClient::BanList { bans });
+            }
+            AdminCommand::AuditLog { limit } => {
+                let entries = self.audit_log.recent(limit as usize);
+                self.send_message_to_player(admin_id, MessageToClient::AuditLog { entries });
+            }
+            AdminCommand::SetConfig { key, value } => {
+                let mut config = match serde_json::to_value(&self.config) {
+                    Ok(config) => config,
+                    Err(error) => {
+                        println!("Error serializing config: {}", error);
+                        return;
+                    }
+                };
+                match config.get_mut(key.as_str()) {
+                    Some(field) => *field = value,
+                    None => {
+                        self.send_error(
+                            admin_id,
+                            ErrorCode::InvalidValue,
+                            &format!("Unknown config key {}", key),
+                        );
+                        return;
+                    }
+                }
+
+                match serde_json::from_value::<GameConfig>(config) {
+                    Ok(config) => self.config = config,
+                    Err(error) => self.send_error(
+                        admin_id,
+                        ErrorCode::InvalidValue,
+                        &format!("Invalid config: {}", error),
+                    ),
+                }
+            }
+        }
+    }
+
+    fn send_error(&self, id: u32, code: ErrorCode, message: &str) {
+        self.send_message_to_player(
+            id,
+            MessageToClient::Error {
+                code,
+                message: String::from(message),
+                command_ref: self.handling.map(String::from),
+            },
+        );
+    }
+
+    fn kick(&mut self, id: u32, code: ErrorCode, message: &str) {
+        self.send_error(id, code, message);
+        self.forget_connection(id);
+        // Commands it already queued still arrive until the socket closes
+        self.kicked.insert(id);
+    }
+
+    fn forget_connection(&mut self, id: u32) {
+        self.remove_player(id, None);
+        self.connections.remove(&id);
+        self.admins.remove(&id);
+        self.addresses.remove(&id);
+        self.respawn_masses.remove(&id);
+        self.keyframe_acks.remove(&id);
+        self.update_rates.remove(&id);
+        self.frame_filters.remove(&id);
+        self.slow_connections.get_mut().remove(&id);
+        // Dropping the queue ends the writer task of the connection once it has flushed it
+        self.players_sockets.remove(&id);
+    }
+
+    fn matches_ban_target(&self, id: u32, target: &BanTarget) -> bool {
+        match target {
+            BanTarget::Ip { ip } => self.addresses.get(&id) == Some(ip),
+            BanTarget::Account { username } => self
+                .players
+                .get(id)
+                .is_some_and(|player| player.account.as_deref() == Some(username.as_str())),
+        }
+    }
+
+    // Why the connection or the account of the player is turned away, if it is
+    fn ban_message(&self, player: &Player) -> Option<String> {
+        let accounts = match self.accounts.lock() {
+            Ok(accounts) => accounts,
+            Err(error) => {
+                println!("Error locking accounts: {}", error);
+                return None;
+            }
+        };
+
+        let now = bans::now();
+        let ip_ban = self
+            .addresses
+            .get(&player.id)
+            .and_then(|&ip| accounts.bans.find_ip(ip, now));
+        let account_ban = player
+            .account
+            .as_deref()
+            .and_then(|username| accounts.bans.find_account(username, now));
+        ip_ban.or(account_ban).map(Ban::message)
+    }
+
+    fn change_accounts(
+        &self,
+        admin_id: u32,
+        change: impl FnOnce(&mut Accounts) -> Result<(), AccountError>,
+    ) {
+        let mut accounts = match self.accounts.lock() {
+            Ok(accounts) => accounts,
+            Err(error) => {
+                println!("Error locking accounts: {}", error);
+                return;
+            }
+        };
+
+        match change(&mut accounts) {
+            Ok(()) => accounts.save(),
+            Err(error) => {
+                let code = match error {
+                    AccountError::AccountNotFound | AccountError::ClanNotFound => {
+                        ErrorCode::NotFound
+                    }
+                    _ => ErrorCode::InvalidValue,
+                };
+                self.send_error(admin_id, code, &error.to_string());
+            }
+        }
+    }
+
+    // Guests only have the default look, cosmetics belong to accounts
+    fn owns_skin(&self, player: &Player, skin: &str) -> bool {
+        let username = match &player.account {
+            Some(username) => username,
+            None => return false,
+        };
+
+        match self.accounts.lock() {
+            Ok(accounts) => accounts
+                .get(username)
+                .is_some_and(|account| account.skins.iter().any(|owned| owned == skin)),
+            Err(error) => {
+                println!("Error locking accounts: {}", error);
+                false
+            }
+        }
+    }
+
+    // Links the player to the account with that token, returns false if there is none
+    fn attach_account(&self, player: &mut Player, token: &str) -> bool {
+        let accounts = match self.accounts.lock() {
+            Ok(accounts) => accounts,
+            Err(error) => {
+                println!("Error locking accounts: {}", error);
+                return false;
+            }
+        };
+
+        let account = match accounts.authenticate(token) {
+            Some(account) => account,
+            None => return false,
+        };
+
+        player.account = Some(account.username.clone());
+        player.clan_tag = account.clan.clone();
+        player.name = player.tagged_name(player.name.clone());
+        true
+    }
+
+    // Gives a joining player the cell they had before the server restarted
+    // Takes the respawn handicap of the connection, it only counts for the next life
+    fn starting_mass(&mut self, id: u32, default_mass: f32) -> f32 {
+        let starting_mass = self.config.starting_mass.unwrap_or(default_mass);
+        let best_mass = match self.respawn_masses.remove(&id) {
+            Some(best_mass) => best_mass,
+            None => return starting_mass,
+        };
+
+        let handicap = (best_mass * self.config.respawn_mass_fraction.max(0.0))
+            .min(self.config.respawn_mass_cap);
+        starting_mass.max(handicap)
+    }
+
+    fn reattach_player(&mut self, player: &mut Player) {
+        let index = self
+            .detached_players
+            .iter()
+            .position(|detached| detached.name == player.name);
+
+        if let Some(index) = index {
+            let detached = self.detached_players.remove(index);
+            detached.apply(player);
+        }
+    }
+
+    fn expire_detached_players(&mut self) {
+        if self.detached_players.is_empty() || Instant::now() < self.detached_until {
+            return;
+        }
+
+        for detached in std::mem::take(&mut self.detached_players) {
+            self.record_score(detached.name, detached.best_mass);
+        }
+    }
+
+    pub fn add_player(&mut self, mut player: Player) {
+        if self.info.is_full() {
+            println!(
+                "Room {} is full, player {} can't join",
+                self.info.id, player.id
+            );
+            self.send_error(player.id, ErrorCode::RoomFull, "Room is full");
+            return;
+        }
+
+        if self.info.mode == GameMode::Squads {
+            player.squad = Some(self.open_squad());
+        }
+
+        self.connection_metrics.observe_join();
+        println!(
+            "event=join connection={} room={} name={:?}",
+            player.id, self.info.id, player.name
+        );
+        self.send_message_to_player(player.id, MessageToClient::JoinSuccess { id: player.id });
+        self.send_message_to_player(player.id, self.welcome(Some(player.public())));
+        if let Some(motd) = &self.config.motd {
+            self.send_message_to_player(
+                player.id,
+                MessageToClient::Announcement {
+                    text: motd.clone(),
+                    severity: Severity::Info,
+                },
+            );
+        }
+        if self.config.night_seconds > 0 {
+            self.send_message_to_player(player.id, self.phase_message());
+        }
+        self.connections.insert(player.id, ConnectionState::Playing);
+        self.players.insert(player);
+        self.update_player_count();
+        self.full_state_pending = true;
+    }
+
+    fn welcome(&self, player: Option<PlayerPublic>) -> MessageToClient {
+        MessageToClient::Welcome {
+            protocol_version: PROTOCOL_VERSION,
+            tick_milliseconds: TICK_MILLISECONDS,
+            width: self.map.width,
+            height: self.map.height,
+            map: Box::new((*self.map).clone()),
+            quantization: Quantization::for_map(&self.map),
+            eat_ratio: self.rules.eat_ratio(),
+            bounds: self.bounds(),
+            player,
+        }
+    }
+
+    fn set_connection_state(&mut self, id: u32, state: ConnectionState) {
+        if let Some(connection) = self.connections.get_mut(&id) {
+            *connection = state;
+            if let ConnectionState::Spectating { target } = state {
+                self.send_message_to_player(id, MessageToClient::Spectating { target });
+            }
+        }
+    }
+
+    // Spectators following a player that was removed follow whoever ate them, or get a free camera
+    fn retarget_spectators(&mut self, removed_id: u32, eaten_by: Option<u32>) {
+        let spectators: Vec<u32> = self
+            .connections
+            .iter()
+            .filter(|(_, state)| {
+                **state
+                    == ConnectionState::Spectating {
+                        target: Some(removed_id),
+                    }
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in spectators {
+            self.set_connection_state(id, ConnectionState::Spectating { target: eaten_by });
+        }
+    }
+
+    // First squad with room for another player
+    fn open_squad(&self) -> u32 {
+        let squad_size = self.config.squad_size.clamp(2, 4) as usize;
+        let mut squad = 0;
+        while self
+            .players
+            .iter()
+            .filter(|player| player.squad == Some(squad))
+            .count()
+            >= squad_size
+        {
+            squad += 1;
+        }
+        squad
+    }
+
+    fn squad_standings(&self) -> Vec<SquadScore> {
+        let mut squads: Vec<SquadScore> = Vec::new();
+        for player in self.players.iter() {
+            let squad = match player.squad {
+                Some(squad) => squad,
+                None => continue,
+            };
+
+            match squads.iter_mut().find(|score| score.squad == squad) {
+                Some(score) => {
+                    score.mass += player.mass();
+                    score.players.push(player.id);
+                }
+                None => squads.push(SquadScore {
+                    squad,
+                    mass: player.mass(),
+                    players: vec![player.id],
+                }),
+            }
+        }
+
+        squads.sort_by(|a, b| b.mass.total_cmp(&a.mass));
+        squads
+    }
+
+    fn send_squad_standings(&self) {
+        self.broadcast_message(MessageToClient::SquadStandings {
+            squads: self.squad_standings(),
+        });
+    }
+
+    // Pushes back what the timed checks wait for, the scheduled events follow the clock instead
+    fn delay_timers(&mut self, delay: Duration) {
+        self.round_started_at += delay;
+        self.phase_started_at += delay;
+        self.boss_changed_at += delay;
+        self.detached_until += delay;
+        if let Some(maintenance_at) = &mut self.maintenance_at {
+            *maintenance_at += delay;
+        }
+        if let Some((_, since)) = &mut self.leader {
+            *since += delay;
+        }
+    }
+
+    // Counts down to the maintenance, announcing every 10 seconds and each of the last 5
+    fn check_maintenance(&mut self) {
+        let maintenance_at = match self.maintenance_at {
+            Some(maintenance_at) => maintenance_at,
+            None => return,
+        };
+
+        let now = Instant::now();
+        if now >= maintenance_at {
+            self.maintenance_at = None;
+            self.end_round();
+            self.broadcast_message(MessageToClient::Announcement {
+                text: String::from("The round is over, the server is going down for maintenance"),
+                severity: Severity::Critical,
+            });
+            return;
+        }
+
+        let seconds_left = (maintenance_at - now).as_secs_f32().ceil() as u64;
+        let announce = seconds_left.is_multiple_of(10) || seconds_left <= 5;
+        if announce && self.maintenance_announced != Some(seconds_left) {
+            self.maintenance_announced = Some(seconds_left);
+            self.broadcast_message(MessageToClient::Announcement {
+                text: format!("The round ends for maintenance in {} seconds", seconds_left),
+                severity: Severity::Warning,
+            });
+        }
+    }
+
+    // Removes every player, recording their scores, so nobody loses their progress to a hard shutdown.
+    // Returns the best players of the round
+    fn end_round(&mut self) -> Vec<RoundScore> {
+        let mut top_scores: Vec<RoundScore> = self
+            .players
+            .iter()
+            .map(|player| RoundScore {
+                name: player.name.clone(),
+                mass: player.mass(),
+            })
+            .collect();
+        top_scores.sort_by(|a, b| b.mass.total_cmp(&a.mass));
+        top_scores.truncate(ROUND_TOP_SCORES);
+
+        if self.info.mode == GameMode::Ranked {
+            let mut standings: Vec<&Player> = self.players.iter().collect();
+            standings.sort_by(|a, b| b.radius.total_cmp(&a.radius));
+            let usernames: Vec<String> = standings
+                .into_iter()
+                .filter_map(|player| player.account.clone())
+                .collect();
+            self.change_ratings(|accounts| accounts.rate_standings(&usernames));
+        }
+
+        let ids: Vec<u32> = self.players.iter().map(|player| player.id).collect();
+        for id in ids {
+            self.remove_player(id, None);
+        }
+        top_scores
+    }
+
+    fn check_phase(&mut self) {
+        let phase_seconds = match self.phase {
+            Phase::Day => self.config.day_seconds,
+            Phase::Night => self.config.night_seconds,
+        };
+
+        // Turning the cycle off in the config ends the current night right away
+        let cycle_off = self.config.night_seconds == 0;
+        if cycle_off && self.phase == Phase::Day {
+            return;
+        }
+        if !cycle_off && self.phase_started_at.elapsed() < Duration::from_secs(phase_seconds) {
+            return;
+        }
+
+        self.phase = match self.phase {
+            Phase::Day => Phase::Night,
+            Phase::Night => Phase::Day,
+        };
+        self.phase_started_at = Instant::now();
+        self.broadcast_message(self.phase_message());
+    }
+
+    fn check_bounty(&mut self) {
+        if self.config.bounty_seconds == 0 {
+            return;
+        }
+
+        let leader = self
+            .players
+            .iter()
+            .max_by(|a, b| a.mass().total_cmp(&b.mass()))
+            .map(|player| player.id);
+        let since = match (leader, self.leader) {
+            (None, _) => {
+                self.leader = None;
+                return;
+            }
+            (Some(leader), Some((id, since))) if leader == id => since,
+            (Some(leader), _) => {
+                self.leader = Some((leader, Instant::now()));
+                return;
+            }
+        };
+
+        if self.bounty.is_some()
+            || since.elapsed() < Duration::from_secs(self.config.bounty_seconds)
+        {
+            return;
+        }
+
+        self.bounty = leader;
+        if let Some(message) = self.bounty_message() {
+            println!(
+                "Bounty placed: room={} id={:?} mass={}",
+                self.info.id, self.bounty, self.config.bounty_mass
+            );
+            self.broadcast_message(message);
+        }
+    }
+
+    fn bounty_message(&self) -> Option<MessageToClient> {
+        self.bounty.map(|id| MessageToClient::BountyPlaced {
+            id,
+            mass: self.config.bounty_mass,
+        })
+    }
+
+    // The eater gets the bounty on top of the mass of the target, capped like any growth
+    fn claim_bounty(&mut self, id: u32, eaten_by: Option<u32>) {
+        self.bounty = None;
+        self.leader = None;
+
+        let bounty_mass = self.config.bounty_mass.max(0.0);
+        if let Some(eater) = eaten_by {
+            if self.add_mass(eater, bounty_mass) {
+                println!(
+                    "Bounty claimed: room={} id={} by={} mass={}",
+                    self.info.id, id, eater, bounty_mass
+                );
+            }
+        }
+        self.broadcast_message(MessageToClient::BountyClaimed { id, by: eaten_by });
+    }
+
+    // Announces the scheduled events that started or ended, checked once a second
+    fn check_events(&mut self) {
+        if !self.tick.is_multiple_of(TICKS_PER_SECOND) {
+            return;
+        }
+
+        let now = (unix_millis() / 1000.0) as u64;
+        let active: Vec<&ScheduledEvent> = self
+            .config
+            .events
+            .iter()
+            .filter(|event| event.is_active(now))
+            .collect();
+
+        let mut announcements = Vec::new();
+        for event in &active {
+            if !self.active_events.contains(&event.name) {
+                announcements.push(format!("{} started", event.name));
+            }
+        }
+        for name in &self.active_events {
+            if !active.iter().any(|event| &event.name == name) {
+                announcements.push(format!("{} ended", name));
+            }
+        }
+
+        self.mass_multiplier = active
+            .iter()
+            .map(|event| event.mass_multiplier.max(0.0))
+            .product();
+        self.decay_disabled = active.iter().any(|event| event.disable_decay);
+        self.active_events = active.iter().map(|event| event.name.clone()).collect();
+
+        for text in announcements {
+            println!("Scheduled event: room={} {}", self.info.id, text);
+            self.broadcast_message(MessageToClient::Announcement {
+                text,
+                severity: Severity::Info,
+            });
+        }
+    }
+
+    // Returns whether the player is still in the room
+    fn add_mass(&mut self, id: u32, mass: f32) -> bool {
+        let max_radius = self.config.max_radius;
+        match self.players.get_mut(id) {
+            Some(player) => {
+                let radius = player.radius;
+                player.set_mass(player.mass() + mass);
+                player.radius = player.radius.min(max_radius.max(radius));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Spawns the boss once the room waited `boss_seconds`, sends where it is going and rewards the players
+    // once it is beaten and the last of its chunks was eaten
+    fn check_boss(&mut self) {
+        let boss = match &mut self.boss {
+            Some(boss) => boss,
+            None => {
+                if self.config.boss_seconds > 0
+                    && !self.players.is_empty()
+                    && self.boss_changed_at.elapsed()
+                        >= Duration::from_secs(self.config.boss_seconds)
+                {
+                    self.spawn_boss();
+                }
+                return;
+            }
+        };
+
+        if !boss.is_beaten() {
+            if self.tick.is_multiple_of(BOSS_UPDATE_TICKS) {
+                let boss = boss.clone();
+                self.broadcast_message(MessageToClient::BossMoved { boss });
+            }
+            return;
+        }
+
+        let food = &self.food;
+        boss.chunks
+            .retain(|&chunk| food.iter().any(|food| food.id == chunk));
+        if !boss.chunks.is_empty() {
+            return;
+        }
+
+        let rewards = boss.rewards(self.config.boss_reward_mass.max(0.0));
+        self.boss = None;
+        self.boss_changed_at = Instant::now();
+        for reward in &rewards {
+            self.add_mass(reward.id, reward.mass);
+        }
+        println!(
+            "Boss defeated: room={} participants={}",
+            self.info.id,
+            rewards.len()
+        );
+        self.broadcast_message(MessageToClient::BossDefeated { rewards });
+    }
+
+    fn spawn_boss(&mut self) {
+        let mass = self.config.boss_mass.max(boss::CHUNK_MASS);
+        let mut boss = Boss::new(Vector2D::new(0.0, 0.0), mass);
+        boss.position = self.bounds().random_point(boss.radius);
+
+        println!("Boss spawned: room={} mass={}", self.info.id, mass);
+        self.boss = Some(boss.clone());
+        self.boss_changed_at = Instant::now();
+        self.broadcast_message(MessageToClient::BossSpawned { boss });
+    }
+
+    // Chases the nearest player it can eat and eats the ones it covers. Everyone else bumping into it knocks off
+    // a chunk of food, which counts for the reward once eaten
+    fn update_boss(&mut self) {
+        let mut boss = match self.boss.take() {
+            Some(boss) => boss,
+            None => return,
+        };
+
+        let boss_mass = boss.mass();
+        let map = &self.map;
+        let edible = |player: &Player| {
+            player.radius > 0.0
+                && player.mass() < boss_mass
+                && !map.is_protected(player.position, player.mass())
+        };
+        let distance = |player: &Player| (player.position - boss.position).magnitude();
+
+        // Same speed as a player of its mass, in units per second
+        let speed = self.config.speed_factor / boss_mass.sqrt()
+            * self.time_scale()
+            * TICKS_PER_SECOND as f32;
+        let target = self
+            .players
+            .iter()
+            .filter(|player| edible(player))
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .map(|player| player.position);
+        if let Some(target) = target {
+            let direction = target - boss.position;
+            if direction.magnitude() > 0.0 {
+                boss.velocity = direction.normalize() * speed;
+            }
+        }
+
+        let bounds = self.bounds();
+        let position = boss.position + boss.velocity * (TICK_MILLISECONDS as f32 / 1000.0);
+        boss.position = Vector2D::new(
+            position.x.clamp(bounds.x, bounds.x + bounds.width),
+            position.y.clamp(bounds.y, bounds.y + bounds.height),
+        );
+
+        let mut chunks = Vec::new();
+        for player in self.players.iter_mut() {
+            let offset = player.position - boss.position;
+            let distance = offset.magnitude();
+            if player.radius <= 0.0 || distance >= boss.radius + player.radius {
+                continue;
+            }
+
+            // Removed along with the players eaten this tick
+            if distance < boss.radius && edible(player) {
+                player.radius = 0.0;
+                continue;
+            }
+
+            if boss.hit(player.id, self.tick) {
+                boss.set_mass(boss.mass() - boss::CHUNK_MASS);
+                let direction = if distance > 0.0 {
+                    offset.normalize()
+                } else {
+                    Vector2D::new(1.0, 0.0)
+                };
+                chunks.push(boss.position + direction * boss.radius);
+            }
+        }
+
+        let radius = (boss::CHUNK_MASS / (2.0 * std::f32::consts::PI)).sqrt();
+        let chunks: Vec<Food> = chunks
+            .into_iter()
+            .map(|position| Food {
+                id: self.next_food_id(),
+                position,
+                radius,
+                velocity: None,
+            })
+            .collect();
+        boss.chunks.extend(chunks.iter().map(|chunk| chunk.id));
+        self.boss = Some(boss);
+
+        if !chunks.is_empty() {
+            Arc::make_mut(&mut self.food).extend(chunks.iter().cloned());
+            self.broadcast_message(MessageToClient::FoodSpawned { food: chunks });
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.map.scaled_bounds(self.map_scale)
+    }
+
+    // Area grows with the players, so the scale follows the square root of the population
+    fn target_map_scale(&self) -> f32 {
+        let min_scale = self.config.min_map_scale.clamp(MAP_SCALE_STEP, 1.0);
+        let population =
+            self.players.len().max(1) as f32 / self.config.players_for_full_map.max(1) as f32;
+        let scale = (population.sqrt() / MAP_SCALE_STEP).ceil() * MAP_SCALE_STEP;
+        scale.clamp(min_scale, 1.0)
+    }
+
+    fn check_bounds(&mut self) {
+        if !self.tick.is_multiple_of(BOUNDS_CHECK_TICKS) {
+            return;
+        }
+
+        let scale = self.target_map_scale();
+        if (scale - self.map_scale).abs() < MAP_SCALE_STEP / 2.0 {
+            return;
+        }
+
+        println!(
+            "Bounds changed: room={} scale={:.1} players={}",
+            self.info.id,
+            scale,
+            self.players.len()
+        );
+        self.map_scale = scale;
+        self.broadcast_message(MessageToClient::BoundsChanged {
+            bounds: self.bounds(),
+        });
+    }
+
+    // Players left outside after the room shrank drift back in instead of being teleported
+    fn push_inside_bounds(&mut self) {
+        if self.map_scale >= 1.0 {
+            return;
+        }
+
+        let bounds = self.bounds();
+        let step = BOUNDS_PUSH_SPEED * self.time_scale() * TICK_MILLISECONDS as f32 / 1000.0;
+        for player in self.players.iter_mut() {
+            if bounds.contains(player.position) {
+                continue;
+            }
+            let inside = Vector2D::new(
+                player.position.x.clamp(bounds.x, bounds.x + bounds.width),
+                player.position.y.clamp(bounds.y, bounds.y + bounds.height),
+            );
+            if (inside - player.position).magnitude() <= step {
+                player.position = inside;
+            } else {
+                player.move_towards(inside, step);
+            }
+        }
+    }
+
+    pub fn vision_radius(&self) -> f32 {
+        match self.phase {
+            Phase::Day => self.config.vision_radius,
+            Phase::Night => self.config.vision_radius * self.config.night_vision_factor,
+        }
+    }
+
+    fn phase_message(&self) -> MessageToClient {
+        MessageToClient::PhaseChanged {
+            phase: self.phase,
+            vision_radius: self.vision_radius(),
+        }
+    }
+
+    fn check_round(&mut self) {
+        let round_seconds = self.config.round_seconds;
+        if round_seconds == 0
+            || self.round_started_at.elapsed() < Duration::from_secs(round_seconds)
+        {
+            return;
+        }
+
+        let top_scores = self.end_round();
+        self.next_map(top_scores);
+    }
+
+    // Starts rounds without respawns once `last_man_players` joined and ends them with the last one left
+    fn check_last_player(&mut self) {
+        if self.rules.allows_respawns() {
+            return;
+        }
+
+        if !self.round_underway {
+            let players = self.players.len() as u32;
+            if players >= self.config.last_man_players.max(2) {
+                self.round_underway = true;
+                self.info.round_underway.store(true, Ordering::Relaxed);
+                println!("Round started: room={} players={}", self.info.id, players);
+                self.broadcast_message(MessageToClient::RoundStarted { players });
+            }
+            return;
+        }
+
+        if self.players.len() > 1 {
+            return;
+        }
+
+        let winner = self.players.iter().next().map(|player| player.id);
+        println!("Round won: room={} winner={:?}", self.info.id, winner);
+        self.broadcast_message(MessageToClient::RoundWon { id: winner });
+        let top_scores = self.end_round();
+        self.next_map(top_scores);
+    }
+
+    // Announces the results of the round that ended, then starts the next one on the next map of the
+    // rotation with fresh food. Rounds nobody played are not announced
+    pub fn next_map(&mut self, top_scores: Vec<RoundScore>) {
+        if let Some(winner) = top_scores.first() {
+            discord::announce(
+                &self.config.discord_webhook_url,
+                format!(
+                    "Round over on {}! {} won with a mass of {:.0}",
+                    self.map.name, winner.name, winner.mass
+                ),
+            );
+            webhooks::dispatch(
+                &self.config.webhook_urls,
+                WebhookEvent::RoundEnded {
+                    room_id: self.info.id,
+                    map: self.map.name.clone(),
+                    top_scores,
+                },
+            );
+        }
+
+        let index = (self.info.map_index.load(Ordering::Relaxed) + 1) % self.info.maps.len();
+        self.info.map_index.store(index, Ordering::Relaxed);
+        self.map = self.info.map();
+        self.round_started_at = Instant::now();
+        self.round_underway = false;
+        self.info.round_underway.store(false, Ordering::Relaxed);
+        self.map_scale = self.target_map_scale();
+        self.food_clusters.clear();
+        self.boss = None;
+        self.boss_changed_at = Instant::now();
+
+        let food_target = self.food_target() as u32;
+        self.food = Arc::new(self.generate_food(food_target));
+        self.full_state_pending = true;
+
+        self.broadcast_message(MessageToClient::RoundOver {
+            map: Box::new((*self.map).clone()),
+            quantization: Quantization::for_map(&self.map),
+        });
+        self.broadcast_message(MessageToClient::BoundsChanged {
+            bounds: self.bounds(),
+        });
+    }
+
+    fn update_player_count(&self) {
+        let player_count = self.players.len() as u32;
+        let previous_count = self.info.player_count.swap(player_count, Ordering::Relaxed);
+        if self.info.mode == GameMode::Ranked {
+            self.update_average_rating();
+        }
+
+        for &threshold in &self.config.player_count_thresholds {
+            if previous_count < threshold && player_count >= threshold {
+                webhooks::dispatch(
+                    &self.config.webhook_urls,
+                    WebhookEvent::PlayerCountThreshold {
+                        room_id: self.info.id,
+                        threshold,
+                        player_count,
+                    },
+                );
+            }
+        }
+    }
+
+    // Removes the player and tells everyone, `eaten_by` is the eater's id when the player didn't leave on their own
+    pub fn remove_player(&mut self, id: u32, eaten_by: Option<u32>) {
+        let player = match self.players.remove(id) {
+            Some(player) => player,
+            None => return,
+        };
+
+        if let Some(username) = &player.account {
+            self.record_session(username, player.session_stats());
+        }
+        if let Some(eater) = eaten_by.and_then(|by| self.players.get(by)) {
+            if let (GameMode::Ranked, Some(winner), Some(loser)) =
+                (self.info.mode, &eater.account, &player.account)
+            {
+                self.change_ratings(|accounts| accounts.rate_match(winner, loser));
+            }
+        }
+        self.record_score(player.name, player.best_mass);
+        self.update_player_count();
+
+        match eaten_by {
+            Some(by) => {
+                if self.config.respawn_mass_fraction > 0.0 {
+                    self.respawn_masses.insert(id, player.best_mass);
+                }
+                self.broadcast_message(MessageToClient::PlayerEaten { id, by });
+                self.set_connection_state(id, ConnectionState::Spectating { target: Some(by) });
+            }
+            None => {
+                self.broadcast_message(MessageToClient::PlayerLeft { id });
+                self.set_connection_state(id, ConnectionState::Lobby);
+            }
+        }
+        self.retarget_spectators(id, eaten_by);
+        if self.bounty == Some(id) {
+            self.claim_bounty(id, eaten_by);
+        }
+
+        // Squads lose as a unit, once their last member is gone
+        if let Some(squad) = player.squad {
+            if !self
+                .players
+                .iter()
+                .any(|player| player.squad == Some(squad))
+            {
+                self.broadcast_message(MessageToClient::SquadEliminated { squad });
+            }
+        }
+    }
+
+    fn change_ratings(&self, change: impl FnOnce(&mut Accounts)) {
+        match self.accounts.lock() {
+            Ok(mut accounts) => {
+                change(&mut accounts);
+                accounts.save();
+            }
+            Err(error) => println!("Error locking accounts: {}", error),
+        }
+        self.update_average_rating();
+    }
+
+    fn update_average_rating(&self) {
+        let ratings: Vec<f32> = match self.accounts.lock() {
+            Ok(accounts) => self
+                .players
+                .iter()
+                .filter_map(|player| accounts.rating(player.account.as_deref()?))
+                .collect(),
+            Err(error) => {
+                println!("Error locking accounts: {}", error);
+                return;
+            }
+        };
+
+        if !ratings.is_empty() {
+            let average_rating = ratings.iter().sum::<f32>() / ratings.len() as f32;
+            self.info
+                .average_rating
+                .store(average_rating as u32, Ordering::Relaxed);
+        }
+    }
+
+    fn record_session(&self, username: &str, session: SessionStats) {
+        match self.accounts.lock() {
+            Ok(mut accounts) => {
+                accounts.record(username, &session);
+                accounts.save();
+            }
+            Err(error) => println!("Error locking accounts: {}", error),
+        }
+    }
+
+    fn record_score(&self, name: String, best_mass: f32) {
+        match self.leaderboard.lock() {
+            Ok(mut leaderboard) => {
+                if leaderboard.record(name.clone(), best_mass) {
+                    discord::announce(
+                        &self.config.discord_webhook_url,
+                        format!("New record! {} reached a mass of {:.0}", name, best_mass),
+                    );
+                    webhooks::dispatch(
+                        &self.config.webhook_urls,
+                        WebhookEvent::NewHighScore {
+                            name,
+                            mass: best_mass,
+                        },
+                    );
+                }
+                leaderboard.save();
+            }
+            Err(error) => {
+                println!("Error locking leaderboard: {}", error);
+            }
+        }
+    }
+
+    pub fn rename_player(&mut self, id: u32, name: String) {
+        let time_scale = self.time_scale();
+        let player = match self.players.get_mut(id) {
+            Some(player) => player,
+            None => return,
+        };
+
+        if !player.can_rename(time_scale) {
+            let message = format!(
+                "Wait {:.0} seconds before renaming again",
+                player.rename_cooldown(time_scale).ceil()
+            );
+            self.send_error(id, ErrorCode::RateLimited, &message);
+            return;
+        }
+
+        player.rename(name);
+        let name = player.name.clone();
+        self.broadcast_message(MessageToClient::PlayerRenamed { id, name });
+    }
+
+    // The owner gets its private state right away so the cooldown shows without waiting for a keyframe
+    pub fn dash_player(&mut self, id: u32) {
+        let time_scale = self.time_scale();
+        let player = match self.players.get_mut(id) {
+            Some(player) => player,
+            None => return,
+        };
+
+        let cooldown = player.dash_cooldown(time_scale);
+        if cooldown > 0.0 {
+            let message = format!("Wait {:.0} seconds before dashing again", cooldown.ceil());
+            self.send_error(id, ErrorCode::RateLimited, &message);
+            return;
+        }
+
+        player.dash();
+        if let Some(player) = self.players.get(id) {
+            self.send_private_state(player);
+        }
+    }
+
+    pub fn move_player(&mut self, id: u32, position: Vector2D) {
+        // Values too big for an f32 deserialize as infinity and would turn the position into NaN
+        if !position.is_finite() {
+            self.send_error(
+                id,
+                ErrorCode::InvalidPosition,
+                "Positions must be finite numbers",
+            );
+            return;
+        }
+
+        let time_scale = self.time_scale();
+        if let Some(player) = self.players.get_mut(id) {
+            let velocity = GameManager::speed(
+                self.rules.as_ref(),
+                &self.config,
+                &self.map,
+                time_scale,
+                player,
+            );
+            player.steering = None;
+            player.move_towards(position, velocity);
+        }
+    }
+
+    pub fn steer_player(&mut self, id: u32, direction: Vector2D, throttle: f32) {
+        if !direction.is_finite() || !throttle.is_finite() {
+            self.send_error(
+                id,
+                ErrorCode::InvalidValue,
+                "Direction and throttle must be finite numbers",
+            );
+            return;
+        }
+
+        if let Some(player) = self.players.get_mut(id) {
+            player.steering = Some((direction.normalize(), throttle.clamp(0.0, 1.0)));
+        }
+    }
+
+    // Distance the player moves in a tick at full speed
+    fn speed(
+        rules: &dyn GameRules,
+        config: &GameConfig,
+        map: &Map,
+        time_scale: f32,
+        player: &Player,
+    ) -> f32 {
+        let dash = if player.is_dashing(time_scale) {
+            DASH_SPEED
+        } else {
+            1.0
+        };
+        rules.speed(player, config.speed_factor)
+            * time_scale
+            * dash
+            * map.speed_multiplier(player.position, player.mass())
+    }
+
+    // Players stop at the edge of the room. Those left outside after it shrank can't steer further out
+    // while `push_inside_bounds` brings them back
+    fn steer_players(&mut self) {
+        let time_scale = self.time_scale();
+        let bounds = self.bounds();
+        let rules = self.rules.as_ref();
+        for player in self.players.as_mut_slice() {
+            let (direction, throttle) = match player.steering {
+                Some(steering) => steering,
+                None => continue,
+            };
+            let velocity =
+                GameManager::speed(rules, &self.config, &self.map, time_scale, player) * throttle;
+            let position = player.position + direction * velocity;
+            player.position = Vector2D::new(
+                position.x.clamp(
+                    bounds.x.min(player.position.x),
+                    (bounds.x + bounds.width).max(player.position.x),
+                ),
+                position.y.clamp(
+                    bounds.y.min(player.position.y),
+                    (bounds.y + bounds.height).max(player.position.y),
+                ),
+            );
+        }
+    }
+
+    // Sandbox command, the radius is still capped at `max_radius`
+    fn set_player_mass(&mut self, id: u32, mass: f32) {
+        if !mass.is_finite() || mass <= 0.0 {
+            self.send_error(
+                id,
+                ErrorCode::InvalidValue,
+                "Mass must be a positive number",
+            );
+            return;
+        }
+
+        let max_radius = self.config.max_radius;
+        if let Some(player) = self.players.get_mut(id) {
+            player.set_mass(mass);
+            player.radius = player.radius.min(max_radius);
+        }
+    }
+
+    fn teleport_player(&mut self, id: u32, position: Vector2D) {
+        if !position.is_finite() {
+            self.send_error(
+                id,
+                ErrorCode::InvalidPosition,
+                "Positions must be finite numbers",
+            );
+            return;
+        }
+
+        let (width, height) = (self.map.width, self.map.height);
+        if let Some(player) = self.players.get_mut(id) {
+            player.position =
+                Vector2D::new(position.x.clamp(0.0, width), position.y.clamp(0.0, height));
+        }
+    }
+
+    // Resolves every overlapping pair of players once, returning the `(eater, eaten)` ids of the players eaten this tick
+    pub fn check_collision(&mut self) -> Vec<(u32, u32)> {
+        let candidates = self.candidates(GameManager::overlaps);
+
+        let mut eats = Vec::new();
+        for (i, others) in candidates.into_iter().enumerate() {
+            for j in others {
+                let player = &self.players[i];
+                let other_player = &self.players[j];
+
+                // Players eaten earlier in this pass can't eat or be eaten again,
+                // and the survivors may have grown since the candidates were found
+                if player.radius <= 0.0
+                    || other_player.radius <= 0.0
+                    || !GameManager::overlaps(player, other_player)
+                {
+                    continue;
+                }
+
+                let (eater, eaten) = if self.can_eat(player, other_player) {
+                    (i, j)
+                } else if self.can_eat(other_player, player) {
+                    (j, i)
+                } else {
+                    continue;
+                };
+
+                eats.push(self.eat(eater, eaten));
+            }
+        }
+
+        eats
+    }
+
+    // Indices in `players`, returns the `(eater, eaten)` ids
+    fn eat(&mut self, eater: usize, eaten: usize) -> (u32, u32) {
+        let players = self.players.as_mut_slice();
+        players[eater].radius = GameManager::grow(
+            self.rules.as_ref(),
+            &self.config,
+            self.mass_multiplier,
+            players[eater].radius,
+            players[eaten].radius,
+        );
+        players[eaten].radius = 0.0;
+        players[eater].eats += 1;
+        players[eater].streak += 1;
+        (players[eater].id, players[eaten].id)
+    }
+
+    // Where every player was in the last ticks, for `compensate_lag`
+    fn record_history(&mut self) {
+        let window = self.config.max_rewind_milliseconds / TICK_MILLISECONDS;
+        if window == 0 {
+            self.position_history.clear();
+            return;
+        }
+
+        while self.position_history.len() as u64 >= window {
+            self.position_history.pop_front();
+        }
+        let positions = self
+            .players
+            .iter()
+            .map(|player| (player.id, player.position))
+            .collect();
+        self.position_history.push_back((self.tick, positions));
+    }
+
+    // Checks the move of a lagging player against where the others were when it was sent, at most
+    // `max_rewind_milliseconds` ago. Players it would have caught there are eaten now
+    fn compensate_lag(&mut self, id: u32, client_time: f64) {
+        let max_rewind = self.config.max_rewind_milliseconds as f64;
+        if !client_time.is_finite() || max_rewind <= 0.0 {
+            return;
+        }
+
+        // The client's claim is only trusted up to the round trip measured with the keyframe acks
+        let rtt = match self
+            .keyframe_acks
+            .get(&id)
+            .and_then(|acks| acks.rtt_milliseconds)
+        {
+            Some(rtt) => rtt,
+            None => return,
+        };
+        let lag = (unix_millis() - client_time).clamp(0.0, max_rewind.min(rtt));
+        let ticks_ago = (lag / TICK_MILLISECONDS as f64) as u64;
+        if ticks_ago == 0 {
+            return;
+        }
+        let tick = self.tick.saturating_sub(ticks_ago);
+        let positions = match self
+            .position_history
+            .iter()
+            .find(|(recorded, _)| *recorded >= tick)
+        {
+            Some((_, positions)) => positions,
+            None => return,
+        };
+
+        let eater = match self.players.get(id) {
+            Some(eater) if eater.radius > 0.0 => eater,
+            _ => return,
+        };
+        let mut caught = Vec::new();
+        for &(other_id, position) in positions {
+            let other = match self.players.get(other_id) {
+                Some(other) if other_id != id && other.radius > 0.0 => other,
+                _ => continue,
+            };
+            // `overlaps` and `can_eat` with the other player where it was
+            let distance = (eater.position - position).magnitude();
+            if distance < eater.radius + other.radius
+                && self.rules.can_eat(eater, other)
+                && !self.map.is_protected(position, other.mass())
+            {
+                caught.push(other_id);
+            }
+        }
+
+        let mut eats = Vec::new();
+        for other_id in caught {
+            let (eater, eaten) = match (self.players.index_of(id), self.players.index_of(other_id))
+            {
+                (Some(eater), Some(eaten)) => (eater, eaten),
+                _ => continue,
+            };
+            if self.players[eaten].radius > 0.0 {
+                eats.push(self.eat(eater, eaten));
+            }
+        }
+        if !eats.is_empty() {
+            self.remove_dead_players(&eats);
+            self.announce_streaks(&eats);
+        }
+    }
+
+    // Radius after eating, with diminishing returns for big players and capped so nobody covers the whole map.
+    // `mass_multiplier` scales the mass of what is eaten, a radius grows with the square root of the mass
+    fn grow(
+        rules: &dyn GameRules,
+        config: &GameConfig,
+        mass_multiplier: f32,
+        radius: f32,
+        eaten_radius: f32,
+    ) -> f32 {
+        let eaten_radius = eaten_radius * mass_multiplier.max(0.0).sqrt();
+        let mut grown = rules.radius_after_eat(radius, eaten_radius);
+
+        if radius > config.growth_falloff_radius {
+            grown = radius + (grown - radius) * config.growth_falloff_radius / radius;
+        }
+
+        grown.min(config.max_radius.max(radius))
+    }
+
+    // Players overlapping without either being able to eat the other push each other apart, the lighter one
+    // moving more. Only part of the overlap is undone every tick so crowds settle instead of jittering
+    fn separate_players(&mut self) {
+        let stiffness = self.config.collision_stiffness.clamp(0.0, 1.0);
+        if stiffness <= 0.0 {
+            return;
+        }
+
+        let candidates = self.candidates(|player, other_player| {
+            player.radius > 0.0
+                && other_player.radius > 0.0
+                && GameManager::overlaps(player, other_player)
+        });
+
+        let mut pushes = vec![Vector2D::new(0.0, 0.0); self.players.len()];
+        for (i, others) in candidates.into_iter().enumerate() {
+            for j in others {
+                let player = &self.players[i];
+                let other_player = &self.players[j];
+                if self.can_eat(player, other_player) || self.can_eat(other_player, player) {
+                    continue;
+                }
+
+                let offset = other_player.position - player.position;
+                let distance = offset.magnitude();
+                let direction = if distance > 0.0 {
+                    offset.normalize()
+                } else {
+                    Vector2D::new(1.0, 0.0)
+                };
+                let overlap = (player.radius + other_player.radius - distance) * stiffness;
+                let share = other_player.mass() / (player.mass() + other_player.mass());
+                pushes[i] = pushes[i] - direction * (overlap * share);
+                pushes[j] = pushes[j] + direction * (overlap * (1.0 - share));
+            }
+        }
+
+        for (player, push) in self.players.as_mut_slice().iter_mut().zip(pushes) {
+            player.position = player.position + push;
+        }
+    }
+
+    // The rules of the room, except inside safe zones
+    fn can_eat(&self, eater: &Player, eaten: &Player) -> bool {
+        self.rules.can_eat(eater, eaten) && !self.map.is_protected(eaten.position, eaten.mass())
+    }
+
+    // For every player, the indices of the players after it that form a pair with it. Finding the pairs is
+    // read-only, so it can be spread over several threads
+    fn candidates(&self, pairs: fn(&Player, &Player) -> bool) -> Vec<Vec<usize>> {
+        let players = self.players.snapshot();
+        let checks = players.len() * players.len() / 2;
+        parallel::map(&players.clone(), checks, move |i, player| {
+            players[i + 1..]
+                .iter()
+                .enumerate()
+                .filter(|(_, other_player)| pairs(player, other_player))
+                .map(|(offset, _)| i + 1 + offset)
+                .collect()
+        })
+    }
+
+    fn overlaps(player: &Player, other_player: &Player) -> bool {
+        let distance = (player.position - other_player.position).magnitude();
+        distance < player.radius + other_player.radius
+    }
+
+    pub fn check_food_collision(&mut self) {
+        // For every food, the player that eats it: the last one overlapping it.
+        // Players eaten earlier in the tick are skipped so they can't grow back before being removed
+        let players = self.players.snapshot();
+        let checks = players.len() * self.food.len();
+        let eaters = parallel::map(&self.food, checks, move |_, food| {
+            players.iter().rposition(|player| {
+                let distance = (player.position - food.position).magnitude();
+                player.radius > 0.0 && distance < player.radius + food.radius
+            })
+        });
+
+        if eaters.iter().all(Option::is_none) {
+            return;
+        }
+
+        let mut eaten = Vec::new();
+        for (food, eater) in self.food.iter().zip(&eaters) {
+            if let Some(i) = *eater {
+                let combined = GameManager::grow(
+                    self.rules.as_ref(),
+                    &self.config,
+                    self.mass_multiplier,
+                    self.players[i].radius,
+                    food.worth(),
+                );
+                self.players.as_mut_slice()[i].radius = combined;
+                eaten.push((food.id, self.players[i].id));
+            }
+        }
+
+        let mut eaters = eaters.iter();
+        Arc::make_mut(&mut self.food).retain(|_| matches!(eaters.next(), Some(None)));
+
+        if let Some(boss) = &mut self.boss {
+            for &(food, by) in &eaten {
+                if let Some(index) = boss.chunks.iter().position(|&chunk| chunk == food) {
+                    boss.chunks.swap_remove(index);
+                    *boss.damage.entry(by).or_insert(0.0) += boss::CHUNK_MASS;
+                }
+            }
+        }
+
+        for (id, by) in eaten {
+            self.broadcast_message(MessageToClient::FoodEaten { id, by });
+        }
+    }
+
+    // Moves the wandering food, turning it now and then and bouncing it off the bounds
+    fn move_food(&mut self) {
+        // Writing would copy food still shared with a snapshot for nothing
+        if self.food.iter().all(|food| food.velocity.is_none()) {
+            return;
+        }
+
+        let bounds = self.bounds();
+        let seconds = self.time_scale() * TICK_MILLISECONDS as f32 / 1000.0;
+        let turn = self.tick.is_multiple_of(WANDER_TICKS);
+        let mut rng = rand::thread_rng();
+
+        let mut moved = Vec::new();
+        for food in Arc::make_mut(&mut self.food).iter_mut() {
+            let mut velocity = match food.velocity {
+                Some(velocity) => velocity,
+                None => continue,
+            };
+            let mut changed = false;
+
+            if turn {
+                let angle = rng.gen_range(-WANDER_TURN..=WANDER_TURN);
+                let (sin, cos) = angle.sin_cos();
+                velocity = Vector2D::new(
+                    velocity.x * cos - velocity.y * sin,
+                    velocity.x * sin + velocity.y * cos,
+                );
+                changed = true;
+            }
+
+            let mut position = food.position + velocity * seconds;
+            if position.x < bounds.x || position.x > bounds.x + bounds.width {
+                velocity.x = -velocity.x;
+                changed = true;
+            }
+            if position.y < bounds.y || position.y > bounds.y + bounds.height {
+                velocity.y = -velocity.y;
+                changed = true;
+            }
+            position.x = position.x.clamp(bounds.x, bounds.x + bounds.width);
+            position.y = position.y.clamp(bounds.y, bounds.y + bounds.height);
+
+            food.position = position;
+            food.velocity = Some(velocity);
+            if changed {
+                moved.push(food.clone());
+            }
+        }
+
+        if !moved.is_empty() {
+            self.broadcast_message(MessageToClient::FoodMoved { food: moved });
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.steer_players();
+        self.move_food();
+        self.update_boss();
+        let eats = self.check_collision();
+        self.separate_players();
+        self.check_food_collision();
+        self.decay_players();
+        self.drain_players();
+        self.push_inside_bounds();
+        self.update_best_masses();
+        self.remove_dead_players(&eats);
+        self.announce_streaks(&eats);
+        self.check_food();
+        self.expire_detached_players();
+        self.publish_biggest_player();
+        self.record_history();
+    }
+
+    fn publish_biggest_player(&self) {
+        let biggest = self
+            .players
+            .iter()
+            .max_by(|a, b| a.mass().total_cmp(&b.mass()))
+            .map(|player| BiggestPlayer {
+                name: player.name.clone(),
+                mass: player.mass(),
+                room: self.info.id,
+            });
+
+        match self.info.biggest_player.lock() {
+            Ok(mut published) => *published = biggest,
+            Err(error) => println!("Error locking biggest player: {}", error),
+        }
+    }
+
+    // Negative or NaN scales from a bad config stop the simulation instead of running it backwards
+    fn time_scale(&self) -> f32 {
+        self.config.time_scale.max(0.0)
+    }
+
+    fn decay_players(&mut self) {
+        if self.decay_disabled {
+            return;
+        }
+
+        let fraction =
+            self.config.decay_rate * self.time_scale() * TICK_MILLISECONDS as f32 / 1000.0;
+        if fraction <= 0.0 {
+            return;
+        }
+
+        for player in self.players.iter_mut() {
+            player.decay(fraction);
+        }
+    }
+
+    // Danger zones shrink the players inside, down to the starting size like decay
+    fn drain_players(&mut self) {
+        if self.map.terrain.is_empty() {
+            return;
+        }
+
+        let seconds = self.time_scale() * TICK_MILLISECONDS as f32 / 1000.0;
+        for player in self.players.iter_mut() {
+            let fraction = self.map.drain(player.position) * seconds;
+            if fraction > 0.0 {
+                player.decay(fraction);
+            }
+        }
+    }
+
+    fn update_best_masses(&mut self) {
+        for player in self.players.iter_mut() {
+            player.update_best_mass();
+        }
+    }
+
+    // Amount of food the room should have for its size and the players in it
+    pub fn food_target(&self) -> usize {
+        let area = self.bounds().area() / (100.0 * 100.0);
+        let food_target = area * self.config.food_density
+            + self.players.len() as f32 * self.config.food_per_player;
+        food_target.round().max(0.0) as usize
+    }
+
+    fn check_food(&mut self) {
+        if self.round_underway && !self.rules.regrows_food() {
+            return;
+        }
+
+        // Check if there are enough food
+        let food_target = self.food_target();
+        if self.food.len() < food_target {
+            let difference = (food_target - self.food.len()) as u32;
+            let extra_food = self.generate_food(difference.min(self.config.food_spawn_per_tick));
+            Arc::make_mut(&mut self.food).extend(extra_food.iter().cloned());
+            self.broadcast_message(MessageToClient::FoodSpawned { food: extra_food });
+        }
+    }
+
+    // Removes the players eaten this tick right away, so they never show up in the next state frame.
+    // `eats` are the `(eater, eaten)` pairs returned by `check_collision`
+    pub fn remove_dead_players(&mut self, eats: &[(u32, u32)]) {
+        let mut dead_players = Vec::new();
+        for player in self.players.iter_mut() {
+            if player.radius > 0.01 {
+                continue;
+            }
+            // Rules that keep eaten players in the room still end their streak
+            player.streak = 0;
+            if self.rules.on_death(player) {
+                dead_players.push(player.id);
+            }
+        }
+
+        for id in dead_players {
+            let eaten_by = eats
+                .iter()
+                .find(|(_, eaten)| *eaten == id)
+                .map(|(eater, _)| *eater);
+            self.remove_player(id, eaten_by);
+        }
+    }
+
+    // A player eating several others in one tick can pass more than one milestone at once
+    fn announce_streaks(&self, eats: &[(u32, u32)]) {
+        let mut eaters: Vec<u32> = eats.iter().map(|&(eater, _)| eater).collect();
+        eaters.sort_unstable();
+        eaters.dedup();
+
+        for eater in eaters {
+            let player = match self.players.get(eater) {
+                Some(player) => player,
+                None => continue,
+            };
+            let eaten_now = eats.iter().filter(|&&(by, _)| by == eater).count() as u32;
+            let before = player.streak.saturating_sub(eaten_now);
+
+            for &milestone in &STREAK_MILESTONES {
+                if before < milestone && player.streak >= milestone {
+                    println!(
+                        "Kill streak: room={} id={} name={} streak={}",
+                        self.info.id, player.id, player.name, milestone
+                    );
+                    self.broadcast_message(MessageToClient::KillStreak {
+                        id: player.id,
+                        streak: milestone,
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Position and radius of every player, written over the previous ones so the map keeps its allocation
+fn record_positions(players: &Players, positions: &mut HashMap<u32, (Vector2D, f32)>) {
+    positions.clear();
+    positions.extend(
+        players
+            .iter()
+            .map(|player| (player.id, (player.position, player.radius))),
+    );
+}
+
+fn unix_millis() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
 }
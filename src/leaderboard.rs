@@ -0,0 +1,105 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::storage::{self, Stored, Writer};
+
+pub const LEADERBOARD_PATH: &str = "leaderboard.json";
+
+// Only the best records (plus everything from the last week) are kept so the file doesn't grow forever
+const MAX_RECORDS: usize = 1000;
+const LEADERBOARD_SIZE: usize = 10;
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScoreRecord {
+    pub name: String,
+    pub best_mass: f32,
+    // Unix timestamp in seconds of the moment the session ended
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    All,
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Leaderboard {
+    records: Vec<ScoreRecord>,
+    // Saves to the leaderboard file, in-memory leaderboards have none
+    #[serde(skip)]
+    writer: Option<Writer>,
+}
+
+impl Stored for Leaderboard {
+    fn writer(&self) -> Option<&Writer> {
+        self.writer.as_ref()
+    }
+}
+
+impl Leaderboard {
+    // Written by `storage::keep_saved` once shared
+    pub fn load(path: &str) -> Leaderboard {
+        let mut leaderboard: Leaderboard = storage::load(path, "leaderboard");
+        leaderboard.writer = Some(Writer::new(path, "leaderboard"));
+        leaderboard
+    }
+
+    // Only marks the leaderboard as changed, cheap enough for the game loop
+    pub fn save(&self) {
+        if let Some(writer) = &self.writer {
+            writer.mark_changed();
+        }
+    }
+
+    // Returns whether the record beats every previous one
+    pub fn record(&mut self, name: String, best_mass: f32) -> bool {
+        let is_high_score = self
+            .records
+            .first()
+            .is_none_or(|best| best_mass > best.best_mass);
+
+        self.records.push(ScoreRecord {
+            name,
+            best_mass,
+            timestamp: now(),
+        });
+
+        self.records
+            .sort_by(|a, b| b.best_mass.total_cmp(&a.best_mass));
+
+        let week_ago = now().saturating_sub(7 * SECONDS_PER_DAY);
+        let mut kept = 0;
+        self.records.retain(|record| {
+            kept += 1;
+            kept <= MAX_RECORDS || record.timestamp >= week_ago
+        });
+
+        is_high_score
+    }
+
+    pub fn top(&self, period: Period) -> Vec<ScoreRecord> {
+        let since = match period {
+            Period::All => 0,
+            Period::Daily => now().saturating_sub(SECONDS_PER_DAY),
+            Period::Weekly => now().saturating_sub(7 * SECONDS_PER_DAY),
+        };
+
+        self.records
+            .iter()
+            .filter(|record| record.timestamp >= since)
+            .take(LEADERBOARD_SIZE)
+            .cloned()
+            .collect()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,31 @@
+pub mod accounts;
+pub mod audit;
+pub mod bans;
+pub mod binary;
+pub mod boss;
+pub mod checksum;
+pub mod client;
+pub mod config;
+pub mod crash;
+pub mod discord;
+pub mod encoder;
+pub mod game_manager;
+pub mod leaderboard;
+pub mod listen;
+pub mod map;
+pub mod metrics;
+pub mod openapi;
+pub mod parallel;
+pub mod player;
+pub mod protocol;
+pub mod proxy;
+pub mod rate_limit;
+pub mod registry;
+pub mod room;
+pub mod rules;
+pub mod schedule;
+pub mod server;
+pub mod snapshot;
+pub mod storage;
+pub mod vector;
+pub mod webhooks;
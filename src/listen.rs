@@ -0,0 +1,80 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+pub const DEFAULT_PORT: u16 = 3000;
+
+// Where the server accepts connections
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl ListenAddress {
+    // Reads `--host`, `--port` and `--unix-socket` from the arguments, falling back to the `HOST`, `PORT`
+    // and `UNIX_SOCKET` environment variables and then to 127.0.0.1:3000. Use `--host 0.0.0.0` inside containers
+    pub fn from_args(
+        args: &[String],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<ListenAddress, String> {
+        let mut host = env("HOST");
+        let mut port = env("PORT");
+        let mut unix_socket = env("UNIX_SOCKET");
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let target = match flag.as_str() {
+                "--host" => &mut host,
+                "--port" => &mut port,
+                "--unix-socket" => &mut unix_socket,
+                _ => return Err(format!("unknown argument {}", flag)),
+            };
+            match args.next() {
+                Some(value) => *target = Some(value.clone()),
+                None => return Err(format!("{} needs a value", flag)),
+            }
+        }
+
+        if let Some(path) = unix_socket.filter(|path| !path.is_empty()) {
+            if cfg!(unix) {
+                return Ok(ListenAddress::Unix(PathBuf::from(path)));
+            }
+            return Err(String::from("unix sockets are only supported on unix"));
+        }
+
+        let ip = match host {
+            Some(host) => host
+                .parse::<IpAddr>()
+                .map_err(|_| format!("invalid host {}, expected an IP address", host))?,
+            None => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        };
+        let port = match port {
+            Some(port) => port
+                .parse::<u16>()
+                .map_err(|_| format!("invalid port {}", port))?,
+            None => DEFAULT_PORT,
+        };
+
+        Ok(ListenAddress::Tcp(SocketAddr::new(ip, port)))
+    }
+}
+
+impl ListenAddress {
+    // Where websocket clients connect, `ws://host:port/game` for TCP
+    pub fn websocket_url(&self) -> String {
+        match self {
+            ListenAddress::Tcp(addr) => format!("ws://{}/game", addr),
+            ListenAddress::Unix(path) => format!("ws+unix:{}:/game", path.display()),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddress::Tcp(addr) => write!(f, "http://{}", addr),
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
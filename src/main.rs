@@ -1,152 +1,217 @@
-use axum::{
-    extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
-    },
-    response::IntoResponse,
-    routing::get,
-    Router,
-};
-use futures::{
-    sink::SinkExt,
-    stream::{SplitSink, StreamExt},
-};
-
-use std::{
-    collections::HashMap,
-    net::SocketAddr,
-    sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc,
-    },
-};
-use tokio::sync::{broadcast, mpsc, Mutex};
-use tower_http::cors::CorsLayer;
-
-mod game_manager;
-mod player;
-mod vector;
-use game_manager::{Command, GameManager, InternalCommand, MessageToClient};
-
-use crate::game_manager::{PlayerCommand, PlayerMessage};
-
-#[derive(serde::Deserialize, serde::Serialize, Clone)]
-struct User {
-    username: String,
-    password: String,
-}
-
-struct AppState {
-    tx_game_manager: mpsc::Sender<Command>,
-    rx_game_manager: broadcast::Sender<MessageToClient>,
-    id_tracker: Arc<AtomicU32>,
-    players_sockets: Arc<Mutex<HashMap<u32, Arc<Mutex<SplitSink<WebSocket, Message>>>>>>,
-}
-
-#[tokio::main]
-async fn main() {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-
-    // This channel is used to send messages to all the players
-    let (broadcast_channel, _) = broadcast::channel::<MessageToClient>(100);
-    let game_manager = GameManager::new(broadcast_channel.clone());
-    let command_tx = game_manager.command_tx.clone();
-
-    let app_state = Arc::new(AppState {
-        tx_game_manager: command_tx.clone(),
-        rx_game_manager: broadcast_channel.clone(),
-        id_tracker: Arc::new(AtomicU32::new(0)),
-        players_sockets: game_manager.players_sockets.clone(),
-    });
-
-    game_manager.start();
-
-    let app = Router::new()
-        .route("/game", get(websocket_handler))
-        .with_state(app_state)
-        .layer(CorsLayer::very_permissive());
-
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+
+use block_explorer::accounts::{Accounts, ACCOUNTS_PATH};
+use block_explorer::audit::AUDIT_LOG_PATH;
+use block_explorer::config::{self, GameConfig, CONFIG_PATH};
+use block_explorer::crash;
+use block_explorer::leaderboard::{Leaderboard, LEADERBOARD_PATH};
+use block_explorer::listen::ListenAddress;
+use block_explorer::map::Map;
+use block_explorer::metrics::{ConnectionMetrics, TickMetrics};
+use block_explorer::protocol;
+use block_explorer::proxy::TrustedProxies;
+use block_explorer::rate_limit::UpgradeLimiter;
+use block_explorer::registry;
+use block_explorer::room::{GameMode, Room, RoomInfo};
+use block_explorer::server::{self, AppState};
+use block_explorer::snapshot::WorldSnapshot;
+use block_explorer::storage;
+
+// A broken map stops the server, falling back to another layout would go unnoticed
+fn load_maps(room_id: u32) -> Vec<Arc<Map>> {
+    match Map::load_rotation(room_id) {
+        Ok(maps) if maps.is_empty() => vec![Arc::new(Map::open(800.0, 600.0))],
+        Ok(maps) => {
+            let names: Vec<&str> = maps.iter().map(|map| map.name.as_str()).collect();
+            println!("Room {} plays {}", room_id, names.join(", "));
+            maps.into_iter().map(Arc::new).collect()
+        }
+        Err((path, error)) => {
+            println!("Error loading {}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
 }
 
-async fn websocket_handler(
-    ws: WebSocketUpgrade,
-    State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| websocket_connection(socket, state))
+// Every core gets a worker when `worker_threads` is 0
+fn build_runtime(name: &str, worker_threads: usize) -> tokio::runtime::Runtime {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all().thread_name(name);
+    if worker_threads > 0 {
+        builder.worker_threads(worker_threads);
+    }
+
+    match builder.build() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            println!("Error starting the {} runtime: {}", name, error);
+            std::process::exit(1);
+        }
+    }
 }
 
-async fn websocket_connection(stream: WebSocket, state: Arc<AppState>) {
-    let id = state.id_tracker.fetch_add(1, Ordering::SeqCst);
-    let (socket_sender, mut socket_receiver) = stream.split();
-
-    let tx_game_manager = state.tx_game_manager.clone();
-    let mut rx_game_manager = state.rx_game_manager.subscribe();
-
-    // Adds the socket to the list of sockets so that the game manager can send messages directly to a player
-    let socket_sender = Arc::new(Mutex::new(socket_sender));
-    let mut players_sockets = state.players_sockets.lock().await;
-    players_sockets.insert(id, socket_sender.clone());
-
-    // Recieves messages from the game manager and sends them to the client
-    tokio::spawn(async move {
-        while let Ok(msg) = rx_game_manager.recv().await {
-            let msg_string = serde_json::to_string::<MessageToClient>(&msg);
-            match msg_string {
-                Ok(msg_string) => {
-                    let sender = socket_sender.clone();
-                    let mut sender = sender.lock().await;
-
-                    if let Err(e) = sender.send(Message::Text(msg_string.clone())).await {
-                        println!("Error sending message to client {}", e);
-                        break;
-                    }
-                }
-                Err(e) => println!("Error serializing message: {}", e),
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.as_slice() {
+        [_, flag, path] if flag == "--generate-types" => {
+            if let Err(error) = std::fs::write(path, protocol::typescript()) {
+                println!("Error writing types: {}", error);
             }
+            return;
+        }
+        [_, flag] if flag == "--dump-schema" => {
+            println!("{:#}", protocol::json_schema());
+            return;
+        }
+        _ => {}
+    }
+
+    let listen_address = match ListenAddress::from_args(&args[1..], |name| std::env::var(name).ok())
+    {
+        Ok(listen_address) => listen_address,
+        Err(error) => {
+            println!("Error: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    crash::install_hook();
+
+    let config = GameConfig::load(CONFIG_PATH);
+    let runtime = build_runtime("network", config.worker_threads);
+    // The rooms share the network runtime unless the simulation gets threads of its own
+    let simulation = (config.simulation_threads > 0)
+        .then(|| build_runtime("simulation", config.simulation_threads));
+    let simulation_handle = match &simulation {
+        Some(simulation) => {
+            println!(
+                "Rooms run on {} dedicated threads",
+                config.simulation_threads
+            );
+            simulation.handle().clone()
         }
-    });
+        None => runtime.handle().clone(),
+    };
 
-    // Recieves messages from the client and sends them to the game manager
-    tokio::spawn(async move {
-        while let Some(Ok(Message::Text(text))) = socket_receiver.next().await {
-            println!("Received message from client: {}", text);
+    runtime.block_on(serve(listen_address, config, simulation_handle));
 
-            let command_from_socket = serde_json::from_str::<PlayerCommand>(&text);
+    // The rooms saved their worlds during the graceful shutdown, whatever is left can be dropped
+    if let Some(simulation) = simulation {
+        simulation.shutdown_timeout(Duration::from_secs(1));
+    }
+}
 
-            let command_from_socket = match command_from_socket {
-                Ok(command_from_socket) => command_from_socket,
-                Err(e) => {
-                    println!("Error deserializing message: {}", e);
-                    continue;
+async fn serve(listen_address: ListenAddress, config: GameConfig, simulation: Handle) {
+    let tick_metrics = Arc::new(TickMetrics::default());
+    let connection_metrics = Arc::new(ConnectionMetrics::default());
+    let leaderboard = Arc::new(std::sync::Mutex::new(Leaderboard::load(LEADERBOARD_PATH)));
+    let accounts = Arc::new(std::sync::Mutex::new(Accounts::load(ACCOUNTS_PATH)));
+    storage::keep_saved(leaderboard.clone());
+    storage::keep_saved(accounts.clone());
+
+    // Game managers spawn their task on the runtime entered here and stay there
+    let simulation_guard = simulation.enter();
+    // Two ranked rooms so players of different levels can be kept apart
+    let rooms: Vec<Room> = [
+        GameMode::FreeForAll,
+        GameMode::Squads,
+        GameMode::Sandbox,
+        GameMode::Ranked,
+        GameMode::Ranked,
+        GameMode::LastManStanding,
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(id, mode)| {
+        let id = id as u32;
+        Room::start(
+            RoomInfo {
+                snapshot_path: Some(WorldSnapshot::path(id)),
+                audit_log_path: Some(String::from(AUDIT_LOG_PATH)),
+                ..RoomInfo::new(id, mode, load_maps(id), 50)
+            },
+            config.clone(),
+            tick_metrics.clone(),
+            connection_metrics.clone(),
+            leaderboard.clone(),
+            accounts.clone(),
+        )
+    })
+    .collect();
+    drop(simulation_guard);
+
+    config::watch(
+        CONFIG_PATH,
+        rooms.iter().map(|room| room.command_tx.clone()).collect(),
+    );
+
+    let mut app_state = AppState::new(
+        rooms,
+        tick_metrics,
+        connection_metrics,
+        leaderboard,
+        accounts,
+    );
+    app_state.trusted_proxies = TrustedProxies::parse(&config.trusted_proxies);
+    app_state.upgrade_limiter = UpgradeLimiter::new(config.upgrades_per_minute);
+    let app_state = Arc::new(app_state);
+    let app = server::router(app_state.clone());
+
+    if let Some(master_server_url) = &config.master_server_url {
+        let address = match &config.public_address {
+            Some(address) => address.clone(),
+            None => listen_address.websocket_url(),
+        };
+        registry::start(
+            master_server_url.clone(),
+            address,
+            config.region.clone(),
+            Duration::from_secs(config.heartbeat_seconds.max(1)),
+            app_state.clone(),
+        );
+    }
+
+    println!("Listening on {}", listen_address);
+    let result = match listen_address {
+        ListenAddress::Tcp(addr) => {
+            axum::Server::bind(&addr)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(server::shutdown_signal(app_state))
+                .await
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            // A socket file left by a previous run would make the bind fail
+            let _ = std::fs::remove_file(&path);
+            let listener = match tokio::net::UnixListener::bind(&path) {
+                Ok(listener) => listener,
+                Err(error) => {
+                    println!("Error binding {}: {}", path.display(), error);
+                    std::process::exit(1);
                 }
             };
-
-            // Adds the ID to the command so that the game manager knows which player sent the command
-            let command_from_socket = PlayerMessage {
-                id,
-                command: command_from_socket,
-            };
-
-            if let Err(e) = tx_game_manager
-                .send(Command::PlayerCommand(command_from_socket))
+            let accept = hyper::server::accept::poll_fn(move |cx| {
+                listener
+                    .poll_accept(cx)
+                    .map(|result| Some(result.map(|(stream, _)| stream)))
+            });
+
+            axum::Server::builder(accept)
+                .serve(app.into_make_service())
+                .with_graceful_shutdown(server::shutdown_signal(app_state))
                 .await
-            {
-                println!("Error sending message to game manager: {}", e);
-            };
         }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(_) => {
+            unreachable!("unix sockets are rejected when parsing the arguments")
+        }
+    };
 
-        // Client disconnected
-        if let Err(e) = tx_game_manager
-            .send(Command::InternalCommand(InternalCommand::RemovePlayer {
-                id,
-            }))
-            .await
-        {
-            println!("Error sending message to game manager: {}", e);
-        };
-    });
+    if let Err(error) = result {
+        println!("Server error: {}", error);
+    }
 }
@@ -0,0 +1,428 @@
+use std::fmt;
+
+use rand::Rng;
+
+use crate::vector::Vector2D;
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn contains(&self, point: Vector2D) -> bool {
+        point.x >= self.x
+            && point.x <= self.x + self.width
+            && point.y >= self.y
+            && point.y <= self.y + self.height
+    }
+
+    pub fn area(&self) -> f32 {
+        self.width * self.height
+    }
+
+    // A random point at least `margin` away from the edges, or the center when the rect is too small for that
+    pub fn random_point(&self, margin: f32) -> Vector2D {
+        let mut rng = rand::thread_rng();
+        let margin_x = margin.min(self.width / 2.0);
+        let margin_y = margin.min(self.height / 2.0);
+        Vector2D::new(
+            rng.gen_range(self.x + margin_x..=self.x + self.width - margin_x),
+            rng.gen_range(self.y + margin_y..=self.y + self.height - margin_y),
+        )
+    }
+
+    fn is_inside(&self, width: f32, height: f32) -> bool {
+        self.x >= 0.0
+            && self.y >= 0.0
+            && self.x + self.width <= width
+            && self.y + self.height <= height
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Circle {
+    pub position: Vector2D,
+    pub radius: f32,
+}
+
+// Entrance and exit of a portal. Only part of the file format for now, portals are validated and sent
+// to the clients but the server doesn't teleport anyone yet
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Portal {
+    pub entrance: Circle,
+    pub exit: Vector2D,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum TerrainKind {
+    // Slows players down
+    Mud,
+    // Speeds players up
+    Boost,
+    // Drains the mass of players inside
+    Danger,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TerrainZone {
+    pub kind: TerrainKind,
+    pub area: Rect,
+    // Speed multiplier for mud and boost pads, fraction of mass drained per second in danger zones
+    pub strength: f32,
+}
+
+// Players lighter than `max_mass` can't be eaten inside, so new spawns get some breathing room.
+// Heavier players move at `speed` times their speed there
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SafeZone {
+    pub area: Rect,
+    pub max_mass: f32,
+    pub speed: f32,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Read(std::io::Error),
+    Parse(serde_json::Error),
+    // Says which value is wrong, like `spawn_zones[1]: outside the map`
+    Invalid(String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Read(error) => write!(f, "can't read the map file: {}", error),
+            MapError::Parse(error) => write!(f, "invalid map json: {}", error),
+            MapError::Invalid(message) => write!(f, "invalid map, {}", message),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+// Static layout of a room, loaded from `maps/room_{id}.json` when that file exists, or from
+// every file in `maps/room_{id}/` to rotate between them. The lists are optional, a map with
+// none of them is an open rectangle
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Map {
+    pub name: String,
+    pub width: f32,
+    pub height: f32,
+    // Obstacles, virus regions and portals are only validated and sent to the clients, nothing simulates them yet
+    #[serde(default)]
+    pub obstacles: Vec<Circle>,
+    // Where players appear when they join, players start at the origin without any
+    #[serde(default)]
+    pub spawn_zones: Vec<Rect>,
+    // Where food grows, anywhere on the map without any
+    #[serde(default)]
+    pub food_regions: Vec<Rect>,
+    #[serde(default)]
+    pub virus_regions: Vec<Rect>,
+    #[serde(default)]
+    pub portals: Vec<Portal>,
+    // Overlapping zones stack
+    #[serde(default)]
+    pub terrain: Vec<TerrainZone>,
+    #[serde(default)]
+    pub safe_zones: Vec<SafeZone>,
+}
+
+impl Map {
+    pub fn path(room_id: u32) -> String {
+        format!("maps/room_{}.json", room_id)
+    }
+
+    pub fn rotation_path(room_id: u32) -> String {
+        format!("maps/room_{}", room_id)
+    }
+
+    // The maps of a room in the order they are played, empty when it has no map files.
+    // Errors come with the path of the file at fault
+    pub fn load_rotation(room_id: u32) -> Result<Vec<Map>, (String, MapError)> {
+        let directory = Map::rotation_path(room_id);
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_) => {
+                let path = Map::path(room_id);
+                if !std::path::Path::new(&path).exists() {
+                    return Ok(Vec::new());
+                }
+                return Map::load(&path)
+                    .map(|map| vec![map])
+                    .map_err(|error| (path, error));
+            }
+        };
+
+        let mut paths: Vec<std::path::PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "json")
+            })
+            .collect();
+        paths.sort();
+
+        paths
+            .iter()
+            .map(|path| {
+                let path = path.to_string_lossy();
+                Map::load(&path).map_err(|error| (path.into_owned(), error))
+            })
+            .collect()
+    }
+
+    pub fn open(width: f32, height: f32) -> Map {
+        Map {
+            name: String::from("Open"),
+            width,
+            height,
+            obstacles: Vec::new(),
+            spawn_zones: Vec::new(),
+            food_regions: Vec::new(),
+            virus_regions: Vec::new(),
+            portals: Vec::new(),
+            terrain: Vec::new(),
+            safe_zones: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Map, MapError> {
+        let contents = std::fs::read_to_string(path).map_err(MapError::Read)?;
+        Map::from_json(&contents)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Map, MapError> {
+        let map: Map = serde_json::from_str(contents).map_err(MapError::Parse)?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    pub fn validate(&self) -> Result<(), MapError> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+
+        if self.name.trim().is_empty() {
+            return Err(MapError::Invalid(String::from("name: empty")));
+        }
+        if !positive(self.width) || !positive(self.height) {
+            return Err(MapError::Invalid(String::from(
+                "width and height: must be positive numbers",
+            )));
+        }
+
+        let inside = |point: Vector2D| {
+            point.is_finite()
+                && (0.0..=self.width).contains(&point.x)
+                && (0.0..=self.height).contains(&point.y)
+        };
+
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            if !positive(obstacle.radius) || !inside(obstacle.position) {
+                return Err(invalid(
+                    "obstacles",
+                    index,
+                    "outside the map or without a radius",
+                ));
+            }
+        }
+
+        let zones = [
+            ("spawn_zones", &self.spawn_zones),
+            ("food_regions", &self.food_regions),
+            ("virus_regions", &self.virus_regions),
+        ];
+        for (name, rects) in zones {
+            for (index, rect) in rects.iter().enumerate() {
+                if !positive(rect.width)
+                    || !positive(rect.height)
+                    || !rect.is_inside(self.width, self.height)
+                {
+                    return Err(invalid(name, index, "outside the map or without a size"));
+                }
+            }
+        }
+
+        for (index, portal) in self.portals.iter().enumerate() {
+            if !positive(portal.entrance.radius) || !inside(portal.entrance.position) {
+                return Err(invalid(
+                    "portals",
+                    index,
+                    "entrance outside the map or without a radius",
+                ));
+            }
+            if !inside(portal.exit) {
+                return Err(invalid("portals", index, "exit outside the map"));
+            }
+        }
+
+        for (index, zone) in self.terrain.iter().enumerate() {
+            let area = zone.area;
+            if !positive(area.width)
+                || !positive(area.height)
+                || !area.is_inside(self.width, self.height)
+            {
+                return Err(invalid(
+                    "terrain",
+                    index,
+                    "outside the map or without a size",
+                ));
+            }
+            let valid_strength = match zone.kind {
+                TerrainKind::Mud => positive(zone.strength) && zone.strength < 1.0,
+                TerrainKind::Boost => zone.strength.is_finite() && zone.strength > 1.0,
+                TerrainKind::Danger => positive(zone.strength) && zone.strength <= 1.0,
+            };
+            if !valid_strength {
+                return Err(invalid(
+                    "terrain",
+                    index,
+                    "strength must be below 1 for mud, above 1 for boost pads and at most 1 for danger zones",
+                ));
+            }
+        }
+
+        for (index, zone) in self.safe_zones.iter().enumerate() {
+            let area = zone.area;
+            if !positive(area.width)
+                || !positive(area.height)
+                || !area.is_inside(self.width, self.height)
+            {
+                return Err(invalid(
+                    "safe_zones",
+                    index,
+                    "outside the map or without a size",
+                ));
+            }
+            if !positive(zone.max_mass) || !positive(zone.speed) || zone.speed > 1.0 {
+                return Err(invalid(
+                    "safe_zones",
+                    index,
+                    "max_mass must be positive and speed between 0 and 1",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // What mud and boost pads under `position` multiply the speed by, and safe zones for players
+    // too heavy to be protected there
+    pub fn speed_multiplier(&self, position: Vector2D, mass: f32) -> f32 {
+        let terrain: f32 = self
+            .terrain
+            .iter()
+            .filter(|zone| zone.kind != TerrainKind::Danger && zone.area.contains(position))
+            .map(|zone| zone.strength)
+            .product();
+        let safe_zones: f32 = self
+            .safe_zones
+            .iter()
+            .filter(|zone| mass >= zone.max_mass && zone.area.contains(position))
+            .map(|zone| zone.speed)
+            .product();
+        terrain * safe_zones
+    }
+
+    // Whether a player of `mass` at `position` can't be eaten
+    pub fn is_protected(&self, position: Vector2D, mass: f32) -> bool {
+        self.safe_zones
+            .iter()
+            .any(|zone| mass < zone.max_mass && zone.area.contains(position))
+    }
+
+    // Fraction of mass lost per second by a player at `position`
+    pub fn drain(&self, position: Vector2D) -> f32 {
+        let drain: f32 = self
+            .terrain
+            .iter()
+            .filter(|zone| zone.kind == TerrainKind::Danger && zone.area.contains(position))
+            .map(|zone| zone.strength)
+            .sum();
+        drain.min(1.0)
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect {
+            x: 0.0,
+            y: 0.0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn spawn_point(&self) -> Option<Vector2D> {
+        if self.spawn_zones.is_empty() {
+            return None;
+        }
+        Some(random_point_in(&self.spawn_zones, self.bounds(), 0.0))
+    }
+
+    pub fn food_point(&self, radius: f32) -> Vector2D {
+        random_point_in(&self.food_regions, self.bounds(), radius)
+    }
+
+    // `count` points spread out over the food regions, each the best of a few candidates at placing it far from
+    // the others so they don't clump like uniform points do
+    pub fn cluster_centers(&self, count: u32) -> Vec<Vector2D> {
+        let mut centers: Vec<Vector2D> = Vec::new();
+        for _ in 0..count {
+            let distance = |candidate: &Vector2D| {
+                centers
+                    .iter()
+                    .map(|&center| (center - *candidate).magnitude())
+                    .fold(f32::INFINITY, f32::min)
+            };
+            let best = (0..CLUSTER_CANDIDATES)
+                .map(|_| self.food_point(0.0))
+                .max_by(|a, b| distance(a).total_cmp(&distance(b)));
+            centers.extend(best);
+        }
+        centers
+    }
+
+    // The centered part of the map spanning `scale` of its width and height
+    pub fn scaled_bounds(&self, scale: f32) -> Rect {
+        let scale = scale.clamp(0.0, 1.0);
+        Rect {
+            x: self.width * (1.0 - scale) / 2.0,
+            y: self.height * (1.0 - scale) / 2.0,
+            width: self.width * scale,
+            height: self.height * scale,
+        }
+    }
+}
+
+// Candidates tried for every cluster center
+const CLUSTER_CANDIDATES: usize = 10;
+
+fn invalid(list: &str, index: usize, problem: &str) -> MapError {
+    MapError::Invalid(format!("{}[{}]: {}", list, index, problem))
+}
+
+// Picks one of the rects with a chance proportional to its area, or `fallback` if there are none
+fn random_point_in(rects: &[Rect], fallback: Rect, margin: f32) -> Vector2D {
+    let total_area: f32 = rects.iter().map(Rect::area).sum();
+    if rects.is_empty() || total_area <= 0.0 {
+        return fallback.random_point(margin);
+    }
+
+    let mut target = rand::thread_rng().gen_range(0.0..total_area);
+    for rect in rects {
+        if target < rect.area() {
+            return rect.random_point(margin);
+        }
+        target -= rect.area();
+    }
+
+    rects[rects.len() - 1].random_point(margin)
+}
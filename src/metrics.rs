@@ -0,0 +1,214 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::game_manager::TICKS_PER_SECOND;
+use crate::room::RoomInfo;
+
+// Upper bounds of the tick duration histogram buckets, in milliseconds
+const TICK_BUCKETS: [f64; 9] = [0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 250.0];
+
+#[derive(Default)]
+pub struct TickMetrics {
+    buckets: [AtomicU64; TICK_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    slow_ticks: AtomicU64,
+}
+
+impl TickMetrics {
+    pub fn observe(&self, duration: Duration) {
+        let milliseconds = duration.as_secs_f64() * 1000.0;
+
+        for (bucket, upper_bound) in self.buckets.iter().zip(TICK_BUCKETS) {
+            if milliseconds <= upper_bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn observe_slow_tick(&self) {
+        self.slow_ticks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Renders the metrics using the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        let count = self.count.load(Ordering::Relaxed);
+        let sum_seconds = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+
+        output.push_str(
+            "# HELP game_tick_duration_seconds Time spent simulating and publishing a tick\n",
+        );
+        output.push_str("# TYPE game_tick_duration_seconds histogram\n");
+        for (bucket, upper_bound) in self.buckets.iter().zip(TICK_BUCKETS) {
+            let _ = writeln!(
+                output,
+                "game_tick_duration_seconds_bucket{{le=\"{}\"}} {}",
+                upper_bound / 1000.0,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let _ = writeln!(
+            output,
+            "game_tick_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(output, "game_tick_duration_seconds_sum {}", sum_seconds);
+        let _ = writeln!(output, "game_tick_duration_seconds_count {}", count);
+
+        output.push_str("# HELP game_slow_ticks_total Ticks that exceeded the tick budget\n");
+        output.push_str("# TYPE game_slow_ticks_total counter\n");
+        let _ = writeln!(
+            output,
+            "game_slow_ticks_total {}",
+            self.slow_ticks.load(Ordering::Relaxed)
+        );
+
+        output
+    }
+}
+
+// Counters of the websocket connections of every room, the per-connection numbers are logged on disconnect
+#[derive(Default)]
+pub struct ConnectionMetrics {
+    connections: AtomicU64,
+    joins: AtomicU64,
+    kicks: AtomicU64,
+    disconnects: AtomicU64,
+    duration_micros: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+}
+
+impl ConnectionMetrics {
+    pub fn observe_connect(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_join(&self) {
+        self.joins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_kick(&self) {
+        self.kicks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_disconnect(&self, duration: Duration) {
+        self.disconnects.fetch_add(1, Ordering::Relaxed);
+        self.duration_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn observe_sent(&self, bytes: usize) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn observe_received(&self, bytes: usize) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        let counters = [
+            (
+                "game_connections_total",
+                "Websocket connections opened",
+                &self.connections,
+            ),
+            (
+                "game_joins_total",
+                "Connections that joined a room",
+                &self.joins,
+            ),
+            (
+                "game_kicks_total",
+                "Connections kicked by an admin",
+                &self.kicks,
+            ),
+            (
+                "game_disconnects_total",
+                "Websocket connections closed",
+                &self.disconnects,
+            ),
+            (
+                "game_bytes_sent_total",
+                "Bytes of messages sent to clients",
+                &self.bytes_sent,
+            ),
+            (
+                "game_bytes_received_total",
+                "Bytes of messages received from clients",
+                &self.bytes_received,
+            ),
+            (
+                "game_messages_sent_total",
+                "Messages sent to clients",
+                &self.messages_sent,
+            ),
+            (
+                "game_messages_received_total",
+                "Messages received from clients",
+                &self.messages_received,
+            ),
+        ];
+
+        for (name, help, counter) in counters {
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} counter", name);
+            let _ = writeln!(output, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+
+        output.push_str(
+            "# HELP game_connection_duration_seconds_total Time spent connected by the closed connections\n",
+        );
+        output.push_str("# TYPE game_connection_duration_seconds_total counter\n");
+        let _ = writeln!(
+            output,
+            "game_connection_duration_seconds_total {}",
+            self.duration_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+
+        output
+    }
+}
+
+// Simulation and broadcast rates of every room, the broadcasts slow down while a room is overloaded
+pub fn render_rates<'a>(rooms: impl Iterator<Item = &'a RoomInfo>) -> String {
+    let mut rooms: Vec<&RoomInfo> = rooms.collect();
+    rooms.sort_by_key(|room| room.id);
+
+    let mut output = String::new();
+    output.push_str("# HELP game_simulation_rate_hz Ticks simulated per second\n");
+    output.push_str("# TYPE game_simulation_rate_hz gauge\n");
+    for room in &rooms {
+        let _ = writeln!(
+            output,
+            "game_simulation_rate_hz{{room=\"{}\"}} {}",
+            room.id, TICKS_PER_SECOND
+        );
+    }
+
+    output.push_str("# HELP game_broadcast_rate_hz States sent to the connections per second\n");
+    output.push_str("# TYPE game_broadcast_rate_hz gauge\n");
+    for room in &rooms {
+        let stride = room.broadcast_stride.load(Ordering::Relaxed).max(1);
+        let _ = writeln!(
+            output,
+            "game_broadcast_rate_hz{{room=\"{}\"}} {}",
+            room.id,
+            TICKS_PER_SECOND as f64 / stride as f64
+        );
+    }
+
+    output
+}
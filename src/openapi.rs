@@ -0,0 +1,360 @@
+use serde_json::json;
+
+// OpenAPI description of the HTTP routes, keep it in sync with the router in `server.rs`
+pub fn spec() -> serde_json::Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "luis_gar.io",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/game": {
+                "get": {
+                    "summary": "Websocket endpoint used to play, see `/schema` for the messages",
+                    "parameters": [{
+                        "name": "room",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "integer", "default": 0 },
+                    }, {
+                        "name": "encoding",
+                        "in": "query",
+                        "required": false,
+                        "description": "`binary` sends the state deltas as quantized binary frames, decoded with the `quantization` of `Welcome`",
+                        "schema": { "type": "string", "enum": ["json", "binary"], "default": "json" },
+                    }, {
+                        "name": "Sec-WebSocket-Protocol",
+                        "in": "header",
+                        "required": false,
+                        "description": "`luisgar.json.v1` or `luisgar.bin.v1`, picks the encoding over the `encoding` query",
+                        "schema": { "type": "string" },
+                    }],
+                    "responses": {
+                        "101": { "description": "Switching to the websocket protocol" },
+                        "400": { "description": "None of the offered subprotocols is supported" },
+                        "403": { "description": "The client address is banned, the body says why" },
+                        "404": { "description": "Room not found" },
+                        "429": { "description": "Too many connection attempts from this address, see the Retry-After header" },
+                        "503": { "description": "The room crashed and is down until a restart" },
+                    },
+                },
+            },
+            "/rooms": {
+                "get": {
+                    "summary": "Rooms running on this server",
+                    "responses": {
+                        "200": {
+                            "description": "Room list",
+                            "content": { "application/json": { "schema": {
+                                "type": "array",
+                                "items": { "$ref": "#/components/schemas/RoomSummary" },
+                            }}},
+                        },
+                    },
+                },
+            },
+            "/stats": {
+                "get": {
+                    "summary": "Players online, rooms, the biggest player and the uptime, cached for a few seconds",
+                    "responses": {
+                        "200": {
+                            "description": "Server stats",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "players_online": { "type": "integer" },
+                                    "rooms": { "type": "integer" },
+                                    "biggest_player": {
+                                        "type": "object",
+                                        "nullable": true,
+                                        "properties": {
+                                            "name": { "type": "string" },
+                                            "mass": { "type": "number" },
+                                            "room": { "type": "integer" },
+                                        },
+                                        "required": ["name", "mass", "room"],
+                                    },
+                                    "uptime_seconds": { "type": "integer" },
+                                },
+                                "required": ["players_online", "rooms", "biggest_player", "uptime_seconds"],
+                            }}},
+                        },
+                    },
+                },
+            },
+            "/matchmaking/ranked": {
+                "get": {
+                    "summary": "Picks the ranked room whose players have the closest ratings",
+                    "parameters": [{
+                        "name": "token",
+                        "in": "query",
+                        "required": true,
+                        "schema": { "type": "string" },
+                    }],
+                    "responses": {
+                        "200": {
+                            "description": "Room to join with the account's rating",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "room": { "type": "integer" },
+                                    "rating": { "type": "number" },
+                                },
+                                "required": ["room", "rating"],
+                            }}},
+                        },
+                        "401": { "description": "Invalid token" },
+                        "503": { "description": "No ranked room available" },
+                    },
+                },
+            },
+            "/leaderboard": {
+                "get": {
+                    "summary": "Best masses of finished sessions",
+                    "parameters": [{
+                        "name": "period",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "string", "enum": ["all", "daily", "weekly"], "default": "all" },
+                    }],
+                    "responses": {
+                        "200": {
+                            "description": "Leaderboard, best first",
+                            "content": { "application/json": { "schema": {
+                                "type": "array",
+                                "items": { "$ref": "#/components/schemas/ScoreRecord" },
+                            }}},
+                        },
+                    },
+                },
+            },
+            "/accounts": {
+                "post": {
+                    "summary": "Registers an account, the returned token is only shown once",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "type": "object",
+                            "properties": { "username": { "type": "string" } },
+                            "required": ["username"],
+                        }}},
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "Account created",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "username": { "type": "string" },
+                                    "token": { "type": "string" },
+                                },
+                                "required": ["username", "token"],
+                            }}},
+                        },
+                        "400": { "description": "Invalid username" },
+                        "409": { "description": "Username already taken" },
+                    },
+                },
+            },
+            "/debug/state": {
+                "get": {
+                    "summary": "The world of a room as JSON: tick, players with their private state, food and config",
+                    "parameters": [{
+                        "name": "room",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "integer", "default": 0 },
+                    }, {
+                        "name": "Authorization",
+                        "in": "header",
+                        "required": true,
+                        "description": "`Bearer` and the `admin_token` of the room",
+                        "schema": { "type": "string" },
+                    }],
+                    "responses": {
+                        "200": { "description": "World dump, the config without its admin token" },
+                        "401": { "description": "Missing or wrong admin token" },
+                        "404": { "description": "Room not found" },
+                        "503": { "description": "The room is down" },
+                    },
+                },
+            },
+            "/players/{username}": {
+                "get": {
+                    "summary": "Lifetime stats of a registered account",
+                    "parameters": [{
+                        "name": "username",
+                        "in": "path",
+                        "required": true,
+                        "schema": { "type": "string" },
+                    }],
+                    "responses": {
+                        "200": {
+                            "description": "Player profile",
+                            "content": { "application/json": { "schema": {
+                                "$ref": "#/components/schemas/Profile",
+                            }}},
+                        },
+                        "404": { "description": "Player not found" },
+                    },
+                },
+            },
+            "/clans": {
+                "get": {
+                    "summary": "Clans ordered by the combined best masses of their members",
+                    "responses": {
+                        "200": {
+                            "description": "Clan leaderboard, best first",
+                            "content": { "application/json": { "schema": {
+                                "type": "array",
+                                "items": { "$ref": "#/components/schemas/ClanScore" },
+                            }}},
+                        },
+                    },
+                },
+                "post": {
+                    "summary": "Creates a clan, its owner joins it",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "type": "object",
+                            "properties": {
+                                "token": { "type": "string" },
+                                "tag": { "type": "string" },
+                                "name": { "type": "string" },
+                            },
+                            "required": ["token", "tag", "name"],
+                        }}},
+                    },
+                    "responses": {
+                        "201": { "description": "Clan created" },
+                        "400": { "description": "Invalid tag or name" },
+                        "401": { "description": "Invalid token" },
+                        "409": { "description": "Clan tag already taken" },
+                    },
+                },
+            },
+            "/clans/{tag}/join": {
+                "post": {
+                    "summary": "Joins a clan, leaving the current one",
+                    "parameters": [{
+                        "name": "tag",
+                        "in": "path",
+                        "required": true,
+                        "schema": { "type": "string" },
+                    }],
+                    "requestBody": { "$ref": "#/components/requestBodies/Token" },
+                    "responses": {
+                        "204": { "description": "Joined" },
+                        "401": { "description": "Invalid token" },
+                        "404": { "description": "Clan not found" },
+                    },
+                },
+            },
+            "/clans/leave": {
+                "post": {
+                    "summary": "Leaves the current clan",
+                    "requestBody": { "$ref": "#/components/requestBodies/Token" },
+                    "responses": {
+                        "204": { "description": "Left" },
+                        "401": { "description": "Invalid token" },
+                    },
+                },
+            },
+            "/metrics": {
+                "get": {
+                    "summary": "Prometheus metrics",
+                    "responses": {
+                        "200": {
+                            "description": "Metrics in the Prometheus text format",
+                            "content": { "text/plain": { "schema": { "type": "string" } } },
+                        },
+                    },
+                },
+            },
+            "/schema": {
+                "get": {
+                    "summary": "JSON Schema of the websocket messages",
+                    "responses": {
+                        "200": {
+                            "description": "JSON Schema document",
+                            "content": { "application/json": { "schema": { "type": "object" } } },
+                        },
+                    },
+                },
+            },
+            "/docs": {
+                "get": {
+                    "summary": "This document",
+                    "responses": {
+                        "200": {
+                            "description": "OpenAPI document",
+                            "content": { "application/json": { "schema": { "type": "object" } } },
+                        },
+                    },
+                },
+            },
+        },
+        "components": {
+            "requestBodies": {
+                "Token": {
+                    "required": true,
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "properties": { "token": { "type": "string" } },
+                        "required": ["token"],
+                    }}},
+                },
+            },
+            "schemas": {
+                "RoomSummary": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "integer" },
+                        "player_count": { "type": "integer" },
+                        "mode": { "type": "string", "enum": ["FreeForAll", "Squads", "Sandbox", "Ranked", "LastManStanding"] },
+                        "map": { "type": "string", "description": "Name of the current map" },
+                        "width": { "type": "number" },
+                        "height": { "type": "number" },
+                        "round_underway": { "type": "boolean", "description": "A round without respawns is being played, new players wait for the next one" },
+                        "joinable": { "type": "boolean" },
+                    },
+                    "required": ["id", "player_count", "mode", "map", "width", "height", "round_underway", "joinable"],
+                },
+                "ScoreRecord": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "best_mass": { "type": "number" },
+                        "timestamp": { "type": "integer", "description": "Unix timestamp in seconds" },
+                    },
+                    "required": ["name", "best_mass", "timestamp"],
+                },
+                "Profile": {
+                    "type": "object",
+                    "properties": {
+                        "username": { "type": "string" },
+                        "clan": { "type": "string", "nullable": true },
+                        "games_played": { "type": "integer" },
+                        "best_mass": { "type": "number" },
+                        "total_eats": { "type": "integer" },
+                        "playtime_seconds": { "type": "integer" },
+                        "rating": { "type": "number" },
+                    },
+                    "required": ["username", "clan", "games_played", "best_mass", "total_eats", "playtime_seconds", "rating"],
+                },
+                "ClanScore": {
+                    "type": "object",
+                    "properties": {
+                        "tag": { "type": "string" },
+                        "name": { "type": "string" },
+                        "members": { "type": "integer" },
+                        "mass": { "type": "number", "description": "Sum of the best masses of the members" },
+                    },
+                    "required": ["tag", "name", "members", "mass"],
+                },
+            },
+        },
+    })
+}
@@ -0,0 +1,119 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use crate::crash;
+
+// Below this many checks the work stays on the calling thread, handing it to the pool would cost more than it saves
+pub const PARALLEL_THRESHOLD: usize = 100_000;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Worker threads started on the first big map and kept for every tick after it
+struct Pool {
+    jobs: Mutex<Sender<Job>>,
+    threads: usize,
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for index in 0..threads {
+            let receiver = receiver.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("collision-{}", index))
+                .spawn(move || work(&receiver));
+            if let Err(error) = spawned {
+                println!("Error starting a collision thread: {}", error);
+            }
+        }
+
+        Pool {
+            jobs: Mutex::new(jobs),
+            threads,
+        }
+    })
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(poisoned) => poisoned.into_inner().recv(),
+        };
+        match job {
+            Ok(job) => job(),
+            Err(_) => return,
+        }
+    }
+}
+
+// Maps every item (with its index) through `f`, splitting the items between the pool threads when `checks` is
+// big enough. The items are shared instead of borrowed so the threads outlive the call, and every clone of them
+// is dropped before this returns, so the caller can go back to mutating them without a copy
+pub fn map<T, R, F>(items: &Arc<Vec<T>>, checks: usize, f: F) -> Vec<R>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(usize, &T) -> R + Send + Sync + 'static,
+{
+    if checks < PARALLEL_THRESHOLD || items.len() < 2 || pool().threads == 1 {
+        return items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| f(index, item))
+            .collect();
+    }
+
+    let chunk_size = items.len().div_ceil(pool().threads);
+    let chunks = items.len().div_ceil(chunk_size);
+    let f = Arc::new(f);
+    // Panics on the pool are tagged with the room and tick like the ones on the game thread
+    let context = crash::context();
+    let (results, received) = mpsc::channel::<(usize, Result<Vec<R>, Box<dyn Any + Send>>)>();
+
+    for chunk in 0..chunks {
+        let (items, f, results) = (items.clone(), f.clone(), results.clone());
+        let job: Job = Box::new(move || {
+            let start = chunk * chunk_size;
+            let end = (start + chunk_size).min(items.len());
+            let run = || {
+                (start..end)
+                    .map(|index| f(index, &items[index]))
+                    .collect::<Vec<R>>()
+            };
+            let mapped = panic::catch_unwind(AssertUnwindSafe(|| match context {
+                Some((room, tick)) => crash::in_room(room, tick, run),
+                None => run(),
+            }));
+            drop((items, f));
+            let _ = results.send((chunk, mapped));
+        });
+
+        let sent = match pool().jobs.lock() {
+            Ok(jobs) => jobs.send(job),
+            Err(poisoned) => poisoned.into_inner().send(job),
+        };
+        if sent.is_err() {
+            panic!("The collision threads are gone");
+        }
+    }
+    drop(results);
+
+    let mut mapped: Vec<Option<Vec<R>>> = (0..chunks).map(|_| None).collect();
+    for (chunk, result) in received.iter().take(chunks) {
+        match result {
+            Ok(result) => mapped[chunk] = Some(result),
+            // Carries on unwinding the game thread, so the room crashes as if the check had panicked there
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    mapped.into_iter().flatten().flatten().collect()
+}
@@ -1,34 +1,218 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::accounts::SessionStats;
 use crate::vector::Vector2D;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub const STARTING_RADIUS: f32 = 10.0;
+pub const MAX_NAME_LENGTH: usize = 16;
+pub const RENAME_COOLDOWN: Duration = Duration::from_secs(10);
+// A dash moves the player `DASH_SPEED` times as fast for `DASH_DURATION`, for a fraction of its mass
+pub const DASH_COOLDOWN: Duration = Duration::from_secs(5);
+pub const DASH_DURATION: Duration = Duration::from_millis(300);
+pub const DASH_SPEED: f32 = 3.0;
+pub const DASH_MASS_FRACTION: f32 = 0.05;
+
+// Serializes as `PlayerPublic`, so state frames can borrow the players instead of converting them.
+// The skipped fields only reach the owner, through `PlayerPrivate`
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct Player {
     pub id: u32,
     pub position: Vector2D,
     pub radius: f32,
     pub name: String,
+    // Squad the player belongs to in squad rooms, clients use it to color teammates alike
+    pub squad: Option<u32>,
+    // Cosmetic chosen at join, checked against the account's inventory
+    pub skin: Option<String>,
+    // Largest mass reached during this session, recorded in the leaderboard when the player leaves
+    #[serde(skip)]
+    pub best_mass: f32,
+    #[serde(skip)]
+    pub last_rename: Option<Instant>,
+    #[serde(skip)]
+    pub last_dash: Option<Instant>,
+    // Unit direction and throttle from 0 to 1 set with `PlayerCommand::Steer`, applied every tick until a `Move`
+    #[serde(skip)]
+    pub steering: Option<(Vector2D, f32)>,
+    // Username of the registered account playing, if any
+    #[serde(skip)]
+    pub account: Option<String>,
+    // Tag of the account's clan, shown in front of the name
+    #[serde(skip)]
+    pub clan_tag: Option<String>,
+    // Players eaten during this session
+    #[serde(skip)]
+    pub eats: u32,
+    // Players eaten since the player last died
+    #[serde(skip)]
+    pub streak: u32,
+    #[serde(skip)]
+    pub joined_at: Instant,
+}
+
+// What every connection sees of a player
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlayerPublic {
+    pub id: u32,
+    pub position: Vector2D,
+    pub radius: f32,
+    pub name: String,
+    pub squad: Option<u32>,
+    pub skin: Option<String>,
+}
+
+// What only the owning connection sees of its player
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlayerPrivate {
+    pub id: u32,
+    pub account: Option<String>,
+    pub best_mass: f32,
+    pub eats: u32,
+    pub alive_seconds: f32,
+    // Seconds until the player may rename again, 0 when they can
+    pub rename_cooldown_seconds: f32,
+    // Seconds until the player may dash again, 0 when they can
+    pub dash_cooldown_seconds: f32,
 }
 
 impl Player {
     pub fn new(id: u32, name: String) -> Player {
-        Player {
+        let mut player = Player {
             id,
             name,
             position: Vector2D::new(0.0, 0.0),
-            radius: 10.0,
+            radius: STARTING_RADIUS,
+            squad: None,
+            skin: None,
+            best_mass: 0.0,
+            last_rename: None,
+            last_dash: None,
+            steering: None,
+            account: None,
+            clan_tag: None,
+            eats: 0,
+            streak: 0,
+            joined_at: Instant::now(),
+        };
+        player.update_best_mass();
+        player
+    }
+
+    // Trims the name, removes control characters and limits its length
+    pub fn sanitize_name(name: &str) -> String {
+        name.trim()
+            .chars()
+            .filter(|character| !character.is_control())
+            .take(MAX_NAME_LENGTH)
+            .collect()
+    }
+
+    // `time_scale` speeds the cooldown up or slows it down along with the simulation
+    pub fn can_rename(&self, time_scale: f32) -> bool {
+        self.rename_cooldown(time_scale) <= 0.0
+    }
+
+    // Seconds left before the next rename is allowed
+    pub fn rename_cooldown(&self, time_scale: f32) -> f32 {
+        match self.last_rename {
+            Some(last_rename) => (RENAME_COOLDOWN.as_secs_f32()
+                - last_rename.elapsed().as_secs_f32() * time_scale)
+                .max(0.0),
+            None => 0.0,
         }
     }
 
+    pub fn public(&self) -> PlayerPublic {
+        PlayerPublic {
+            id: self.id,
+            position: self.position,
+            radius: self.radius,
+            name: self.name.clone(),
+            squad: self.squad,
+            skin: self.skin.clone(),
+        }
+    }
+
+    pub fn private(&self, time_scale: f32) -> PlayerPrivate {
+        PlayerPrivate {
+            id: self.id,
+            account: self.account.clone(),
+            best_mass: self.best_mass,
+            eats: self.eats,
+            alive_seconds: self.joined_at.elapsed().as_secs_f32(),
+            rename_cooldown_seconds: self.rename_cooldown(time_scale),
+            dash_cooldown_seconds: self.dash_cooldown(time_scale),
+        }
+    }
+
+    // Seconds left before the next dash is allowed
+    pub fn dash_cooldown(&self, time_scale: f32) -> f32 {
+        match self.last_dash {
+            Some(last_dash) => (DASH_COOLDOWN.as_secs_f32()
+                - last_dash.elapsed().as_secs_f32() * time_scale)
+                .max(0.0),
+            None => 0.0,
+        }
+    }
+
+    pub fn is_dashing(&self, time_scale: f32) -> bool {
+        self.last_dash.is_some_and(|last_dash| {
+            last_dash.elapsed().as_secs_f32() * time_scale < DASH_DURATION.as_secs_f32()
+        })
+    }
+
+    // Pays for the dash like decay does, without shrinking below the starting size
+    pub fn dash(&mut self) {
+        self.decay(DASH_MASS_FRACTION);
+        self.last_dash = Some(Instant::now());
+    }
+
+    pub fn rename(&mut self, name: String) {
+        self.name = self.tagged_name(name);
+        self.last_rename = Some(Instant::now());
+    }
+
+    // The name as shown to everyone, with the clan tag in front
+    pub fn tagged_name(&self, name: String) -> String {
+        match &self.clan_tag {
+            Some(clan_tag) => format!("[{}] {}", clan_tag, name),
+            None => name,
+        }
+    }
+
+    pub fn session_stats(&self) -> SessionStats {
+        SessionStats {
+            best_mass: self.best_mass,
+            eats: self.eats,
+            playtime: self.joined_at.elapsed(),
+        }
+    }
+
+    pub fn update_best_mass(&mut self) {
+        self.best_mass = self.best_mass.max(self.mass());
+    }
+
     pub fn mass(&self) -> f32 {
         2.0 * self.radius.powf(2.0) * std::f32::consts::PI
     }
 
-    pub fn radius_after_eat(player: &Player, other: &Player) -> f32 {
-        let combined_mass = other.mass() + player.mass();
-        (combined_mass / (2.0 * std::f32::consts::PI)).sqrt()
+    pub fn set_mass(&mut self, mass: f32) {
+        self.radius = (mass / (2.0 * std::f32::consts::PI)).sqrt();
+    }
+
+    // Loses a fraction of the mass, without shrinking below the starting size
+    pub fn decay(&mut self, fraction: f32) {
+        if self.radius <= STARTING_RADIUS {
+            return;
+        }
+
+        let radius = self.radius * (1.0 - fraction).sqrt();
+        self.radius = radius.max(STARTING_RADIUS);
     }
 
-    pub fn move_towards(&mut self, position: Vector2D) {
-        let velocity = 100.0 / self.mass().sqrt();
+    pub fn move_towards(&mut self, position: Vector2D, velocity: f32) {
         let mut difference = position - self.position;
 
         if difference.magnitude() < velocity {
@@ -39,3 +223,83 @@ impl Player {
         self.position = self.position + difference;
     }
 }
+
+// Players stored contiguously for the per-tick passes, with an index by id for the commands targeting a single player.
+// Reads go through the slice, writes through the methods below so the index stays in sync.
+// The slice is shared copy-on-write with the snapshots, writes only copy it while one is still being encoded
+#[derive(Debug, Clone, Default)]
+pub struct Players {
+    players: Arc<Vec<Player>>,
+    indices: HashMap<u32, usize>,
+}
+
+impl Players {
+    pub fn new() -> Players {
+        Players::default()
+    }
+
+    // Adds the player, replacing the one with the same id if there is one
+    pub fn insert(&mut self, player: Player) {
+        match self.indices.get(&player.id) {
+            Some(&index) => Arc::make_mut(&mut self.players)[index] = player,
+            None => {
+                self.indices.insert(player.id, self.players.len());
+                Arc::make_mut(&mut self.players).push(player);
+            }
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Player> {
+        self.indices.get(&id).map(|&index| &self.players[index])
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Player> {
+        let players = &mut self.players;
+        self.indices
+            .get(&id)
+            .map(|&index| &mut Arc::make_mut(players)[index])
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.indices.contains_key(&id)
+    }
+
+    // Position of the player in the slice, until a player is removed
+    pub fn index_of(&self, id: u32) -> Option<usize> {
+        self.indices.get(&id).copied()
+    }
+
+    // Removes the player in O(1), the last player takes its place
+    pub fn remove(&mut self, id: u32) -> Option<Player> {
+        let index = self.indices.remove(&id)?;
+        let player = Arc::make_mut(&mut self.players).swap_remove(index);
+
+        if let Some(moved) = self.players.get(index) {
+            self.indices.insert(moved.id, index);
+        }
+
+        Some(player)
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Player> {
+        Arc::make_mut(&mut self.players).iter_mut()
+    }
+
+    // Changing a player's id through this slice would break the index
+    pub fn as_mut_slice(&mut self) -> &mut [Player] {
+        Arc::make_mut(&mut self.players).as_mut_slice()
+    }
+
+    // The players as they are now, without copying them until the next write
+    pub fn snapshot(&self) -> Arc<Vec<Player>> {
+        self.players.clone()
+    }
+}
+
+impl std::ops::Deref for Players {
+    type Target = [Player];
+
+    fn deref(&self) -> &[Player] {
+        self.players.as_slice()
+    }
+}
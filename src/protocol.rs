@@ -0,0 +1,706 @@
+// Description of the messages exchanged with the clients, mirroring how serde encodes them.
+// It has to be kept in sync by hand with `PlayerCommand`, `MessageToClient` and the types they contain.
+// Clients connecting with the `luisgar.bin.v1` subprotocol or `?encoding=binary` get the state deltas as binary frames instead, laid out in `binary.rs`.
+
+use serde_json::json;
+
+// Bumped whenever a change to the messages breaks existing clients
+pub const PROTOCOL_VERSION: u32 = 2;
+
+pub enum TypeRef {
+    Number,
+    Boolean,
+    String,
+    // Any JSON value
+    Any,
+    Named(&'static str),
+    Array(Box<TypeRef>),
+    Optional(Box<TypeRef>),
+}
+
+pub struct Field {
+    pub name: &'static str,
+    pub type_ref: TypeRef,
+    // `#[serde(default)]`, clients can leave it out instead of sending null
+    pub defaulted: bool,
+}
+
+// Externally tagged like serde's default: unit variants are strings, the others `{ "Variant": { ...fields } }`
+pub struct Variant {
+    pub name: &'static str,
+    pub fields: Option<Vec<Field>>,
+}
+
+pub enum TypeDef {
+    Struct {
+        name: &'static str,
+        fields: Vec<Field>,
+    },
+    Enum {
+        name: &'static str,
+        variants: Vec<Variant>,
+    },
+}
+
+fn optional(type_ref: TypeRef) -> TypeRef {
+    TypeRef::Optional(Box::new(type_ref))
+}
+
+fn field(name: &'static str, type_ref: TypeRef) -> Field {
+    Field {
+        name,
+        type_ref,
+        defaulted: false,
+    }
+}
+
+fn defaulted(name: &'static str, type_ref: TypeRef) -> Field {
+    Field {
+        name,
+        type_ref: optional(type_ref),
+        defaulted: true,
+    }
+}
+
+fn variant(name: &'static str, fields: Vec<Field>) -> Variant {
+    Variant {
+        name,
+        fields: Some(fields),
+    }
+}
+
+fn array(type_ref: TypeRef) -> TypeRef {
+    TypeRef::Array(Box::new(type_ref))
+}
+
+pub fn types() -> Vec<TypeDef> {
+    vec![
+        TypeDef::Struct {
+            name: "Vector2D",
+            fields: vec![field("x", TypeRef::Number), field("y", TypeRef::Number)],
+        },
+        TypeDef::Struct {
+            name: "PlayerPublic",
+            fields: vec![
+                field("id", TypeRef::Number),
+                field("position", TypeRef::Named("Vector2D")),
+                field("radius", TypeRef::Number),
+                field("name", TypeRef::String),
+                field("squad", optional(TypeRef::Number)),
+                field("skin", optional(TypeRef::String)),
+            ],
+        },
+        TypeDef::Struct {
+            name: "PlayerPrivate",
+            fields: vec![
+                field("id", TypeRef::Number),
+                field("account", optional(TypeRef::String)),
+                field("best_mass", TypeRef::Number),
+                field("eats", TypeRef::Number),
+                field("alive_seconds", TypeRef::Number),
+                field("rename_cooldown_seconds", TypeRef::Number),
+                field("dash_cooldown_seconds", TypeRef::Number),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Rect",
+            fields: vec![
+                field("x", TypeRef::Number),
+                field("y", TypeRef::Number),
+                field("width", TypeRef::Number),
+                field("height", TypeRef::Number),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Circle",
+            fields: vec![
+                field("position", TypeRef::Named("Vector2D")),
+                field("radius", TypeRef::Number),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Portal",
+            fields: vec![
+                field("entrance", TypeRef::Named("Circle")),
+                field("exit", TypeRef::Named("Vector2D")),
+            ],
+        },
+        TypeDef::Enum {
+            name: "TerrainKind",
+            variants: vec![
+                Variant {
+                    name: "Mud",
+                    fields: None,
+                },
+                Variant {
+                    name: "Boost",
+                    fields: None,
+                },
+                Variant {
+                    name: "Danger",
+                    fields: None,
+                },
+            ],
+        },
+        TypeDef::Struct {
+            name: "TerrainZone",
+            fields: vec![
+                field("kind", TypeRef::Named("TerrainKind")),
+                field("area", TypeRef::Named("Rect")),
+                field("strength", TypeRef::Number),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Boss",
+            fields: vec![
+                field("position", TypeRef::Named("Vector2D")),
+                field("radius", TypeRef::Number),
+                field("velocity", TypeRef::Named("Vector2D")),
+            ],
+        },
+        TypeDef::Struct {
+            name: "BossReward",
+            fields: vec![field("id", TypeRef::Number), field("mass", TypeRef::Number)],
+        },
+        TypeDef::Struct {
+            name: "SafeZone",
+            fields: vec![
+                field("area", TypeRef::Named("Rect")),
+                field("max_mass", TypeRef::Number),
+                field("speed", TypeRef::Number),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Map",
+            fields: vec![
+                field("name", TypeRef::String),
+                field("width", TypeRef::Number),
+                field("height", TypeRef::Number),
+                field("obstacles", array(TypeRef::Named("Circle"))),
+                field("spawn_zones", array(TypeRef::Named("Rect"))),
+                field("food_regions", array(TypeRef::Named("Rect"))),
+                field("virus_regions", array(TypeRef::Named("Rect"))),
+                field("portals", array(TypeRef::Named("Portal"))),
+                field("terrain", array(TypeRef::Named("TerrainZone"))),
+                field("safe_zones", array(TypeRef::Named("SafeZone"))),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Quantization",
+            fields: vec![
+                field("x_step", TypeRef::Number),
+                field("y_step", TypeRef::Number),
+                field("radius_step", TypeRef::Number),
+            ],
+        },
+        TypeDef::Struct {
+            name: "SquadScore",
+            fields: vec![
+                field("squad", TypeRef::Number),
+                field("mass", TypeRef::Number),
+                field("players", array(TypeRef::Number)),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Food",
+            fields: vec![
+                field("id", TypeRef::Number),
+                field("position", TypeRef::Named("Vector2D")),
+                field("radius", TypeRef::Number),
+                field("velocity", optional(TypeRef::Named("Vector2D"))),
+            ],
+        },
+        TypeDef::Enum {
+            name: "BanTarget",
+            variants: vec![
+                variant("Ip", vec![field("ip", TypeRef::String)]),
+                variant("Account", vec![field("username", TypeRef::String)]),
+            ],
+        },
+        TypeDef::Struct {
+            name: "Ban",
+            fields: vec![
+                field("target", TypeRef::Named("BanTarget")),
+                field("reason", TypeRef::String),
+                field("banned_at", TypeRef::Number),
+                field("expires_at", optional(TypeRef::Number)),
+            ],
+        },
+        TypeDef::Struct {
+            name: "AuditEntry",
+            fields: vec![
+                field("timestamp", TypeRef::Number),
+                field("room", TypeRef::Number),
+                field("actor", TypeRef::Number),
+                field("ip", optional(TypeRef::String)),
+                field("command", TypeRef::Named("AdminCommand")),
+            ],
+        },
+        TypeDef::Enum {
+            name: "PlayerCommand",
+            variants: vec![
+                variant(
+                    "Move",
+                    vec![
+                        field("position", TypeRef::Named("Vector2D")),
+                        defaulted("client_time", TypeRef::Number),
+                    ],
+                ),
+                variant(
+                    "Join",
+                    vec![
+                        field("name", TypeRef::String),
+                        defaulted("token", TypeRef::String),
+                        defaulted("skin", TypeRef::String),
+                        defaulted("update_rate", TypeRef::Number),
+                    ],
+                ),
+                variant("Rename", vec![field("name", TypeRef::String)]),
+                variant("SetMass", vec![field("mass", TypeRef::Number)]),
+                variant(
+                    "Teleport",
+                    vec![field("position", TypeRef::Named("Vector2D"))],
+                ),
+                variant(
+                    "Steer",
+                    vec![
+                        field("direction", TypeRef::Named("Vector2D")),
+                        field("throttle", TypeRef::Number),
+                    ],
+                ),
+                Variant {
+                    name: "Dash",
+                    fields: None,
+                },
+                variant("Login", vec![field("token", TypeRef::String)]),
+                variant(
+                    "Admin",
+                    vec![field("command", TypeRef::Named("AdminCommand"))],
+                ),
+                variant("Spectate", vec![field("target_id", TypeRef::Number)]),
+                variant("AckKeyframe", vec![field("tick", TypeRef::Number)]),
+                Variant {
+                    name: "RequestKeyframe",
+                    fields: None,
+                },
+                Variant {
+                    name: "RequestSnapshot",
+                    fields: None,
+                },
+                variant(
+                    "TimeSyncRequest",
+                    vec![field("client_time", TypeRef::Number)],
+                ),
+                variant(
+                    "Subscribe",
+                    vec![field("categories", array(TypeRef::Named("Category")))],
+                ),
+                variant(
+                    "Unsubscribe",
+                    vec![field("categories", array(TypeRef::Named("Category")))],
+                ),
+            ],
+        },
+        TypeDef::Enum {
+            name: "ErrorCode",
+            variants: [
+                "InvalidCommand",
+                "InvalidName",
+                "InvalidPosition",
+                "InvalidValue",
+                "NotJoined",
+                "AlreadyJoined",
+                "Unauthorized",
+                "Forbidden",
+                "NotFound",
+                "RateLimited",
+                "RoomFull",
+                "Maintenance",
+                "Banned",
+                "Kicked",
+                "RoundUnderway",
+            ]
+            .into_iter()
+            .map(|name| Variant { name, fields: None })
+            .collect(),
+        },
+        TypeDef::Enum {
+            name: "Category",
+            variants: ["State", "Leaderboard", "Chat", "Events"]
+                .into_iter()
+                .map(|name| Variant { name, fields: None })
+                .collect(),
+        },
+        TypeDef::Enum {
+            name: "Severity",
+            variants: vec![
+                Variant {
+                    name: "Info",
+                    fields: None,
+                },
+                Variant {
+                    name: "Warning",
+                    fields: None,
+                },
+                Variant {
+                    name: "Critical",
+                    fields: None,
+                },
+            ],
+        },
+        TypeDef::Enum {
+            name: "Phase",
+            variants: vec![
+                Variant {
+                    name: "Day",
+                    fields: None,
+                },
+                Variant {
+                    name: "Night",
+                    fields: None,
+                },
+            ],
+        },
+        TypeDef::Enum {
+            name: "AdminCommand",
+            variants: vec![
+                variant("Kick", vec![field("id", TypeRef::Number)]),
+                variant(
+                    "Announce",
+                    vec![
+                        field("text", TypeRef::String),
+                        field("severity", TypeRef::Named("Severity")),
+                    ],
+                ),
+                variant("StartMaintenance", vec![field("seconds", TypeRef::Number)]),
+                Variant {
+                    name: "EndMaintenance",
+                    fields: None,
+                },
+                Variant {
+                    name: "Pause",
+                    fields: None,
+                },
+                Variant {
+                    name: "Resume",
+                    fields: None,
+                },
+                variant(
+                    "GrantSkin",
+                    vec![
+                        field("username", TypeRef::String),
+                        field("skin", TypeRef::String),
+                    ],
+                ),
+                variant(
+                    "RevokeSkin",
+                    vec![
+                        field("username", TypeRef::String),
+                        field("skin", TypeRef::String),
+                    ],
+                ),
+                variant(
+                    "Ban",
+                    vec![
+                        field("target", TypeRef::Named("BanTarget")),
+                        field("minutes", optional(TypeRef::Number)),
+                        field("reason", TypeRef::String),
+                    ],
+                ),
+                variant("Unban", vec![field("target", TypeRef::Named("BanTarget"))]),
+                Variant {
+                    name: "ListBans",
+                    fields: None,
+                },
+                variant("AuditLog", vec![field("limit", TypeRef::Number)]),
+                variant(
+                    "SetConfig",
+                    vec![field("key", TypeRef::String), field("value", TypeRef::Any)],
+                ),
+            ],
+        },
+        TypeDef::Enum {
+            name: "MessageToClient",
+            variants: vec![
+                variant("JoinSuccess", vec![field("id", TypeRef::Number)]),
+                Variant {
+                    name: "LoginSuccess",
+                    fields: None,
+                },
+                variant(
+                    "Welcome",
+                    vec![
+                        field("protocol_version", TypeRef::Number),
+                        field("tick_milliseconds", TypeRef::Number),
+                        field("width", TypeRef::Number),
+                        field("height", TypeRef::Number),
+                        field("map", TypeRef::Named("Map")),
+                        field("quantization", TypeRef::Named("Quantization")),
+                        field("eat_ratio", TypeRef::Number),
+                        field("bounds", TypeRef::Named("Rect")),
+                        field("player", optional(TypeRef::Named("PlayerPublic"))),
+                    ],
+                ),
+                variant(
+                    "PlayerEaten",
+                    vec![field("id", TypeRef::Number), field("by", TypeRef::Number)],
+                ),
+                variant(
+                    "BoundsChanged",
+                    vec![field("bounds", TypeRef::Named("Rect"))],
+                ),
+                variant(
+                    "BountyPlaced",
+                    vec![field("id", TypeRef::Number), field("mass", TypeRef::Number)],
+                ),
+                variant(
+                    "BountyClaimed",
+                    vec![
+                        field("id", TypeRef::Number),
+                        field("by", optional(TypeRef::Number)),
+                    ],
+                ),
+                variant("BossSpawned", vec![field("boss", TypeRef::Named("Boss"))]),
+                variant("BossMoved", vec![field("boss", TypeRef::Named("Boss"))]),
+                variant(
+                    "BossDefeated",
+                    vec![field("rewards", array(TypeRef::Named("BossReward")))],
+                ),
+                variant(
+                    "KillStreak",
+                    vec![
+                        field("id", TypeRef::Number),
+                        field("streak", TypeRef::Number),
+                    ],
+                ),
+                variant("PlayerLeft", vec![field("id", TypeRef::Number)]),
+                variant(
+                    "SquadStandings",
+                    vec![field("squads", array(TypeRef::Named("SquadScore")))],
+                ),
+                variant("SquadEliminated", vec![field("squad", TypeRef::Number)]),
+                variant(
+                    "Announcement",
+                    vec![
+                        field("text", TypeRef::String),
+                        field("severity", TypeRef::Named("Severity")),
+                    ],
+                ),
+                variant(
+                    "Spectating",
+                    vec![field("target", optional(TypeRef::Number))],
+                ),
+                variant(
+                    "PhaseChanged",
+                    vec![
+                        field("phase", TypeRef::Named("Phase")),
+                        field("vision_radius", TypeRef::Number),
+                    ],
+                ),
+                variant(
+                    "RoundOver",
+                    vec![
+                        field("map", TypeRef::Named("Map")),
+                        field("quantization", TypeRef::Named("Quantization")),
+                    ],
+                ),
+                variant("RoundStarted", vec![field("players", TypeRef::Number)]),
+                variant("RoundWon", vec![field("id", optional(TypeRef::Number))]),
+                variant("GamePaused", vec![field("paused", TypeRef::Boolean)]),
+                variant(
+                    "Error",
+                    vec![
+                        field("code", TypeRef::Named("ErrorCode")),
+                        field("message", TypeRef::String),
+                        field("command_ref", optional(TypeRef::String)),
+                    ],
+                ),
+                variant("BanList", vec![field("bans", array(TypeRef::Named("Ban")))]),
+                variant(
+                    "AuditLog",
+                    vec![field("entries", array(TypeRef::Named("AuditEntry")))],
+                ),
+                variant(
+                    "TimeSyncResponse",
+                    vec![
+                        field("client_time", TypeRef::Number),
+                        field("server_receive_time", TypeRef::Number),
+                        field("server_send_time", TypeRef::Number),
+                        field("tick", TypeRef::Number),
+                        field("tick_time", TypeRef::Number),
+                    ],
+                ),
+                variant(
+                    "PlayerRenamed",
+                    vec![field("id", TypeRef::Number), field("name", TypeRef::String)],
+                ),
+                variant(
+                    "State",
+                    vec![
+                        field("sequence", TypeRef::Number),
+                        field("tick", TypeRef::Number),
+                        field("checksum", TypeRef::Number),
+                        field("players", array(TypeRef::Named("PlayerPublic"))),
+                        field("food", array(TypeRef::Named("Food"))),
+                    ],
+                ),
+                variant(
+                    "StateDelta",
+                    vec![
+                        field("sequence", TypeRef::Number),
+                        field("tick", TypeRef::Number),
+                        field("checksum", optional(TypeRef::Number)),
+                        field("players", array(TypeRef::Named("PlayerPublic"))),
+                        field("removed_players", array(TypeRef::Number)),
+                    ],
+                ),
+                variant(
+                    "PrivateState",
+                    vec![
+                        field("tick", TypeRef::Number),
+                        field("player", TypeRef::Named("PlayerPrivate")),
+                    ],
+                ),
+                variant(
+                    "FoodSpawned",
+                    vec![field("food", array(TypeRef::Named("Food")))],
+                ),
+                variant(
+                    "FoodEaten",
+                    vec![field("id", TypeRef::Number), field("by", TypeRef::Number)],
+                ),
+                variant(
+                    "FoodMoved",
+                    vec![field("food", array(TypeRef::Named("Food")))],
+                ),
+            ],
+        },
+    ]
+}
+
+fn typescript_type(type_ref: &TypeRef) -> String {
+    match type_ref {
+        TypeRef::Number => String::from("number"),
+        TypeRef::Boolean => String::from("boolean"),
+        TypeRef::String => String::from("string"),
+        TypeRef::Any => String::from("unknown"),
+        TypeRef::Named(name) => String::from(*name),
+        TypeRef::Array(item) => format!("{}[]", typescript_type(item)),
+        TypeRef::Optional(item) => format!("{} | null", typescript_type(item)),
+    }
+}
+
+fn typescript_fields(fields: &[Field]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match &field.type_ref {
+            TypeRef::Optional(item) if field.defaulted => {
+                format!("{}?: {}", field.name, typescript_type(item))
+            }
+            type_ref => format!("{}: {}", field.name, typescript_type(type_ref)),
+        })
+        .collect();
+    format!("{{ {} }}", fields.join("; "))
+}
+
+fn schema_type(type_ref: &TypeRef) -> serde_json::Value {
+    match type_ref {
+        TypeRef::Number => json!({ "type": "number" }),
+        TypeRef::Boolean => json!({ "type": "boolean" }),
+        TypeRef::String => json!({ "type": "string" }),
+        TypeRef::Any => json!({}),
+        TypeRef::Named(name) => json!({ "$ref": format!("#/definitions/{}", name) }),
+        TypeRef::Array(item) => json!({ "type": "array", "items": schema_type(item) }),
+        TypeRef::Optional(item) => json!({ "oneOf": [schema_type(item), { "type": "null" }] }),
+    }
+}
+
+fn schema_fields(fields: &[Field]) -> serde_json::Value {
+    let mut properties = serde_json::Map::new();
+    for field in fields {
+        properties.insert(String::from(field.name), schema_type(&field.type_ref));
+    }
+
+    // Serde fills in the optional fields left out
+    let required: Vec<&str> = fields
+        .iter()
+        .filter(|field| !matches!(field.type_ref, TypeRef::Optional(_)))
+        .map(|field| field.name)
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+// Renders every protocol type as a JSON Schema (draft 7) document
+pub fn json_schema() -> serde_json::Value {
+    let mut definitions = serde_json::Map::new();
+
+    for type_def in types() {
+        match type_def {
+            TypeDef::Struct { name, fields } => {
+                definitions.insert(String::from(name), schema_fields(&fields));
+            }
+            TypeDef::Enum { name, variants } => {
+                let variants: Vec<serde_json::Value> = variants
+                    .iter()
+                    .map(|variant| match &variant.fields {
+                        Some(fields) => json!({
+                            "type": "object",
+                            "properties": { variant.name: schema_fields(fields) },
+                            "required": [variant.name],
+                            "additionalProperties": false,
+                        }),
+                        None => json!({ "const": variant.name }),
+                    })
+                    .collect();
+                definitions.insert(String::from(name), json!({ "oneOf": variants }));
+            }
+        }
+    }
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "definitions": definitions,
+    })
+}
+
+// Renders every protocol type as a TypeScript declaration file
+pub fn typescript() -> String {
+    let mut output =
+        String::from("// Generated by `block_explorer --generate-types`, do not edit\n");
+
+    for type_def in types() {
+        output.push('\n');
+        match type_def {
+            TypeDef::Struct { name, fields } => {
+                output.push_str(&format!(
+                    "export interface {} {}\n",
+                    name,
+                    typescript_fields(&fields)
+                ));
+            }
+            TypeDef::Enum { name, variants } => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|variant| match &variant.fields {
+                        Some(fields) => {
+                            format!("{{ {}: {} }}", variant.name, typescript_fields(fields))
+                        }
+                        None => format!("\"{}\"", variant.name),
+                    })
+                    .collect();
+                output.push_str(&format!(
+                    "export type {} =\n  | {};\n",
+                    name,
+                    variants.join("\n  | ")
+                ));
+            }
+        }
+    }
+
+    output
+}
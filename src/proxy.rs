@@ -0,0 +1,126 @@
+use std::net::IpAddr;
+
+use axum::http::HeaderMap;
+
+// Proxies whose `Forwarded` and `X-Forwarded-For` headers are believed, as addresses or CIDR ranges
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    // Entries that don't parse are reported and skipped
+    pub fn parse(entries: &[String]) -> TrustedProxies {
+        let mut ranges = Vec::new();
+        for entry in entries {
+            match parse_range(entry) {
+                Some(range) => ranges.push(range),
+                None => println!("Ignoring invalid trusted proxy {}", entry),
+            }
+        }
+        TrustedProxies { ranges }
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(network, prefix)| in_range(ip, network, prefix))
+    }
+
+    // The address of the client behind the proxies. `peer` is None for connections over a Unix socket,
+    // which only a local proxy can open. The forwarded chain is read from the right so a client can't
+    // pose as someone else by sending the headers itself
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        if let Some(peer) = peer {
+            if !self.contains(peer) {
+                return Some(peer);
+            }
+        }
+
+        let chain = forwarded_chain(headers);
+        chain
+            .iter()
+            .rev()
+            .find(|ip| !self.contains(**ip))
+            .or(chain.first())
+            .copied()
+            .or(peer)
+    }
+}
+
+// Addresses from the `Forwarded` header, or `X-Forwarded-For` without it, the client first
+fn forwarded_chain(headers: &HeaderMap) -> Vec<IpAddr> {
+    let values = |name: &str| -> Vec<String> {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|value| String::from(value.trim()))
+            .collect()
+    };
+
+    let forwarded: Vec<IpAddr> = values("forwarded")
+        .iter()
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                if !key.eq_ignore_ascii_case("for") {
+                    return None;
+                }
+                parse_node(value.trim_matches('"'))
+            })
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    values("x-forwarded-for")
+        .iter()
+        .filter_map(|value| parse_node(value))
+        .collect()
+}
+
+// Accepts `1.2.3.4`, `1.2.3.4:80`, `2001:db8::1` and `[2001:db8::1]:80`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.rsplit_once(':')?.0.parse().ok()
+}
+
+fn parse_range(entry: &str) -> Option<(IpAddr, u8)> {
+    let (address, prefix) = match entry.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (entry, None),
+    };
+
+    let network: IpAddr = address.trim().parse().ok()?;
+    let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix.trim().parse::<u8>().ok()?,
+        None => max_prefix,
+    };
+    if prefix > max_prefix {
+        return None;
+    }
+    Some((network, prefix))
+}
+
+fn in_range(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+// Addresses tracked before the expired windows are swept, so a flood of spoofed sources can't grow the map forever
+const SWEEP_THRESHOLD: usize = 10_000;
+
+// Counts the websocket upgrade attempts of each address in one-minute windows
+#[derive(Debug, Default)]
+pub struct UpgradeLimiter {
+    per_minute: u32,
+    windows: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl UpgradeLimiter {
+    // A limit of 0 allows everything
+    pub fn new(per_minute: u32) -> UpgradeLimiter {
+        UpgradeLimiter {
+            per_minute,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Records an attempt, the error is how long the address has to wait when it is over its limit
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+
+        let mut windows = match self.windows.lock() {
+            Ok(windows) => windows,
+            Err(error) => {
+                println!("Error locking upgrade limiter: {}", error);
+                return Ok(());
+            }
+        };
+
+        if windows.len() >= SWEEP_THRESHOLD {
+            windows.retain(|_, (started_at, _)| now.duration_since(*started_at) < WINDOW);
+        }
+
+        let (started_at, attempts) = windows.entry(ip).or_insert((now, 0));
+        if now.duration_since(*started_at) >= WINDOW {
+            *started_at = now;
+            *attempts = 0;
+        }
+
+        *attempts += 1;
+        if *attempts > self.per_minute {
+            return Err(WINDOW - now.duration_since(*started_at));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use hyper::{Body, Client, Method, Request};
+use tokio::time::{self, Duration};
+
+use crate::room::{GameMode, RoomSummary};
+use crate::server::AppState;
+
+// What the master server is told about this server on every heartbeat
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Heartbeat {
+    pub address: String,
+    pub region: Option<String>,
+    pub player_count: u32,
+    pub max_players: u32,
+    pub modes: Vec<GameMode>,
+    pub rooms: Vec<RoomSummary>,
+}
+
+impl Heartbeat {
+    pub fn new(address: &str, region: Option<String>, state: &AppState) -> Heartbeat {
+        let mut rooms: Vec<RoomSummary> = state.rooms.values().map(|room| room.summary()).collect();
+        rooms.sort_by_key(|room| room.id);
+
+        let mut modes = Vec::new();
+        for room in &rooms {
+            if !modes.contains(&room.mode) {
+                modes.push(room.mode);
+            }
+        }
+
+        Heartbeat {
+            address: String::from(address),
+            region,
+            player_count: rooms.iter().map(|room| room.player_count).sum(),
+            max_players: state.rooms.values().map(|room| room.info.max_players).sum(),
+            modes,
+            rooms,
+        }
+    }
+}
+
+// Posts a heartbeat to the master server every `interval`. A failed heartbeat isn't retried, the next one
+// follows soon enough. Like the webhooks only plain `http://` urls are supported
+pub fn start(
+    master_server_url: String,
+    address: String,
+    region: Option<String>,
+    interval: Duration,
+    state: Arc<AppState>,
+) {
+    tokio::spawn(async move {
+        let client = Client::new();
+        let mut interval = time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let heartbeat = Heartbeat::new(&address, region.clone(), &state);
+            let body = match serde_json::to_string(&heartbeat) {
+                Ok(body) => body,
+                Err(error) => {
+                    println!("Error serializing heartbeat: {}", error);
+                    continue;
+                }
+            };
+
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(&master_server_url)
+                .header("content-type", "application/json")
+                .body(Body::from(body));
+            let request = match request {
+                Ok(request) => request,
+                Err(error) => {
+                    println!("Invalid master server url {}: {}", master_server_url, error);
+                    return;
+                }
+            };
+
+            match client.request(request).await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => println!(
+                    "Master server {} answered {}",
+                    master_server_url,
+                    response.status()
+                ),
+                Err(error) => println!(
+                    "Error sending heartbeat to {}: {}",
+                    master_server_url, error
+                ),
+            }
+        }
+    });
+}
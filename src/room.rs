@@ -0,0 +1,192 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, mpsc};
+
+use crate::accounts::Accounts;
+use crate::config::GameConfig;
+use crate::game_manager::{Command, Frame, GameManager};
+use crate::leaderboard::Leaderboard;
+use crate::map::Map;
+use crate::metrics::{ConnectionMetrics, TickMetrics};
+use crate::rules::{DefaultRules, GameRules, LastManRules, SquadRules};
+
+// Rating difference under which a player is matched with a ranked room's players
+pub const RANKED_RATING_BAND: u32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GameMode {
+    FreeForAll,
+    // Players are grouped in squads that can't eat each other
+    Squads,
+    // Practice room where the cheat commands are allowed
+    Sandbox,
+    // Only registered players, results change their ratings
+    Ranked,
+    // No respawns and no food growing back, the round ends when a single player is left
+    LastManStanding,
+}
+
+impl GameMode {
+    pub fn rules(&self) -> Box<dyn GameRules> {
+        match self {
+            GameMode::FreeForAll => Box::new(DefaultRules),
+            GameMode::Squads => Box::new(SquadRules),
+            GameMode::Sandbox => Box::new(DefaultRules),
+            GameMode::Ranked => Box::new(DefaultRules),
+            GameMode::LastManStanding => Box::new(LastManRules),
+        }
+    }
+}
+
+// Static description of a room plus the live player count, shared between the game manager and the HTTP routes
+pub struct RoomInfo {
+    pub id: u32,
+    pub mode: GameMode,
+    // Maps played in turn, one round each, never empty
+    pub maps: Vec<Arc<Map>>,
+    // Index in `maps` of the current map, only the game manager changes it
+    pub map_index: AtomicUsize,
+    pub max_players: u32,
+    pub player_count: AtomicU32,
+    // Set by an admin before a restart, the room refuses joins while it is set
+    pub maintenance: AtomicBool,
+    // Set when the game manager died from a panic, the room stays down until a restart
+    pub crashed: AtomicBool,
+    // Mirrors `GameManager::round_underway`, late players can't join a round without respawns
+    pub round_underway: AtomicBool,
+    // Average rating of the players in a ranked room, used to match players of a similar level
+    pub average_rating: AtomicU32,
+    // File the world is saved to on shutdown and restored from on startup
+    pub snapshot_path: Option<String>,
+    // File the admin commands are appended to, shared by every room. Kept in memory without one
+    pub audit_log_path: Option<String>,
+    // Ticks between two broadcast states, raised by the game manager while the ticks run late
+    pub broadcast_stride: AtomicU32,
+    // Published by the game manager every tick for the `/stats` route
+    pub biggest_player: std::sync::Mutex<Option<BiggestPlayer>>,
+}
+
+impl RoomInfo {
+    // A room with nobody in it yet, not saved anywhere
+    pub fn new(id: u32, mode: GameMode, maps: Vec<Arc<Map>>, max_players: u32) -> RoomInfo {
+        RoomInfo {
+            id,
+            mode,
+            maps,
+            map_index: AtomicUsize::new(0),
+            max_players,
+            player_count: AtomicU32::new(0),
+            maintenance: AtomicBool::new(false),
+            crashed: AtomicBool::new(false),
+            round_underway: AtomicBool::new(false),
+            average_rating: AtomicU32::new(0),
+            snapshot_path: None,
+            audit_log_path: None,
+            broadcast_stride: AtomicU32::new(1),
+            biggest_player: std::sync::Mutex::new(None),
+        }
+    }
+
+    pub fn map(&self) -> Arc<Map> {
+        let index = self.map_index.load(Ordering::Relaxed) % self.maps.len();
+        self.maps[index].clone()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.player_count.load(Ordering::Relaxed) >= self.max_players
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RoomSummary {
+    pub id: u32,
+    pub player_count: u32,
+    pub mode: GameMode,
+    pub map: String,
+    pub width: f32,
+    pub height: f32,
+    pub round_underway: bool,
+    pub joinable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BiggestPlayer {
+    pub name: String,
+    pub mass: f32,
+    pub room: u32,
+}
+
+// Handles used by the websocket connections to talk to a running game manager
+pub struct Room {
+    pub info: Arc<RoomInfo>,
+    pub command_tx: mpsc::Sender<Command>,
+    pub broadcast_channel: broadcast::Sender<Frame>,
+}
+
+impl Room {
+    pub fn start(
+        info: RoomInfo,
+        config: GameConfig,
+        tick_metrics: Arc<TickMetrics>,
+        connection_metrics: Arc<ConnectionMetrics>,
+        leaderboard: Arc<std::sync::Mutex<Leaderboard>>,
+        accounts: Arc<std::sync::Mutex<Accounts>>,
+    ) -> Room {
+        let info = Arc::new(info);
+
+        // This channel is used to send messages to all the players in the room
+        let (broadcast_channel, _) = broadcast::channel::<Frame>(100);
+        let game_manager = GameManager::new(
+            info.clone(),
+            config,
+            broadcast_channel.clone(),
+            tick_metrics,
+            connection_metrics,
+            leaderboard,
+            accounts,
+        );
+
+        let room = Room {
+            info,
+            command_tx: game_manager.command_tx.clone(),
+            broadcast_channel,
+        };
+
+        game_manager.start();
+        room
+    }
+
+    // The joinable ranked room whose players are the closest to `rating`. An empty room is
+    // only picked when no room is within `RANKED_RATING_BAND` of the player
+    pub fn find_ranked<'a>(rooms: impl Iterator<Item = &'a Room>, rating: f32) -> Option<&'a Room> {
+        rooms
+            .filter(|room| room.info.mode == GameMode::Ranked && room.summary().joinable)
+            .min_by_key(|room| {
+                if room.info.player_count.load(Ordering::Relaxed) == 0 {
+                    return RANKED_RATING_BAND;
+                }
+                let average_rating = room.info.average_rating.load(Ordering::Relaxed);
+                (average_rating as f32 - rating).abs() as u32
+            })
+    }
+
+    pub fn summary(&self) -> RoomSummary {
+        let map = self.info.map();
+        let round_underway = self.info.round_underway.load(Ordering::Relaxed);
+
+        RoomSummary {
+            id: self.info.id,
+            player_count: self.info.player_count.load(Ordering::Relaxed),
+            mode: self.info.mode,
+            map: map.name.clone(),
+            width: map.width,
+            height: map.height,
+            round_underway,
+            joinable: !self.info.is_full()
+                && !self.info.maintenance.load(Ordering::Relaxed)
+                && !self.info.crashed.load(Ordering::Relaxed)
+                && !round_underway,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::player::Player;
+
+fn mass(radius: f32) -> f32 {
+    2.0 * radius.powf(2.0) * std::f32::consts::PI
+}
+
+// Decisions the game manager delegates to the ruleset of the room, every method defaults to the
+// free for all rules so a ruleset only overrides what it changes
+pub trait GameRules: Send + Sync {
+    // Whether `eater` can eat `other` once they overlap, at most one of the two directions should be true
+    fn can_eat(&self, eater: &Player, other: &Player) -> bool {
+        // Between players of the same size the one that joined first wins
+        let other_radius = other.radius * self.eat_ratio();
+        eater.radius > other_radius || (eater.radius == other_radius && eater.id < other.id)
+    }
+
+    // Radius ratio a player needs over another to eat it, told to the clients so they can show who is edible
+    fn eat_ratio(&self) -> f32 {
+        1.0
+    }
+
+    // Radius of a player after eating something with `eaten_radius`
+    fn radius_after_eat(&self, radius: f32, eaten_radius: f32) -> f32 {
+        let combined_mass = mass(radius) + mass(eaten_radius);
+        (combined_mass / (2.0 * std::f32::consts::PI)).sqrt()
+    }
+
+    // Distance the player moves each tick
+    fn speed(&self, player: &Player, speed_factor: f32) -> f32 {
+        speed_factor / player.mass().sqrt()
+    }
+
+    // Called when a player is eaten, returns whether the player should be removed from the room
+    fn on_death(&self, _player: &mut Player) -> bool {
+        true
+    }
+
+    // Whether eaten food grows back once the round is under way
+    fn regrows_food(&self) -> bool {
+        true
+    }
+
+    // Whether players can join and rejoin at any time. Without respawns a round waits for its players,
+    // then nobody else gets in until one of them is left
+    fn allows_respawns(&self) -> bool {
+        true
+    }
+}
+
+pub struct DefaultRules;
+
+impl GameRules for DefaultRules {}
+
+// Default rules, except that members of the same squad can't eat each other
+pub struct SquadRules;
+
+impl GameRules for SquadRules {
+    fn can_eat(&self, eater: &Player, other: &Player) -> bool {
+        let teammates = eater.squad.is_some() && eater.squad == other.squad;
+        !teammates && DefaultRules.can_eat(eater, other)
+    }
+}
+
+// Default rules, without respawns or food growing back, the last player left wins the round
+pub struct LastManRules;
+
+impl GameRules for LastManRules {
+    fn regrows_food(&self) -> bool {
+        false
+    }
+
+    fn allows_respawns(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+
+// Cron-like "minute hour weekday" in UTC. Each field is `*` or a comma separated list of numbers, weekdays
+// count from 0 for Sunday, so "0 18 6" is every Saturday at 18:00 and "30 * *" every hour at half past
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    minutes: Option<Vec<u64>>,
+    hours: Option<Vec<u64>>,
+    weekdays: Option<Vec<u64>>,
+}
+
+impl Schedule {
+    pub fn parse(text: &str) -> Result<Schedule, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() != 3 {
+            return Err(format!(
+                "invalid schedule {:?}, expected \"minute hour weekday\"",
+                text
+            ));
+        }
+
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 59)?,
+            hours: parse_field(fields[1], 23)?,
+            weekdays: parse_field(fields[2], 6)?,
+        })
+    }
+
+    // Whether the schedule fires at the start of this minute since the unix epoch
+    pub fn fires_at(&self, unix_minute: u64) -> bool {
+        let minute = unix_minute % 60;
+        let hour = unix_minute / 60 % 24;
+        // The epoch was a Thursday
+        let weekday = (unix_minute / (60 * 24) + 4) % 7;
+
+        matches(&self.minutes, minute)
+            && matches(&self.hours, hour)
+            && matches(&self.weekdays, weekday)
+    }
+}
+
+fn parse_field(field: &str, max: u64) -> Result<Option<Vec<u64>>, String> {
+    if field == "*" {
+        return Ok(None);
+    }
+
+    field
+        .split(',')
+        .map(|value| match value.parse::<u64>() {
+            Ok(value) if value <= max => Ok(value),
+            _ => Err(format!(
+                "invalid schedule field {:?}, expected * or numbers from 0 to {}",
+                field, max
+            )),
+        })
+        .collect::<Result<Vec<u64>, String>>()
+        .map(Some)
+}
+
+fn matches(field: &Option<Vec<u64>>, value: u64) -> bool {
+    field.as_ref().is_none_or(|values| values.contains(&value))
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Schedule, String> {
+        Schedule::parse(&text)
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> String {
+        schedule.to_string()
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = |values: &Option<Vec<u64>>| match values {
+            Some(values) => values
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<String>>()
+                .join(","),
+            None => String::from("*"),
+        };
+        write!(
+            f,
+            "{} {} {}",
+            field(&self.minutes),
+            field(&self.hours),
+            field(&self.weekdays)
+        )
+    }
+}
+
+// A window that repeats on `start` for `duration_minutes`, during which the mass gained from eating is
+// multiplied and decay can be turned off. "Double mass weekends" start on "0 0 6" for 2880 minutes
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledEvent {
+    pub name: String,
+    pub start: Schedule,
+    pub duration_minutes: u64,
+    #[serde(default = "default_multiplier")]
+    pub mass_multiplier: f32,
+    #[serde(default)]
+    pub disable_decay: bool,
+}
+
+// Windows longer than this are cut to it, they would never end anyway with a weekly schedule
+const MAX_DURATION_MINUTES: u64 = 7 * 24 * 60;
+
+impl ScheduledEvent {
+    // Whether one of the windows covers this second since the unix epoch
+    pub fn is_active(&self, unix_seconds: u64) -> bool {
+        let now = unix_seconds / 60;
+        let duration = self.duration_minutes.min(MAX_DURATION_MINUTES);
+        (0..duration).any(|ago| now >= ago && self.start.fires_at(now - ago))
+    }
+}
+
+fn default_multiplier() -> f32 {
+    1.0
+}
@@ -0,0 +1,753 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Path, Query, State,
+    },
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::{sink::SinkExt, stream::StreamExt};
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, mpsc};
+use tower_http::cors::CorsLayer;
+
+use crate::accounts::{AccountError, Accounts};
+use crate::bans::{self, Ban};
+use crate::binary::Encoding;
+use crate::game_manager::{
+    Command, ErrorCode, Frame, FrameFilter, InternalCommand, MessageToClient, PlayerCommand,
+    PlayerMessage, PLAYER_QUEUE_LENGTH,
+};
+use crate::leaderboard::{Leaderboard, Period};
+use crate::metrics::{self, ConnectionMetrics, TickMetrics};
+use crate::proxy::TrustedProxies;
+use crate::rate_limit::UpgradeLimiter;
+use crate::room::{BiggestPlayer, Room, RoomSummary};
+use crate::{openapi, protocol, storage};
+
+pub struct AppState {
+    pub rooms: HashMap<u32, Room>,
+    pub id_tracker: Arc<AtomicU32>,
+    pub tick_metrics: Arc<TickMetrics>,
+    pub connection_metrics: Arc<ConnectionMetrics>,
+    pub leaderboard: Arc<std::sync::Mutex<Leaderboard>>,
+    pub accounts: Arc<std::sync::Mutex<Accounts>>,
+    pub trusted_proxies: TrustedProxies,
+    // Unlimited unless set from the config
+    pub upgrade_limiter: UpgradeLimiter,
+    pub started_at: Instant,
+    // Last answer of `/stats` and when it was computed
+    stats_cache: std::sync::Mutex<Option<(Instant, ServerStats)>>,
+}
+
+impl AppState {
+    pub fn new(
+        rooms: Vec<Room>,
+        tick_metrics: Arc<TickMetrics>,
+        connection_metrics: Arc<ConnectionMetrics>,
+        leaderboard: Arc<std::sync::Mutex<Leaderboard>>,
+        accounts: Arc<std::sync::Mutex<Accounts>>,
+    ) -> AppState {
+        AppState {
+            rooms: rooms.into_iter().map(|room| (room.info.id, room)).collect(),
+            id_tracker: Arc::new(AtomicU32::new(0)),
+            tick_metrics,
+            connection_metrics,
+            leaderboard,
+            accounts,
+            trusted_proxies: TrustedProxies::default(),
+            upgrade_limiter: UpgradeLimiter::new(0),
+            started_at: Instant::now(),
+            stats_cache: std::sync::Mutex::new(None),
+        }
+    }
+}
+
+// How long `/stats` answers with the same numbers, so landing page visits don't add up
+pub const STATS_CACHE_SECONDS: u64 = 5;
+
+// Live numbers for the website, without opening a websocket
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ServerStats {
+    pub players_online: u32,
+    pub rooms: u32,
+    pub biggest_player: Option<BiggestPlayer>,
+    pub uptime_seconds: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct GameQuery {
+    room: Option<u32>,
+    #[serde(default)]
+    encoding: Encoding,
+}
+
+#[derive(serde::Deserialize)]
+struct DebugQuery {
+    room: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+struct LeaderboardQuery {
+    period: Option<Period>,
+}
+
+#[derive(serde::Deserialize)]
+struct RegisterRequest {
+    username: String,
+}
+
+#[derive(serde::Serialize)]
+struct RegisterResponse {
+    username: String,
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+struct CreateClanRequest {
+    token: String,
+    tag: String,
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenRequest {
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+struct RankedQuery {
+    token: String,
+}
+
+#[derive(serde::Serialize)]
+struct RankedMatch {
+    room: u32,
+    rating: f32,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route(
+            "/game",
+            get(websocket_handler).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                limit_upgrades,
+            )),
+        )
+        .route("/metrics", get(metrics_handler))
+        .route("/leaderboard", get(leaderboard_handler))
+        .route("/rooms", get(rooms_handler))
+        .route("/stats", get(stats_handler))
+        .route("/matchmaking/ranked", get(ranked_handler))
+        .route("/accounts", post(register_handler))
+        .route("/players/:username", get(profile_handler))
+        .route("/clans", get(clans_handler).post(create_clan_handler))
+        .route("/clans/leave", post(leave_clan_handler))
+        .route("/clans/:tag/join", post(join_clan_handler))
+        .route("/schema", get(schema_handler))
+        .route("/docs", get(docs_handler))
+        .route("/debug/state", get(debug_state_handler))
+        .with_state(state)
+        .layer(CorsLayer::very_permissive())
+}
+
+// Waits for ctrl-c or SIGTERM, then lets every room save its world before the server stops
+pub async fn shutdown_signal(state: Arc<AppState>) {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            println!("Error listening for ctrl-c: {}", error);
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(error) => println!("Error listening for SIGTERM: {}", error),
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    println!("Shutting down");
+
+    for room in state.rooms.values() {
+        if let Err(error) = room
+            .command_tx
+            .send(Command::InternalCommand(InternalCommand::Shutdown))
+            .await
+        {
+            println!("Error sending shutdown command: {}", error);
+            continue;
+        }
+
+        // The room drops its receiver once the snapshot is written
+        room.command_tx.closed().await;
+    }
+
+    // What changed since the last flush
+    storage::flush(&state.leaderboard);
+    storage::flush(&state.accounts);
+}
+
+// There is no peer address for connections over a Unix socket
+async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<GameQuery>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Response {
+    let room_id = query.room.unwrap_or(0);
+
+    if !state.rooms.contains_key(&room_id) {
+        return (StatusCode::NOT_FOUND, "Room not found").into_response();
+    }
+
+    if state.rooms[&room_id].info.crashed.load(Ordering::Relaxed) {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Room is down").into_response();
+    }
+
+    // The first offered subprotocol we speak is picked, clients offering only unknown ones are turned away
+    let mut encoding = query.encoding;
+    let mut ws = ws;
+    if let Some(offered) = headers.get(header::SEC_WEBSOCKET_PROTOCOL) {
+        let offered = offered.to_str().unwrap_or_default();
+        match offered
+            .split(',')
+            .find_map(|name| Encoding::from_subprotocol(name.trim()))
+        {
+            Some(negotiated) => {
+                encoding = negotiated;
+                ws = ws.protocols([negotiated.subprotocol()]);
+            }
+            None => {
+                let message = format!(
+                    "Unsupported websocket subprotocol {}, expected one of {}",
+                    offered,
+                    Encoding::SUBPROTOCOLS.join(", ")
+                );
+                return (StatusCode::BAD_REQUEST, message).into_response();
+            }
+        }
+    }
+
+    let peer = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    let ip = state.trusted_proxies.client_ip(peer, &headers);
+
+    if let Some(ip) = ip {
+        let ban = match state.accounts.lock() {
+            Ok(accounts) => accounts.bans.find_ip(ip, bans::now()).map(Ban::message),
+            Err(error) => {
+                println!("Error locking accounts: {}", error);
+                None
+            }
+        };
+        if let Some(message) = ban {
+            return (StatusCode::FORBIDDEN, message).into_response();
+        }
+    }
+
+    ws.on_upgrade(move |socket| websocket_connection(socket, state, room_id, ip, encoding))
+}
+
+// Turns away handshake floods before the upgrade is accepted
+async fn limit_upgrades<B>(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    if let Some(ip) = state.trusted_proxies.client_ip(peer, request.headers()) {
+        if let Err(wait) = state.upgrade_limiter.check(ip, Instant::now()) {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, wait.as_secs().max(1).to_string())],
+                "Too many connection attempts",
+            )
+                .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut output = state.tick_metrics.render();
+    output.push_str(&state.connection_metrics.render());
+    output.push_str(&metrics::render_rates(
+        state.rooms.values().map(|room| &*room.info),
+    ));
+    output
+}
+
+async fn leaderboard_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LeaderboardQuery>,
+) -> impl IntoResponse {
+    let period = query.period.unwrap_or(Period::All);
+
+    let records = match state.leaderboard.lock() {
+        Ok(leaderboard) => leaderboard.top(period),
+        Err(error) => {
+            println!("Error locking leaderboard: {}", error);
+            Vec::new()
+        }
+    };
+
+    Json(records)
+}
+
+async fn rooms_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut rooms: Vec<RoomSummary> = state.rooms.values().map(Room::summary).collect();
+    rooms.sort_by_key(|room| room.id);
+    Json(rooms)
+}
+
+async fn stats_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let cache_control = format!("public, max-age={}", STATS_CACHE_SECONDS);
+    let stats = match state.stats_cache.lock() {
+        Ok(mut cache) => match cache.as_ref() {
+            Some((computed_at, stats))
+                if computed_at.elapsed() < Duration::from_secs(STATS_CACHE_SECONDS) =>
+            {
+                stats.clone()
+            }
+            _ => {
+                let stats = compute_stats(&state);
+                *cache = Some((Instant::now(), stats.clone()));
+                stats
+            }
+        },
+        Err(error) => {
+            println!("Error locking stats cache: {}", error);
+            compute_stats(&state)
+        }
+    };
+
+    ([(header::CACHE_CONTROL, cache_control)], Json(stats))
+}
+
+fn compute_stats(state: &AppState) -> ServerStats {
+    let biggest_player = state
+        .rooms
+        .values()
+        .filter_map(|room| match room.info.biggest_player.lock() {
+            Ok(biggest) => biggest.clone(),
+            Err(error) => {
+                println!("Error locking biggest player: {}", error);
+                None
+            }
+        })
+        .max_by(|a, b| a.mass.total_cmp(&b.mass));
+
+    ServerStats {
+        players_online: state
+            .rooms
+            .values()
+            .map(|room| room.info.player_count.load(Ordering::Relaxed))
+            .sum(),
+        rooms: state.rooms.len() as u32,
+        biggest_player,
+        uptime_seconds: state.started_at.elapsed().as_secs(),
+    }
+}
+
+fn account_error_response(error: AccountError) -> Response {
+    let status = match error {
+        AccountError::InvalidToken => StatusCode::UNAUTHORIZED,
+        AccountError::UsernameTaken | AccountError::ClanTaken => StatusCode::CONFLICT,
+        AccountError::AccountNotFound | AccountError::ClanNotFound => StatusCode::NOT_FOUND,
+        AccountError::InvalidUsername | AccountError::InvalidClan => StatusCode::BAD_REQUEST,
+    };
+    (status, error.to_string()).into_response()
+}
+
+// Runs `change` on the accounts, saving them and answering with `respond` if it succeeded
+fn change_accounts<T>(
+    state: &AppState,
+    change: impl FnOnce(&mut Accounts) -> Result<T, AccountError>,
+    respond: impl FnOnce(T) -> Response,
+) -> Response {
+    let mut accounts = match state.accounts.lock() {
+        Ok(accounts) => accounts,
+        Err(error) => {
+            println!("Error locking accounts: {}", error);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    match change(&mut accounts) {
+        Ok(result) => {
+            accounts.save();
+            respond(result)
+        }
+        Err(error) => account_error_response(error),
+    }
+}
+
+async fn register_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<RegisterRequest>,
+) -> Response {
+    let username = request.username.clone();
+    change_accounts(
+        &state,
+        |accounts| accounts.register(&request.username),
+        |token| Json(RegisterResponse { username, token }).into_response(),
+    )
+}
+
+async fn profile_handler(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+) -> Response {
+    let profile = match state.accounts.lock() {
+        Ok(accounts) => accounts.profile(&username),
+        Err(error) => {
+            println!("Error locking accounts: {}", error);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    match profile {
+        Some(profile) => Json(profile).into_response(),
+        None => (StatusCode::NOT_FOUND, "Player not found").into_response(),
+    }
+}
+
+async fn clans_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let clans = match state.accounts.lock() {
+        Ok(accounts) => accounts.clan_leaderboard(),
+        Err(error) => {
+            println!("Error locking accounts: {}", error);
+            Vec::new()
+        }
+    };
+
+    Json(clans)
+}
+
+async fn create_clan_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreateClanRequest>,
+) -> Response {
+    change_accounts(
+        &state,
+        |accounts| accounts.create_clan(&request.token, &request.tag, &request.name),
+        |_| StatusCode::CREATED.into_response(),
+    )
+}
+
+async fn join_clan_handler(
+    State(state): State<Arc<AppState>>,
+    Path(tag): Path<String>,
+    Json(request): Json<TokenRequest>,
+) -> Response {
+    change_accounts(
+        &state,
+        |accounts| accounts.join_clan(&request.token, &tag),
+        |_| StatusCode::NO_CONTENT.into_response(),
+    )
+}
+
+async fn leave_clan_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<TokenRequest>,
+) -> Response {
+    change_accounts(
+        &state,
+        |accounts| accounts.leave_clan(&request.token),
+        |_| StatusCode::NO_CONTENT.into_response(),
+    )
+}
+
+// Picks the ranked room to join, the client then connects to `/game?room=` with its token
+async fn ranked_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RankedQuery>,
+) -> Response {
+    let rating = match state.accounts.lock() {
+        Ok(accounts) => accounts
+            .authenticate(&query.token)
+            .map(|account| account.rating),
+        Err(error) => {
+            println!("Error locking accounts: {}", error);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let rating = match rating {
+        Some(rating) => rating,
+        None => return account_error_response(AccountError::InvalidToken),
+    };
+
+    match Room::find_ranked(state.rooms.values(), rating) {
+        Some(room) => Json(RankedMatch {
+            room: room.info.id,
+            rating,
+        })
+        .into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "No ranked room available").into_response(),
+    }
+}
+
+// The world of a room as JSON, for the `Authorization: Bearer <admin_token>` of that room
+async fn debug_state_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DebugQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let room = match state.rooms.get(&query.room.unwrap_or(0)) {
+        Some(room) => room,
+        None => return (StatusCode::NOT_FOUND, "Room not found").into_response(),
+    };
+
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let (reply, mut dump) = mpsc::channel(1);
+    let command = InternalCommand::DumpState {
+        token: String::from(token),
+        reply,
+    };
+    if room
+        .command_tx
+        .send(Command::InternalCommand(command))
+        .await
+        .is_err()
+    {
+        return (StatusCode::SERVICE_UNAVAILABLE, "The room is down").into_response();
+    }
+
+    match dump.recv().await {
+        Some(Some(dump)) => Json(dump).into_response(),
+        Some(None) => (StatusCode::UNAUTHORIZED, "Invalid admin token").into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "The room is down").into_response(),
+    }
+}
+
+async fn schema_handler() -> impl IntoResponse {
+    Json(protocol::json_schema())
+}
+
+async fn docs_handler() -> impl IntoResponse {
+    Json(openapi::spec())
+}
+
+async fn websocket_connection(
+    stream: WebSocket,
+    state: Arc<AppState>,
+    room_id: u32,
+    ip: Option<IpAddr>,
+    encoding: Encoding,
+) {
+    let room = &state.rooms[&room_id];
+    let id = state.id_tracker.fetch_add(1, Ordering::SeqCst);
+    let (mut socket_sender, mut socket_receiver) = stream.split();
+
+    let tx_game_manager = room.command_tx.clone();
+    let mut rx_game_manager = room.broadcast_channel.subscribe();
+
+    // Registers a queue for the socket so that the game manager can send messages directly to a player
+    let (player_tx, mut player_rx) = mpsc::channel::<Frame>(PLAYER_QUEUE_LENGTH);
+    let filter = Arc::new(FrameFilter::default());
+    let reply_tx = player_tx.clone();
+    state.connection_metrics.observe_connect();
+    println!(
+        "event=connect connection={} room={} ip={}",
+        id,
+        room_id,
+        display_ip(ip)
+    );
+    let connected_at = Instant::now();
+    let stats = Arc::new(ConnectionStats::default());
+
+    if let Err(e) = tx_game_manager
+        .send(Command::InternalCommand(InternalCommand::Connect {
+            id,
+            socket: player_tx,
+            ip,
+            filter: filter.clone(),
+        }))
+        .await
+    {
+        println!("Error sending message to game manager: {}", e);
+        return;
+    }
+
+    // Writes the messages for this client, both the broadcasts and the ones sent only to this player
+    let tx_resync = tx_game_manager.clone();
+    let writer_state = state.clone();
+    let writer_stats = stats.clone();
+    tokio::spawn(async move {
+        let mut sequence: u32 = 0;
+        loop {
+            let mut frame = tokio::select! {
+                frame = player_rx.recv() => match frame {
+                    Some(frame) if filter.allows(&frame) => frame,
+                    Some(_) => continue,
+                    // The game manager dropped this connection, for example because it was kicked
+                    None => {
+                        let _ = socket_sender.send(Message::Close(None)).await;
+                        break;
+                    }
+                },
+                frame = rx_game_manager.recv() => match frame {
+                    Ok(frame) if filter.allows_broadcast(&frame) => frame,
+                    // Unsubscribed, or states the game manager sends this connection at the rate it asked for
+                    Ok(_) => continue,
+                    // The client missed some frames, the deltas that follow are useless without a fresh full state
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        println!("Client {} lagged behind by {} messages", id, skipped);
+                        if let Err(e) = tx_resync
+                            .send(Command::InternalCommand(InternalCommand::Resync { id }))
+                            .await
+                        {
+                            println!("Error sending message to game manager: {}", e);
+                            break;
+                        }
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+
+            if frame.state {
+                sequence = sequence.wrapping_add(1);
+                frame = frame.sequenced(sequence);
+            }
+            let message = match frame {
+                Frame {
+                    binary: Some(binary),
+                    ..
+                } if encoding == Encoding::Binary => Message::Binary(binary.to_vec()),
+                frame => Message::Text(frame.text.to_string()),
+            };
+            let length = match &message {
+                Message::Text(text) => text.len(),
+                Message::Binary(bytes) => bytes.len(),
+                _ => 0,
+            };
+            if let Err(e) = socket_sender.send(message).await {
+                println!("Error sending message to client {}", e);
+                break;
+            }
+            writer_stats.add(
+                &writer_stats.bytes_sent,
+                &writer_stats.messages_sent,
+                length,
+            );
+            writer_state.connection_metrics.observe_sent(length);
+        }
+    });
+
+    // Recieves messages from the client and sends them to the game manager
+    tokio::spawn(async move {
+        while let Some(Ok(Message::Text(text))) = socket_receiver.next().await {
+            println!("Received message from client: {}", text);
+            stats.add(&stats.bytes_received, &stats.messages_received, text.len());
+            state.connection_metrics.observe_received(text.len());
+
+            let command_from_socket = serde_json::from_str::<PlayerCommand>(&text);
+
+            let command_from_socket = match command_from_socket {
+                Ok(command_from_socket) => command_from_socket,
+                Err(e) => {
+                    println!("Error deserializing message: {}", e);
+                    let error = MessageToClient::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: format!("Invalid command: {}", e),
+                        command_ref: None,
+                    };
+                    if let Ok(text) = serde_json::to_string(&error) {
+                        let _ = reply_tx.try_send(Frame::text(text));
+                    }
+                    continue;
+                }
+            };
+
+            // Adds the ID to the command so that the game manager knows which player sent the command
+            let command_from_socket = PlayerMessage {
+                id,
+                command: command_from_socket,
+            };
+
+            if let Err(e) = tx_game_manager
+                .send(Command::PlayerCommand(command_from_socket))
+                .await
+            {
+                println!("Error sending message to game manager: {}", e);
+            };
+        }
+
+        // Client disconnected
+        let duration = connected_at.elapsed();
+        state.connection_metrics.observe_disconnect(duration);
+        println!(
+            "event=disconnect connection={} room={} ip={} duration_seconds={:.3} bytes_sent={} bytes_received={} messages_sent={} messages_received={}",
+            id,
+            room_id,
+            display_ip(ip),
+            duration.as_secs_f64(),
+            stats.bytes_sent.load(Ordering::Relaxed),
+            stats.bytes_received.load(Ordering::Relaxed),
+            stats.messages_sent.load(Ordering::Relaxed),
+            stats.messages_received.load(Ordering::Relaxed),
+        );
+
+        if let Err(e) = tx_game_manager
+            .send(Command::InternalCommand(InternalCommand::Disconnect { id }))
+            .await
+        {
+            println!("Error sending message to game manager: {}", e);
+        };
+    });
+}
+
+// Traffic of a single connection, shared by its reader and writer tasks
+#[derive(Default)]
+struct ConnectionStats {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+}
+
+impl ConnectionStats {
+    fn add(&self, bytes: &AtomicU64, messages: &AtomicU64, length: usize) {
+        bytes.fetch_add(length as u64, Ordering::Relaxed);
+        messages.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn display_ip(ip: Option<IpAddr>) -> String {
+    match ip {
+        Some(ip) => ip.to_string(),
+        None => String::from("unknown"),
+    }
+}
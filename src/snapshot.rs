@@ -0,0 +1,85 @@
+use crate::game_manager::Food;
+use crate::player::Player;
+use crate::vector::Vector2D;
+
+// Bumped whenever the snapshot layout changes, snapshots from newer versions are ignored
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlayerSnapshot {
+    pub name: String,
+    pub position: Vector2D,
+    pub radius: f32,
+    pub best_mass: f32,
+}
+
+impl PlayerSnapshot {
+    pub fn from_player(player: &Player) -> PlayerSnapshot {
+        PlayerSnapshot {
+            name: player.name.clone(),
+            position: player.position,
+            radius: player.radius,
+            best_mass: player.best_mass,
+        }
+    }
+
+    pub fn apply(&self, player: &mut Player) {
+        player.position = self.position;
+        player.radius = self.radius;
+        player.best_mass = self.best_mass;
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WorldSnapshot {
+    pub version: u32,
+    pub players: Vec<PlayerSnapshot>,
+    pub food: Vec<Food>,
+}
+
+impl WorldSnapshot {
+    pub fn path(room_id: u32) -> String {
+        format!("snapshot_{}.json", room_id)
+    }
+
+    pub fn save(&self, path: &str) {
+        let contents = match serde_json::to_string(self) {
+            Ok(contents) => contents,
+            Err(error) => {
+                println!("Error serializing snapshot: {}", error);
+                return;
+            }
+        };
+
+        if let Err(error) = std::fs::write(path, contents) {
+            println!("Error saving snapshot: {}", error);
+        }
+    }
+
+    // Loads and deletes the snapshot, so a crash later on doesn't restore an old world
+    pub fn take(path: &str) -> Option<WorldSnapshot> {
+        let contents = std::fs::read_to_string(path).ok()?;
+
+        if let Err(error) = std::fs::remove_file(path) {
+            println!("Error removing snapshot: {}", error);
+        }
+
+        let snapshot = match serde_json::from_str::<WorldSnapshot>(&contents) {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                println!("Error reading snapshot: {}", error);
+                return None;
+            }
+        };
+
+        if snapshot.version > SNAPSHOT_VERSION {
+            println!(
+                "Ignoring snapshot with unsupported version {} (current version is {})",
+                snapshot.version, SNAPSHOT_VERSION
+            );
+            return None;
+        }
+
+        Some(snapshot)
+    }
+}
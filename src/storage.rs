@@ -0,0 +1,120 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::time::{self, Duration};
+
+// Changes are written at most this often, so a burst of them costs one write
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Where a value is saved. Saving only marks it as changed, `keep_saved` writes it later from a task of
+// its own, so the game loop never serializes or touches the disk
+#[derive(Debug, Clone)]
+pub struct Writer {
+    path: String,
+    // Names the file in the logs
+    what: &'static str,
+    changed: Arc<AtomicBool>,
+    // Held while writing, so two flushes don't interleave on the temporary file
+    writing: Arc<Mutex<()>>,
+}
+
+impl Writer {
+    pub fn new(path: &str, what: &'static str) -> Writer {
+        Writer {
+            path: String::from(path),
+            what,
+            changed: Arc::new(AtomicBool::new(false)),
+            writing: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn mark_changed(&self) {
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    // The version goes to `<path>.tmp` first and is renamed over the file, so a crash mid-write leaves
+    // the previous one intact
+    fn write(&self, contents: String) {
+        let _writing = match self.writing.lock() {
+            Ok(writing) => writing,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let temporary_path = format!("{}.tmp", self.path);
+        let written = std::fs::write(&temporary_path, contents)
+            .and_then(|()| std::fs::rename(&temporary_path, &self.path));
+        if let Err(error) = written {
+            println!("Error saving {}: {}", self.what, error);
+        }
+    }
+}
+
+// A value with a file of its own, values without a writer are kept in memory
+pub trait Stored: serde::Serialize + Send + 'static {
+    fn writer(&self) -> Option<&Writer>;
+}
+
+// Writes the value if it changed since the last flush. The lock is only held while serializing
+pub fn flush<T: Stored>(value: &Mutex<T>) {
+    let (writer, contents) = {
+        let value = match value.lock() {
+            Ok(value) => value,
+            Err(error) => {
+                println!("Error locking a saved value: {}", error);
+                return;
+            }
+        };
+        let writer = match value.writer() {
+            Some(writer) if writer.changed.swap(false, Ordering::Relaxed) => writer.clone(),
+            _ => return,
+        };
+        match serde_json::to_string(&*value) {
+            Ok(contents) => (writer, contents),
+            Err(error) => {
+                println!("Error serializing {}: {}", writer.what, error);
+                return;
+            }
+        }
+    };
+
+    writer.write(contents);
+}
+
+// Flushes the value every `FLUSH_INTERVAL` on the blocking pool, needs a tokio runtime. Whatever changed
+// after the last tick is lost unless `flush` is called once more on shutdown
+pub fn keep_saved<T: Stored>(value: Arc<Mutex<T>>) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let value = value.clone();
+            if let Err(error) = tokio::task::spawn_blocking(move || flush(&value)).await {
+                println!("Error flushing a saved value: {}", error);
+            }
+        }
+    });
+}
+
+// Reads a file saved by a `Writer`, the default when there is none yet. A file that doesn't parse is moved to
+// `<path>.corrupt` before starting over, so it can be repaired instead of being overwritten by the next save
+pub fn load<T: serde::de::DeserializeOwned + Default>(path: &str, what: &str) -> T {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return T::default(),
+    };
+
+    match serde_json::from_str::<T>(&contents) {
+        Ok(value) => value,
+        Err(error) => {
+            let corrupt_path = format!("{}.corrupt", path);
+            println!(
+                "Error reading {}: {}, moving it to {} and starting empty",
+                what, error, corrupt_path
+            );
+            if let Err(error) = std::fs::rename(path, &corrupt_path) {
+                panic!("Can't move the unreadable {} aside: {}", what, error);
+            }
+            T::default()
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::ops;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Copy)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Copy, PartialEq)]
 pub struct Vector2D {
     pub x: f32,
     pub y: f32,
@@ -15,6 +15,10 @@ impl Vector2D {
         (self.x.powf(2.0) + self.y.powf(2.0)).sqrt()
     }
 
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+
     pub fn normalize(&self) -> Vector2D {
         let magnitude = self.magnitude();
 
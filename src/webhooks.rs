@@ -0,0 +1,99 @@
+use hyper::{Body, Client, Method, Request};
+use tokio::time::{self, Duration};
+
+const MAX_ATTEMPTS: u32 = 4;
+const FIRST_RETRY_MILLISECONDS: u64 = 1000;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum WebhookEvent {
+    NewHighScore {
+        name: String,
+        mass: f32,
+    },
+    PlayerCountThreshold {
+        room_id: u32,
+        threshold: u32,
+        player_count: u32,
+    },
+    RoundEnded {
+        room_id: u32,
+        // Map the round was played on
+        map: String,
+        // Best players of the round by mass, the winner first
+        top_scores: Vec<RoundScore>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RoundScore {
+    pub name: String,
+    pub mass: f32,
+}
+
+// Posts the event to every url in the background, retrying failed deliveries with exponential backoff.
+// Only plain `http://` urls are supported since the server has no TLS client.
+pub fn dispatch(urls: &[String], event: WebhookEvent) {
+    if urls.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_string(&event) {
+        Ok(body) => body,
+        Err(error) => {
+            println!("Error serializing webhook event: {}", error);
+            return;
+        }
+    };
+
+    for url in urls {
+        post(url.clone(), body.clone());
+    }
+}
+
+// Posts a JSON body in the background with the same retry policy as the event webhooks
+pub fn post(url: String, body: String) {
+    tokio::spawn(async move {
+        deliver(url, body).await;
+    });
+}
+
+async fn deliver(url: String, body: String) {
+    let client = Client::new();
+    let mut backoff = Duration::from_millis(FIRST_RETRY_MILLISECONDS);
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&url)
+            .header("content-type", "application/json")
+            .body(Body::from(body.clone()));
+
+        let request = match request {
+            Ok(request) => request,
+            Err(error) => {
+                println!("Invalid webhook url {}: {}", url, error);
+                return;
+            }
+        };
+
+        match client.request(request).await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => println!(
+                "Webhook {} answered {} (attempt {}/{})",
+                url,
+                response.status(),
+                attempt,
+                MAX_ATTEMPTS
+            ),
+            Err(error) => println!(
+                "Error sending webhook to {}: {} (attempt {}/{})",
+                url, error, attempt, MAX_ATTEMPTS
+            ),
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
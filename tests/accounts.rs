@@ -0,0 +1,252 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use block_explorer::accounts::{Accounts, SessionStats, STARTING_RATING};
+use block_explorer::game_manager::{MessageToClient, PlayerCommand};
+use block_explorer::room::GameMode;
+use block_explorer::storage;
+use common::{expect_message, TestServer};
+
+fn session(best_mass: f32) -> SessionStats {
+    SessionStats {
+        best_mass,
+        eats: 0,
+        playtime: Duration::from_secs(60),
+    }
+}
+
+#[tokio::test]
+async fn clan_members_join_with_their_tag() {
+    let server = TestServer::start().await;
+    let token = {
+        let mut accounts = server.state.accounts.lock().unwrap();
+        let token = accounts.register("judy").unwrap();
+        accounts.create_clan(&token, "ABC", "Alphabet").unwrap();
+        token
+    };
+
+    let mut client = server.connect().await;
+    client
+        .send(PlayerCommand::Join {
+            name: String::from("judy"),
+            token: Some(token),
+            skin: None,
+            update_rate: None,
+        })
+        .await
+        .unwrap();
+
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Welcome { player: Some(player), .. } if player.name == "[ABC] judy")
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn join_with_an_unknown_token_is_rejected() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    client
+        .send(PlayerCommand::Join {
+            name: String::from("mallory"),
+            token: Some(String::from("not a token")),
+            skin: None,
+            update_rate: None,
+        })
+        .await
+        .unwrap();
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Error { .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn clans_are_ranked_by_their_members_best_masses() {
+    let server = TestServer::start().await;
+    let mut accounts = server.state.accounts.lock().unwrap();
+
+    let alice = accounts.register("alice").unwrap();
+    let bob = accounts.register("bob").unwrap();
+    accounts.create_clan(&alice, "A", "Small").unwrap();
+    accounts.create_clan(&bob, "B", "Big").unwrap();
+    accounts.record("alice", &session(100.0));
+    accounts.record("bob", &session(500.0));
+
+    let tags: Vec<String> = accounts
+        .clan_leaderboard()
+        .into_iter()
+        .map(|clan| clan.tag)
+        .collect();
+    assert_eq!(tags, ["B", "A"]);
+    assert!(accounts.join_clan(&alice, "C").is_err());
+}
+
+#[tokio::test]
+async fn skins_must_be_in_the_inventory() {
+    let server = TestServer::start().await;
+    let token = {
+        let mut accounts = server.state.accounts.lock().unwrap();
+        let token = accounts.register("kim").unwrap();
+        accounts.grant_skin("kim", "gold").unwrap();
+        token
+    };
+
+    let mut spoofer = server.connect().await;
+    spoofer
+        .send(PlayerCommand::Join {
+            name: String::from("kim"),
+            token: Some(token.clone()),
+            skin: Some(String::from("rainbow")),
+            update_rate: None,
+        })
+        .await
+        .unwrap();
+    expect_message(&mut spoofer, |message| {
+        matches!(message, MessageToClient::Error { .. })
+    })
+    .await;
+
+    let mut client = server.connect().await;
+    client
+        .send(PlayerCommand::Join {
+            name: String::from("kim"),
+            token: Some(token),
+            skin: Some(String::from("gold")),
+            update_rate: None,
+        })
+        .await
+        .unwrap();
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Welcome { player: Some(player), .. } if player.skin.as_deref() == Some("gold"))
+    })
+    .await;
+}
+
+#[test]
+fn finished_sessions_add_up_in_the_profile() {
+    let mut game_manager = common::game_manager();
+    game_manager
+        .accounts
+        .lock()
+        .unwrap()
+        .register("liam")
+        .unwrap();
+
+    let mut eater = common::player(1, 100.0, 100.0, 30.0);
+    eater.account = Some(String::from("liam"));
+    game_manager.players.insert(eater);
+    game_manager
+        .players
+        .insert(common::player(2, 100.0, 100.0, 10.0));
+
+    let eats = game_manager.check_collision();
+    game_manager.remove_dead_players(&eats);
+    game_manager.remove_player(1, None);
+
+    let profile = game_manager
+        .accounts
+        .lock()
+        .unwrap()
+        .profile("liam")
+        .unwrap();
+    assert_eq!(profile.games_played, 1);
+    assert_eq!(profile.total_eats, 1);
+    assert!(profile.best_mass > 0.0);
+}
+
+#[test]
+fn eating_in_a_ranked_room_moves_ratings() {
+    let mut game_manager = common::game_manager_in(GameMode::Ranked);
+    {
+        let mut accounts = game_manager.accounts.lock().unwrap();
+        accounts.register("mia").unwrap();
+        accounts.register("noah").unwrap();
+    }
+
+    let mut eater = common::player(1, 100.0, 100.0, 30.0);
+    eater.account = Some(String::from("mia"));
+    let mut prey = common::player(2, 100.0, 100.0, 10.0);
+    prey.account = Some(String::from("noah"));
+    game_manager.players.insert(eater);
+    game_manager.players.insert(prey);
+
+    let eats = game_manager.check_collision();
+    game_manager.remove_dead_players(&eats);
+
+    let accounts = game_manager.accounts.lock().unwrap();
+    let winner = accounts.rating("mia").unwrap();
+    let loser = accounts.rating("noah").unwrap();
+    assert!(winner > STARTING_RATING);
+    assert!((winner - STARTING_RATING - (STARTING_RATING - loser)).abs() < 0.001);
+}
+
+#[test]
+fn upsets_are_worth_more_than_expected_wins() {
+    let mut accounts = Accounts::default();
+    accounts.register("strong").unwrap();
+    accounts.register("weak").unwrap();
+
+    accounts.rate_match("strong", "weak");
+    let expected_win = accounts.rating("strong").unwrap() - STARTING_RATING;
+
+    accounts.rate_match("weak", "strong");
+    let upset = accounts.rating("weak").unwrap() - (STARTING_RATING - expected_win);
+    assert!(upset > expected_win);
+}
+
+#[test]
+fn accounts_are_saved_on_flush_and_corrupt_files_kept_aside() {
+    let path = std::env::temp_dir().join(format!("accounts_{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    let corrupt_path = format!("{}.corrupt", path);
+
+    let accounts = Mutex::new(Accounts::load(path));
+    for username in ["first", "second", "third"] {
+        let mut accounts = accounts.lock().unwrap();
+        accounts.register(username).unwrap();
+        accounts.save();
+    }
+    storage::flush(&accounts);
+
+    let reloaded = Accounts::load(path);
+    assert!(reloaded.get("third").is_some());
+    assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+
+    std::fs::write(path, "{ \"accounts\": ").unwrap();
+    let empty = Accounts::load(path);
+    assert!(empty.get("third").is_none());
+    assert_eq!(
+        std::fs::read_to_string(&corrupt_path).unwrap(),
+        "{ \"accounts\": "
+    );
+
+    std::fs::remove_file(&corrupt_path).unwrap();
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn sessions_ending_in_a_room_are_saved_by_the_flush_not_the_tick() {
+    let path = std::env::temp_dir().join(format!("sessions_{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut game_manager = common::game_manager();
+    let mut accounts = Accounts::load(path);
+    accounts.register("saved").unwrap();
+    game_manager.accounts = Arc::new(Mutex::new(accounts));
+    let mut player = common::player(1, 100.0, 100.0, 10.0);
+    player.account = Some(String::from("saved"));
+    game_manager.players.insert(player);
+
+    game_manager.remove_player(1, None);
+    assert!(!std::path::Path::new(path).exists());
+
+    storage::flush(&game_manager.accounts);
+    let reloaded = Accounts::load(path);
+    assert_eq!(reloaded.get("saved").unwrap().games_played, 1);
+
+    std::fs::remove_file(path).unwrap();
+}
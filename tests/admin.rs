@@ -0,0 +1,378 @@
+mod common;
+
+use block_explorer::bans::BanTarget;
+use block_explorer::client::GameClient;
+use block_explorer::config::GameConfig;
+use block_explorer::game_manager::{
+    AdminCommand, ErrorCode, MessageToClient, PlayerCommand, PlayerMessage, Severity,
+};
+use common::{expect_message, TestServer};
+use hyper::{Body, Client, Request, StatusCode};
+use tokio::time::{self, Duration};
+
+fn admin_config() -> GameConfig {
+    GameConfig {
+        admin_token: Some(String::from("secret")),
+        ..GameConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn admin_can_announce_after_login() {
+    let server = TestServer::start_with_config(admin_config()).await;
+    let mut admin = server.connect().await;
+    let (mut player, _) = server.join("grace").await;
+
+    admin
+        .send(PlayerCommand::Login {
+            token: String::from("secret"),
+        })
+        .await
+        .unwrap();
+    expect_message(&mut admin, |message| {
+        matches!(message, MessageToClient::LoginSuccess)
+    })
+    .await;
+
+    admin
+        .send(PlayerCommand::Admin {
+            command: AdminCommand::Announce {
+                text: String::from("hello"),
+                severity: Severity::Warning,
+            },
+        })
+        .await
+        .unwrap();
+    expect_message(&mut player, |message| {
+        matches!(
+            message,
+            MessageToClient::Announcement { text, severity: Severity::Warning } if text == "hello"
+        )
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn admin_commands_need_a_login() {
+    let server = TestServer::start_with_config(admin_config()).await;
+    let mut client = server.connect().await;
+
+    client
+        .send(PlayerCommand::Login {
+            token: String::from("wrong"),
+        })
+        .await
+        .unwrap();
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Error { .. })
+    })
+    .await;
+
+    client
+        .send(PlayerCommand::Admin {
+            command: AdminCommand::Kick { id: 0 },
+        })
+        .await
+        .unwrap();
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Error { message, .. } if message == "Not an admin")
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn maintenance_ends_the_round_and_refuses_joins() {
+    let server = TestServer::start_with_config(admin_config()).await;
+    let (mut admin, _) = server.join("admin").await;
+    admin
+        .send(PlayerCommand::Login {
+            token: String::from("secret"),
+        })
+        .await
+        .unwrap();
+    admin
+        .send(PlayerCommand::Admin {
+            command: AdminCommand::StartMaintenance { seconds: 0 },
+        })
+        .await
+        .unwrap();
+
+    expect_message(&mut admin, |message| {
+        matches!(
+            message,
+            MessageToClient::Announcement {
+                severity: Severity::Critical,
+                ..
+            }
+        )
+    })
+    .await;
+
+    let mut late = server.connect().await;
+    late.join("late").await.unwrap();
+    expect_message(&mut late, |message| {
+        matches!(message, MessageToClient::Error { .. })
+    })
+    .await;
+}
+
+// Whether a `RoundOver` arrives within `wait`
+async fn round_ends_within(client: &mut GameClient, wait: Duration) -> bool {
+    let round_over = async {
+        loop {
+            match client.next_message().await {
+                Some(Ok(MessageToClient::RoundOver { .. })) => return,
+                Some(Ok(_)) => continue,
+                _ => std::future::pending::<()>().await,
+            }
+        }
+    };
+    time::timeout(wait, round_over).await.is_ok()
+}
+
+#[tokio::test]
+async fn pause_and_resume_are_broadcast() {
+    let config = GameConfig {
+        round_seconds: 2,
+        ..admin_config()
+    };
+    let server = TestServer::start_with_config(config).await;
+    let (mut admin, _) = server.join("ivy").await;
+    admin
+        .send(PlayerCommand::Login {
+            token: String::from("secret"),
+        })
+        .await
+        .unwrap();
+
+    admin
+        .send(PlayerCommand::Admin {
+            command: AdminCommand::Pause,
+        })
+        .await
+        .unwrap();
+    expect_message(&mut admin, |message| {
+        matches!(message, MessageToClient::GamePaused { paused: true })
+    })
+    .await;
+
+    // The round deadline passes while paused, and the time paused doesn't count once resumed
+    assert!(!round_ends_within(&mut admin, Duration::from_millis(2500)).await);
+    admin
+        .send(PlayerCommand::Admin {
+            command: AdminCommand::Resume,
+        })
+        .await
+        .unwrap();
+    expect_message(&mut admin, |message| {
+        matches!(message, MessageToClient::GamePaused { paused: false })
+    })
+    .await;
+    assert!(!round_ends_within(&mut admin, Duration::from_millis(1000)).await);
+}
+
+#[tokio::test]
+async fn ip_ban_kicks_the_connection_and_refuses_upgrades() {
+    let server = TestServer::start_with_config(admin_config()).await;
+    let (mut admin, _) = server.join("mallory").await;
+    admin
+        .send(PlayerCommand::Login {
+            token: String::from("secret"),
+        })
+        .await
+        .unwrap();
+
+    admin
+        .send(PlayerCommand::Admin {
+            command: AdminCommand::Ban {
+                target: BanTarget::Ip {
+                    ip: "127.0.0.1".parse().unwrap(),
+                },
+                minutes: Some(10),
+                reason: String::from("flooding"),
+            },
+        })
+        .await
+        .unwrap();
+    expect_message(&mut admin, |message| {
+        matches!(message, MessageToClient::Error { message, .. } if message == "You are banned: flooding")
+    })
+    .await;
+
+    assert!(GameClient::connect(&server.url()).await.is_err());
+    assert_eq!(
+        server.state.accounts.lock().unwrap().bans.active(0).len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn banned_accounts_cant_join() {
+    let server = TestServer::start().await;
+    let token = {
+        let mut accounts = server.state.accounts.lock().unwrap();
+        let token = accounts.register("oscar").unwrap();
+        accounts.bans.ban(
+            BanTarget::Account {
+                username: String::from("oscar"),
+            },
+            "cheating",
+            None,
+            0,
+        );
+        token
+    };
+
+    let mut client = server.connect().await;
+    client
+        .send(PlayerCommand::Join {
+            name: String::from("oscar"),
+            token: Some(token),
+            skin: None,
+            update_rate: None,
+        })
+        .await
+        .unwrap();
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Error { message, .. } if message == "You are banned: cheating")
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn admin_commands_are_audited() {
+    let server = TestServer::start_with_config(admin_config()).await;
+    let mut admin = server.connect().await;
+    admin
+        .send(PlayerCommand::Login {
+            token: String::from("secret"),
+        })
+        .await
+        .unwrap();
+
+    for command in [
+        AdminCommand::Pause,
+        AdminCommand::Kick { id: 42 },
+        AdminCommand::AuditLog { limit: 2 },
+    ] {
+        admin.send(PlayerCommand::Admin { command }).await.unwrap();
+    }
+
+    let message = expect_message(&mut admin, |message| {
+        matches!(message, MessageToClient::AuditLog { .. })
+    })
+    .await;
+    match message {
+        MessageToClient::AuditLog { entries } => {
+            assert_eq!(entries.len(), 2);
+            assert!(matches!(entries[0].command, AdminCommand::Kick { id: 42 }));
+            assert!(matches!(
+                entries[1].command,
+                AdminCommand::AuditLog { limit: 2 }
+            ));
+            assert!(entries[0].ip.is_some());
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn kicked_connections_are_forgotten_and_ignored() {
+    let mut game_manager = common::game_manager();
+    game_manager.admins.extend([1, 2]);
+    game_manager
+        .players
+        .insert(common::player(2, 100.0, 100.0, 10.0));
+    let (socket, _messages) = tokio::sync::mpsc::channel(16);
+    game_manager.players_sockets.insert(2, socket);
+
+    game_manager.execute_player_command(PlayerMessage {
+        id: 1,
+        command: PlayerCommand::Admin {
+            command: AdminCommand::Kick { id: 2 },
+        },
+    });
+    assert!(!game_manager.players.contains(2));
+    assert!(!game_manager.admins.contains(&2));
+    assert!(!game_manager.players_sockets.contains_key(&2));
+
+    // A command it queued before the kick
+    game_manager.execute_player_command(PlayerMessage {
+        id: 2,
+        command: PlayerCommand::Admin {
+            command: AdminCommand::Pause,
+        },
+    });
+    assert!(!game_manager.paused);
+}
+
+#[test]
+fn unknown_config_keys_are_refused() {
+    let mut game_manager = common::game_manager();
+    game_manager.admins.insert(1);
+    let (socket, mut messages) = tokio::sync::mpsc::channel(16);
+    game_manager.players_sockets.insert(1, socket);
+
+    game_manager.execute_player_command(PlayerMessage {
+        id: 1,
+        command: PlayerCommand::Admin {
+            command: AdminCommand::SetConfig {
+                key: String::from("no_such_key"),
+                value: serde_json::json!(1),
+            },
+        },
+    });
+
+    let error: MessageToClient = serde_json::from_str(&messages.try_recv().unwrap().text).unwrap();
+    assert!(matches!(
+        error,
+        MessageToClient::Error { code: ErrorCode::InvalidValue, message, .. } if message == "Unknown config key no_such_key"
+    ));
+}
+
+async fn debug_state(server: &TestServer, token: &str) -> (StatusCode, Vec<u8>) {
+    let request = Request::get(format!("http://{}/debug/state?room=0", server.addr))
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = Client::new().request(request).await.unwrap();
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, body.to_vec())
+}
+
+#[tokio::test]
+async fn debug_state_needs_the_admin_token() {
+    let server = TestServer::start_with_config(admin_config()).await;
+    let (_client, id) = server.join("oscar").await;
+
+    let (status, _) = debug_state(&server, "wrong").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = debug_state(&server, "secret").await;
+    assert_eq!(status, StatusCode::OK);
+    let dump: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(dump["players"][0]["id"], id);
+    assert_eq!(dump["private"][0]["id"], id);
+    assert!(dump["tick"].is_u64());
+    assert!(dump["config"]["admin_token"].is_null());
+}
+
+#[test]
+fn debug_output_hides_the_secrets() {
+    let config = GameConfig {
+        webhook_urls: vec![String::from("https://hooks.example.com/webhook-secret")],
+        discord_webhook_url: Some(String::from(
+            "https://discord.com/api/webhooks/1/discord-secret",
+        )),
+        master_server_url: Some(String::from(
+            "https://master.example.com/?key=master-secret",
+        )),
+        ..admin_config()
+    };
+    let output = format!("{:?}", config);
+
+    assert!(!output.contains("secret"));
+    assert!(output.contains(r#""webhook_urls":["<redacted>"]"#));
+    assert!(output.contains(r#""admin_token":"<redacted>""#));
+}
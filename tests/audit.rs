@@ -0,0 +1,21 @@
+use block_explorer::audit::AuditLog;
+use block_explorer::game_manager::AdminCommand;
+
+#[test]
+fn entries_are_appended_to_the_file_and_read_back() {
+    let path = std::env::temp_dir().join(format!("audit_{}.log", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let _ = std::fs::remove_file(&path);
+
+    let mut log = AuditLog::open(Some(path.clone()));
+    log.record(0, 1, None, AdminCommand::Pause);
+    log.record(3, 2, None, AdminCommand::Resume);
+
+    // Another room appending to the same file sees both entries
+    let entries = AuditLog::open(Some(path.clone())).recent(10);
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].room, 3);
+    assert!(matches!(entries[1].command, AdminCommand::Resume));
+}
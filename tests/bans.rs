@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use block_explorer::bans::{BanTarget, Bans};
+
+fn account(username: &str) -> BanTarget {
+    BanTarget::Account {
+        username: String::from(username),
+    }
+}
+
+#[test]
+fn temporary_bans_expire() {
+    let mut bans = Bans::default();
+    bans.ban(
+        account("peggy"),
+        "spam",
+        Some(Duration::from_secs(600)),
+        1000,
+    );
+
+    assert!(bans.find_account("peggy", 1599).is_some());
+    assert!(bans.find_account("peggy", 1600).is_none());
+    assert!(bans.active(1600).is_empty());
+}
+
+#[test]
+fn banning_again_replaces_the_earlier_ban() {
+    let mut bans = Bans::default();
+    bans.ban(account("peggy"), "spam", Some(Duration::from_secs(60)), 0);
+    bans.ban(account("peggy"), "cheating", None, 10);
+
+    let active = bans.active(100_000);
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].reason, "cheating");
+}
+
+#[test]
+fn unban_lifts_only_that_target() {
+    let mut bans = Bans::default();
+    bans.ban(account("peggy"), "spam", None, 0);
+    bans.ban(
+        BanTarget::Ip {
+            ip: "203.0.113.7".parse().unwrap(),
+        },
+        "flooding",
+        None,
+        0,
+    );
+
+    assert!(bans.unban(&account("peggy")));
+    assert!(!bans.unban(&account("peggy")));
+    assert!(bans.find_ip("203.0.113.7".parse().unwrap(), 0).is_some());
+}
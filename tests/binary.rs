@@ -0,0 +1,152 @@
+mod common;
+
+use std::sync::Arc;
+
+use futures::{SinkExt, StreamExt};
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use block_explorer::binary::{self, Encoding, Quantization};
+use block_explorer::game_manager::{Category, Frame, MessageToClient, PlayerCommand};
+use block_explorer::map::Map;
+use block_explorer::vector::Vector2D;
+use common::{player, TestServer};
+
+#[test]
+fn state_delta_round_trips_within_a_step() {
+    let quantization = Quantization::for_map(&Map::open(800.0, 600.0));
+    let moved = player(7, 123.4, 567.8, 31.5);
+
+    let bytes = binary::state_delta(&quantization, 42, &[&moved], &[3, 9]);
+    assert_eq!(bytes.len(), 1 + 8 + 4 + 2 + 10 + 2 + 8);
+
+    let delta = binary::decode_state_delta(&quantization, &bytes).unwrap();
+    assert_eq!(delta.tick, 42);
+    let (id, position, radius) = delta.players[0];
+    assert_eq!(id, 7);
+    assert!((position.x - 123.4).abs() <= quantization.x_step);
+    assert!((position.y - 567.8).abs() <= quantization.y_step);
+    assert!((radius - 31.5).abs() <= quantization.radius_step);
+    assert_eq!(delta.removed_players, vec![3, 9]);
+}
+
+#[test]
+fn sequence_numbers_are_written_per_connection() {
+    let quantization = Quantization::for_map(&Map::open(800.0, 600.0));
+    let bytes = binary::state_delta(&quantization, 42, &[], &[]);
+    let frame = Frame {
+        text: Arc::from(r#"{"StateDelta":{"tick":42,"players":[],"removed_players":[]}}"#),
+        binary: Some(Arc::from(bytes)),
+        category: Some(Category::State),
+        state: true,
+    };
+
+    let sequenced = frame.sequenced(7);
+    let message: MessageToClient = serde_json::from_str(&sequenced.text).unwrap();
+    assert!(matches!(
+        message,
+        MessageToClient::StateDelta {
+            sequence: 7,
+            tick: 42,
+            ..
+        }
+    ));
+    let delta =
+        binary::decode_state_delta(&quantization, sequenced.binary.as_deref().unwrap()).unwrap();
+    assert_eq!((delta.tick, delta.sequence), (42, 7));
+}
+
+#[test]
+fn positions_outside_the_map_are_clamped() {
+    let quantization = Quantization::for_map(&Map::open(800.0, 600.0));
+
+    assert_eq!(
+        quantization.position(Vector2D::new(-5.0, 900.0)),
+        (0, u16::MAX)
+    );
+    assert!(binary::decode_state_delta(&quantization, &[binary::STATE_DELTA, 1, 0]).is_none());
+}
+
+#[tokio::test]
+async fn binary_clients_get_packed_state_deltas() {
+    let server = TestServer::start().await;
+    let url = format!("{}?encoding=binary", server.url());
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+    let text = |command: PlayerCommand| Message::Text(serde_json::to_string(&command).unwrap());
+    socket
+        .send(text(PlayerCommand::Join {
+            name: String::from("olive"),
+            token: None,
+            skin: None,
+            update_rate: None,
+        }))
+        .await
+        .unwrap();
+
+    let mut quantization = None;
+    let delta = timeout(Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            match message {
+                // Moves once the full state is in, so the next update can only be a delta
+                Message::Text(message) => match serde_json::from_str(&message) {
+                    Ok(MessageToClient::Welcome {
+                        quantization: q, ..
+                    }) => quantization = Some(q),
+                    Ok(MessageToClient::State { .. }) => {
+                        let step = PlayerCommand::Move {
+                            position: Vector2D::new(700.0, 500.0),
+                            client_time: None,
+                        };
+                        socket.send(text(step)).await.unwrap();
+                    }
+                    _ => {}
+                },
+                Message::Binary(bytes) => return bytes,
+                _ => {}
+            }
+        }
+        panic!("connection closed before a binary frame");
+    })
+    .await
+    .expect("no binary frame arrived in time");
+
+    let quantization = quantization.expect("the welcome comes before any delta");
+    let delta = binary::decode_state_delta(&quantization, &delta).unwrap();
+    assert_eq!(delta.players.len(), 1);
+}
+
+fn with_subprotocol(server: &TestServer, protocols: &str) -> Request {
+    let mut request = server.url().into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("sec-websocket-protocol", protocols.parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn subprotocols_are_negotiated_in_the_client_order() {
+    let server = TestServer::start().await;
+    let request = with_subprotocol(&server, "luisgar.v0, luisgar.bin.v1, luisgar.json.v1");
+    let (_socket, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    assert_eq!(
+        response.headers()["sec-websocket-protocol"],
+        Encoding::Binary.subprotocol()
+    );
+}
+
+#[tokio::test]
+async fn unknown_subprotocols_are_rejected() {
+    let server = TestServer::start().await;
+    let request = with_subprotocol(&server, "luisgar.v0");
+
+    match tokio_tungstenite::connect_async(request).await {
+        Err(tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), 400);
+        }
+        other => panic!("expected a rejected handshake, got {:?}", other.map(|_| ())),
+    }
+}
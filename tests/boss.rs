@@ -0,0 +1,39 @@
+mod common;
+
+use block_explorer::boss::Boss;
+use block_explorer::game_manager::InternalCommand;
+use block_explorer::vector::Vector2D;
+use common::{game_manager, player};
+
+#[test]
+fn bosses_reward_the_players_who_ate_their_chunks() {
+    let mut game_manager = game_manager();
+    game_manager.config.boss_reward_mass = 1000.0;
+    game_manager.boss = Some(Boss::new(Vector2D::new(400.0, 300.0), 1000.0));
+    game_manager.players.insert(player(1, 410.0, 300.0, 30.0));
+    let starting_mass = game_manager.players.get(1).unwrap().mass();
+
+    for _ in 0..30 {
+        game_manager.execute_internal_command(InternalCommand::Update);
+    }
+
+    assert!(game_manager.boss.is_none());
+    // Two chunks of 500 and the reward, a little less once growth is capped
+    let mass = game_manager.players.get(1).unwrap().mass();
+    assert!(mass > starting_mass + 1500.0);
+}
+
+#[test]
+fn bosses_eat_the_smaller_players_they_cover() {
+    let mut game_manager = game_manager();
+    game_manager.boss = Some(Boss::new(Vector2D::new(400.0, 300.0), 20000.0));
+    game_manager.players.insert(player(1, 405.0, 300.0, 10.0));
+    game_manager.players.insert(player(2, 100.0, 100.0, 10.0));
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+
+    assert!(game_manager.players.get(1).is_none());
+    game_manager.execute_internal_command(InternalCommand::Update);
+    let boss = game_manager.boss.as_ref().unwrap();
+    assert!(boss.velocity.x < 0.0 && boss.velocity.y < 0.0);
+}
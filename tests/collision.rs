@@ -0,0 +1,355 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use block_explorer::game_manager::{
+    ConnectionState, GameManager, InternalCommand, MessageToClient, PlayerCommand, PlayerMessage,
+};
+use block_explorer::map::{Map, Rect, SafeZone};
+use block_explorer::room::{GameMode, Room};
+use block_explorer::rules::{DefaultRules, GameRules};
+use block_explorer::vector::Vector2D;
+use common::{game_manager, player};
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant};
+
+fn radius(game_manager: &GameManager, id: u32) -> f32 {
+    game_manager.players.get(id).unwrap().radius
+}
+
+#[test]
+fn bigger_player_eats_smaller_player_once() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 110.0, 100.0, 10.0));
+
+    let eats = game_manager.check_collision();
+
+    assert_eq!(eats, vec![(1, 2)]);
+    assert_eq!(
+        radius(&game_manager, 1),
+        DefaultRules.radius_after_eat(20.0, 10.0)
+    );
+    assert_eq!(radius(&game_manager, 2), 0.0);
+}
+
+#[test]
+fn smaller_player_listed_first_is_still_eaten() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 10.0));
+    game_manager.players.insert(player(2, 110.0, 100.0, 20.0));
+
+    assert_eq!(game_manager.check_collision(), vec![(2, 1)]);
+}
+
+#[test]
+fn equal_players_resolve_in_favour_of_the_lower_id() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(7, 100.0, 100.0, 15.0));
+    game_manager.players.insert(player(3, 105.0, 100.0, 15.0));
+
+    assert_eq!(game_manager.check_collision(), vec![(3, 7)]);
+    assert_eq!(
+        radius(&game_manager, 3),
+        DefaultRules.radius_after_eat(15.0, 15.0)
+    );
+    assert_eq!(radius(&game_manager, 7), 0.0);
+}
+
+#[test]
+fn eaten_player_takes_no_part_in_later_collisions() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 30.0));
+    game_manager.players.insert(player(2, 105.0, 100.0, 20.0));
+    game_manager.players.insert(player(3, 110.0, 100.0, 10.0));
+
+    let eats = game_manager.check_collision();
+
+    assert_eq!(eats, vec![(1, 2), (1, 3)]);
+    assert_eq!(radius(&game_manager, 2), 0.0);
+    assert_eq!(radius(&game_manager, 3), 0.0);
+}
+
+#[test]
+fn players_apart_do_not_collide() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 200.0, 100.0, 10.0));
+
+    assert!(game_manager.check_collision().is_empty());
+    assert_eq!(radius(&game_manager, 1), 20.0);
+    assert_eq!(radius(&game_manager, 2), 10.0);
+}
+
+#[test]
+fn eaten_player_is_removed_in_the_same_update() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 110.0, 100.0, 10.0));
+
+    game_manager.update();
+
+    assert!(game_manager.players.contains(1));
+    assert!(!game_manager.players.contains(2));
+}
+
+#[test]
+fn growth_is_capped_at_the_max_radius() {
+    let mut game_manager = game_manager();
+    let max_radius = game_manager.config.max_radius;
+    game_manager
+        .players
+        .insert(player(1, 300.0, 300.0, max_radius - 1.0));
+    game_manager.players.insert(player(2, 310.0, 300.0, 50.0));
+
+    assert_eq!(game_manager.check_collision(), vec![(1, 2)]);
+    assert_eq!(radius(&game_manager, 1), max_radius);
+}
+
+#[test]
+fn squad_members_do_not_eat_each_other() {
+    let mut game_manager = game_manager();
+    game_manager.rules = GameMode::Squads.rules();
+
+    let mut big = player(1, 100.0, 100.0, 20.0);
+    big.squad = Some(0);
+    let mut small = player(2, 110.0, 100.0, 10.0);
+    small.squad = Some(0);
+    let mut rival = player(3, 90.0, 100.0, 10.0);
+    rival.squad = Some(1);
+
+    game_manager.players.insert(big);
+    game_manager.players.insert(small);
+    game_manager.players.insert(rival);
+
+    assert_eq!(game_manager.check_collision(), vec![(1, 3)]);
+}
+
+#[test]
+fn eaten_player_spectates_their_eater() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 110.0, 100.0, 10.0));
+    game_manager.connections.insert(1, ConnectionState::Playing);
+    game_manager.connections.insert(2, ConnectionState::Playing);
+
+    game_manager.update();
+
+    assert_eq!(
+        game_manager.connections[&2],
+        ConnectionState::Spectating { target: Some(1) }
+    );
+}
+
+#[test]
+fn streaks_are_announced_at_milestones() {
+    let mut game_manager = game_manager();
+    let (channel, mut frames) = tokio::sync::broadcast::channel(16);
+    game_manager.broadcast_channel = channel;
+    game_manager.players.insert(player(1, 100.0, 100.0, 50.0));
+    game_manager.players.get_mut(1).unwrap().streak = 1;
+    game_manager.players.insert(player(2, 110.0, 100.0, 10.0));
+    game_manager.players.insert(player(3, 90.0, 100.0, 10.0));
+
+    game_manager.update();
+
+    let streaks: Vec<(u32, u32)> = std::iter::from_fn(|| frames.try_recv().ok())
+        .filter_map(|frame| match serde_json::from_str(&frame.text) {
+            Ok(MessageToClient::KillStreak { id, streak }) => Some((id, streak)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(streaks, vec![(1, 3)]);
+}
+
+#[test]
+fn bounties_go_to_the_lasting_leader_and_their_eater() {
+    let mut game_manager = game_manager();
+    game_manager.config.bounty_seconds = 30;
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 400.0, 400.0, 30.0));
+    game_manager.players.insert(player(3, 700.0, 500.0, 10.0));
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+    assert!(matches!(game_manager.leader, Some((2, _))));
+    assert_eq!(game_manager.bounty, None);
+
+    game_manager.leader = Some((2, Instant::now() - Duration::from_secs(31)));
+    game_manager.execute_internal_command(InternalCommand::Update);
+    assert_eq!(game_manager.bounty, Some(2));
+
+    let mass_before = game_manager.players.get(3).unwrap().mass();
+    game_manager.remove_player(2, Some(3));
+    assert_eq!(game_manager.bounty, None);
+    let gained = game_manager.players.get(3).unwrap().mass() - mass_before;
+    assert!((gained - game_manager.config.bounty_mass).abs() < 1.0);
+}
+
+#[test]
+fn light_players_are_safe_inside_safe_zones() {
+    let mut map = Map::open(800.0, 600.0);
+    map.safe_zones.push(SafeZone {
+        area: Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 200.0,
+            height: 200.0,
+        },
+        max_mass: 1000.0,
+        speed: 0.5,
+    });
+    let mut game_manager = common::game_manager_with(GameMode::FreeForAll, vec![map.clone()]);
+    game_manager.players.insert(player(1, 100.0, 100.0, 40.0));
+    game_manager.players.insert(player(2, 110.0, 100.0, 10.0));
+    game_manager.players.insert(player(3, 400.0, 400.0, 40.0));
+    game_manager.players.insert(player(4, 410.0, 400.0, 10.0));
+
+    assert_eq!(game_manager.check_collision(), vec![(3, 4)]);
+
+    let big = player(1, 100.0, 100.0, 40.0).mass();
+    assert_eq!(map.speed_multiplier(Vector2D::new(100.0, 100.0), big), 0.5);
+    assert_eq!(map.speed_multiplier(Vector2D::new(400.0, 400.0), big), 1.0);
+}
+
+#[test]
+fn last_man_standing_rounds_lock_and_end_with_one_player() {
+    let mut game_manager = common::game_manager_in(GameMode::LastManStanding);
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 500.0, 500.0, 10.0));
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+    assert!(game_manager.round_underway);
+    assert!(game_manager.info.round_underway.load(Ordering::Relaxed));
+
+    game_manager.food = Arc::default();
+    game_manager.execute_internal_command(InternalCommand::AddPlayer {
+        id: 3,
+        name: String::from("late"),
+        token: None,
+        skin: None,
+    });
+    game_manager.execute_internal_command(InternalCommand::Update);
+    assert!(game_manager.food.is_empty());
+    assert!(game_manager.players.get(3).is_none());
+
+    game_manager.players.get_mut(2).unwrap().position = Vector2D::new(100.0, 100.0);
+    game_manager.execute_internal_command(InternalCommand::Update);
+    assert!(!game_manager.round_underway);
+    assert!(!game_manager.info.round_underway.load(Ordering::Relaxed));
+    assert!(game_manager.players.is_empty());
+}
+
+#[test]
+fn rooms_are_not_joinable_while_a_round_is_underway() {
+    let mut game_manager = common::game_manager_in(GameMode::LastManStanding);
+    let room = Room {
+        info: game_manager.info.clone(),
+        command_tx: game_manager.command_tx.clone(),
+        broadcast_channel: broadcast::channel(1).0,
+    };
+    assert!(room.summary().joinable);
+
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 500.0, 500.0, 10.0));
+    game_manager.execute_internal_command(InternalCommand::Update);
+    assert!(room.summary().round_underway);
+    assert!(!room.summary().joinable);
+}
+
+#[test]
+fn eaten_players_respawn_with_part_of_their_best_mass() {
+    let mut game_manager = game_manager();
+    game_manager.config.starting_mass = Some(1000.0);
+    game_manager.config.respawn_mass_fraction = 0.5;
+    game_manager.config.respawn_mass_cap = 3000.0;
+    let join = |game_manager: &mut GameManager| {
+        game_manager.execute_internal_command(InternalCommand::AddPlayer {
+            id: 2,
+            name: String::from("again"),
+            token: None,
+            skin: None,
+        });
+        game_manager.players.get(2).unwrap().mass()
+    };
+
+    assert!((join(&mut game_manager) - 1000.0).abs() < 0.1);
+
+    game_manager.players.get_mut(2).unwrap().best_mass = 10000.0;
+    game_manager.players.insert(player(1, 100.0, 100.0, 10.0));
+    game_manager.remove_player(2, Some(1));
+    assert!((join(&mut game_manager) - 3000.0).abs() < 0.1);
+
+    // Only the life right after being eaten gets it
+    game_manager.remove_player(2, None);
+    assert!((join(&mut game_manager) - 1000.0).abs() < 0.1);
+}
+
+#[test]
+fn teammates_push_each_other_apart() {
+    let mut game_manager = common::game_manager_in(GameMode::Squads);
+    for (id, x) in [(1, 100.0), (2, 105.0)] {
+        let mut teammate = player(id, x, 100.0, 10.0);
+        teammate.squad = Some(0);
+        game_manager.players.insert(teammate);
+    }
+
+    game_manager.update();
+
+    let x = |game_manager: &GameManager, id| game_manager.players.get(id).unwrap().position.x;
+    assert!((x(&game_manager, 1) - 96.25).abs() < 0.001);
+    assert!((x(&game_manager, 2) - 108.75).abs() < 0.001);
+
+    game_manager.config.collision_stiffness = 0.0;
+    game_manager.update();
+    assert!((x(&game_manager, 2) - x(&game_manager, 1) - 12.5).abs() < 0.001);
+}
+
+fn unix_millis() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64()
+        * 1000.0
+}
+
+#[test]
+fn lagging_moves_are_checked_against_past_positions() {
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    game_manager.players.insert(player(2, 135.0, 100.0, 10.0));
+    game_manager.tick = 10;
+    // The smaller player got away 5 ticks ago
+    game_manager
+        .position_history
+        .push_back((5, vec![(2, Vector2D::new(115.0, 100.0))]));
+
+    let lagging_move = |client_time| PlayerMessage {
+        id: 1,
+        command: PlayerCommand::Move {
+            position: Vector2D::new(100.0, 100.0),
+            client_time: Some(client_time),
+        },
+    };
+    game_manager.execute_player_command(lagging_move(unix_millis()));
+    assert!(game_manager.players.contains(2));
+
+    // Without a measured round trip the claimed lag isn't trusted
+    game_manager.execute_player_command(lagging_move(unix_millis() - 50.0));
+    assert!(game_manager.players.contains(2));
+
+    let ack = |tick| PlayerMessage {
+        id: 1,
+        command: PlayerCommand::AckKeyframe { tick },
+    };
+    // A 20ms round trip doesn't reach back the 5 ticks the move claims
+    game_manager.execute_player_command(ack(8));
+    game_manager.execute_player_command(lagging_move(unix_millis() - 50.0));
+    assert!(game_manager.players.contains(2));
+
+    game_manager.tick = 15;
+    game_manager.execute_player_command(ack(10));
+    game_manager.tick = 10;
+    game_manager.execute_player_command(lagging_move(unix_millis() - 50.0));
+    assert!(!game_manager.players.contains(2));
+}
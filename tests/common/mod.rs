@@ -0,0 +1,171 @@
+// Shared helpers for the integration tests: every test file that needs them declares `mod common;`
+// and not every file uses every helper.
+#![allow(dead_code)]
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+
+use block_explorer::accounts::Accounts;
+use block_explorer::client::GameClient;
+use block_explorer::config::GameConfig;
+use block_explorer::game_manager::{GameManager, MessageToClient};
+use block_explorer::leaderboard::Leaderboard;
+use block_explorer::map::Map;
+use block_explorer::metrics::{ConnectionMetrics, TickMetrics};
+use block_explorer::player::Player;
+use block_explorer::room::{GameMode, Room, RoomInfo};
+use block_explorer::server::{self, AppState};
+use block_explorer::vector::Vector2D;
+use tokio::sync::broadcast;
+use tokio::time::{self, Duration};
+
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// A real server with a single room, listening on an ephemeral port and never touching the disk
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub state: Arc<AppState>,
+}
+
+impl TestServer {
+    pub async fn start() -> TestServer {
+        TestServer::start_with_config(GameConfig::default()).await
+    }
+
+    pub async fn start_with_config(config: GameConfig) -> TestServer {
+        let tick_metrics = Arc::new(TickMetrics::default());
+        let connection_metrics = Arc::new(ConnectionMetrics::default());
+        let leaderboard = Arc::new(std::sync::Mutex::new(Leaderboard::default()));
+        let accounts = Arc::new(std::sync::Mutex::new(Accounts::default()));
+
+        let room = Room::start(
+            RoomInfo::new(
+                0,
+                GameMode::FreeForAll,
+                vec![Arc::new(Map::open(800.0, 600.0))],
+                50,
+            ),
+            config,
+            tick_metrics.clone(),
+            connection_metrics.clone(),
+            leaderboard.clone(),
+            accounts.clone(),
+        );
+
+        let state = Arc::new(AppState::new(
+            vec![room],
+            tick_metrics,
+            connection_metrics,
+            leaderboard,
+            accounts,
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind test listener");
+        let addr = listener.local_addr().expect("failed to read test address");
+        let app = server::router(state.clone());
+
+        tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .expect("failed to start test server")
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .expect("test server failed");
+        });
+
+        TestServer { addr, state }
+    }
+
+    pub fn url(&self) -> String {
+        format!("ws://{}/game", self.addr)
+    }
+
+    pub async fn connect(&self) -> GameClient {
+        GameClient::connect(&self.url())
+            .await
+            .expect("failed to connect to test server")
+    }
+
+    // Connects and joins, returning the client together with the id assigned by the server
+    pub async fn join(&self, name: &str) -> (GameClient, u32) {
+        let mut client = self.connect().await;
+        client.join(name).await.expect("failed to send join");
+
+        let message = expect_message(&mut client, |message| {
+            matches!(message, MessageToClient::JoinSuccess { .. })
+        })
+        .await;
+
+        match message {
+            MessageToClient::JoinSuccess { id } => (client, id),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Skips messages until one matches the predicate, failing the test if none arrives in time
+pub async fn expect_message<F>(client: &mut GameClient, mut predicate: F) -> MessageToClient
+where
+    F: FnMut(&MessageToClient) -> bool,
+{
+    let wait = async {
+        loop {
+            match client.next_message().await {
+                Some(Ok(message)) if predicate(&message) => return message,
+                Some(Ok(_)) => continue,
+                Some(Err(error)) => panic!("error receiving message: {}", error),
+                None => panic!("connection closed while waiting for a message"),
+            }
+        }
+    };
+
+    time::timeout(MESSAGE_TIMEOUT, wait)
+        .await
+        .expect("timed out waiting for a message")
+}
+
+// Waits for a state frame that satisfies the predicate
+pub async fn expect_state<F>(client: &mut GameClient, mut predicate: F) -> MessageToClient
+where
+    F: FnMut(&MessageToClient) -> bool,
+{
+    expect_message(client, |message| {
+        matches!(message, MessageToClient::State { .. }) && predicate(message)
+    })
+    .await
+}
+
+// A game manager that isn't running, for tests that drive the simulation passes by hand
+pub fn game_manager() -> GameManager {
+    game_manager_in(GameMode::FreeForAll)
+}
+
+pub fn game_manager_in(mode: GameMode) -> GameManager {
+    game_manager_with(mode, vec![Map::open(800.0, 600.0)])
+}
+
+pub fn game_manager_with(mode: GameMode, maps: Vec<Map>) -> GameManager {
+    let info = Arc::new(RoomInfo::new(
+        0,
+        mode,
+        maps.into_iter().map(Arc::new).collect(),
+        50,
+    ));
+
+    let (broadcast_channel, _) = broadcast::channel(1);
+    GameManager::new(
+        info,
+        GameConfig::default(),
+        broadcast_channel,
+        Arc::new(TickMetrics::default()),
+        Arc::new(ConnectionMetrics::default()),
+        Arc::new(std::sync::Mutex::new(Leaderboard::default())),
+        Arc::new(std::sync::Mutex::new(Accounts::default())),
+    )
+}
+
+pub fn player(id: u32, x: f32, y: f32, radius: f32) -> Player {
+    let mut player = Player::new(id, format!("player-{}", id));
+    player.position = Vector2D::new(x, y);
+    player.radius = radius;
+    player
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use block_explorer::crash::{self, CrashGuard};
+use block_explorer::parallel;
+
+#[test]
+fn panicking_room_is_marked_as_crashed_and_unlocks_shared_state() {
+    let game_manager = common::game_manager();
+    let info = game_manager.info.clone();
+    let accounts = game_manager.accounts.clone();
+    let guard = CrashGuard {
+        info: game_manager.info.clone(),
+        accounts: game_manager.accounts.clone(),
+        leaderboard: game_manager.leaderboard.clone(),
+    };
+
+    let task_accounts = accounts.clone();
+    let result = std::thread::spawn(move || {
+        let _guard = guard;
+        let _accounts = task_accounts.lock().unwrap();
+        crash::in_room(0, 7, || panic!("broken tick"));
+    })
+    .join();
+
+    assert!(result.is_err());
+    assert!(info.crashed.load(Ordering::Relaxed));
+    assert!(accounts.lock().is_ok());
+}
+
+#[test]
+fn room_that_stops_normally_is_not_crashed() {
+    let game_manager = common::game_manager();
+    let info = game_manager.info.clone();
+    drop(CrashGuard {
+        info: game_manager.info.clone(),
+        accounts: game_manager.accounts.clone(),
+        leaderboard: game_manager.leaderboard.clone(),
+    });
+
+    assert!(!info.crashed.load(Ordering::Relaxed));
+}
+
+#[test]
+fn panics_on_the_collision_threads_reach_the_room() {
+    let items = Arc::new((0..parallel::PARALLEL_THRESHOLD).collect::<Vec<usize>>());
+
+    let result = std::panic::catch_unwind(|| {
+        crash::in_room(0, 7, || {
+            parallel::map(&items, parallel::PARALLEL_THRESHOLD, |index, _| {
+                if index == 1 {
+                    panic!("broken check");
+                }
+            })
+        })
+    });
+
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"broken check"));
+    assert_eq!(crash::context(), None);
+}
+
+#[test]
+fn big_maps_keep_the_order_of_the_items() {
+    let items = Arc::new((0..parallel::PARALLEL_THRESHOLD).collect::<Vec<usize>>());
+
+    let mapped = parallel::map(&items, parallel::PARALLEL_THRESHOLD, |index, item| {
+        index + item
+    });
+
+    assert!(mapped
+        .iter()
+        .enumerate()
+        .all(|(index, &sum)| sum == 2 * index));
+    assert_eq!(Arc::strong_count(&items), 1);
+}
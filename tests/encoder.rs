@@ -0,0 +1,72 @@
+mod common;
+
+use std::sync::Arc;
+
+use block_explorer::encoder::{self, Broadcast, BLOCKING_ENTITIES};
+use block_explorer::game_manager::{Food, Frame, MessageToClient};
+use block_explorer::vector::Vector2D;
+use tokio::sync::broadcast;
+
+fn big_food() -> Vec<Food> {
+    (0..BLOCKING_ENTITIES as u32 + 1)
+        .map(|id| Food {
+            id,
+            position: Vector2D::new(1.0, 2.0),
+            radius: 5.0,
+            velocity: None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn broadcasts_keep_their_order_around_big_states() {
+    let (channel, mut frames) = broadcast::channel(8);
+    let encoder = encoder::spawn(channel);
+
+    encoder
+        .send(Broadcast::Frame(Frame::text(String::from("before"))))
+        .unwrap();
+    encoder
+        .send(Broadcast::State {
+            tick: 3,
+            checksum: 0,
+            players: Arc::default(),
+            food: Arc::new(big_food()),
+        })
+        .unwrap();
+    encoder
+        .send(Broadcast::Frame(Frame::text(String::from("after"))))
+        .unwrap();
+
+    assert_eq!(&*frames.recv().await.unwrap().text, "before");
+    let state = frames.recv().await.unwrap();
+    assert!(state.state);
+    let message: MessageToClient = serde_json::from_str(&state.text).unwrap();
+    assert!(
+        matches!(message, MessageToClient::State { tick: 3, ref food, .. } if food.len() > BLOCKING_ENTITIES)
+    );
+    assert_eq!(&*frames.recv().await.unwrap().text, "after");
+}
+
+#[tokio::test]
+async fn only_big_states_share_the_world_with_the_encoder() {
+    let mut game_manager = common::game_manager();
+    game_manager.config.keyframe_ticks = 1;
+    game_manager.spawn_encoder();
+    let _frames = game_manager.broadcast_channel.subscribe();
+    game_manager
+        .players
+        .insert(common::player(1, 100.0, 100.0, 10.0));
+    assert!(game_manager.players.len() + game_manager.food.len() <= BLOCKING_ENTITIES);
+
+    // Small states are encoded during the tick, nothing is left to copy on the next write
+    game_manager.send_state();
+    assert_eq!(Arc::strong_count(&game_manager.food), 1);
+    assert_eq!(Arc::strong_count(&game_manager.players.snapshot()), 2);
+
+    // Big ones share a snapshot with the encoder task, which hasn't run yet
+    game_manager.food = Arc::new(big_food());
+    game_manager.send_state();
+    assert_eq!(Arc::strong_count(&game_manager.food), 2);
+    assert_eq!(Arc::strong_count(&game_manager.players.snapshot()), 3);
+}
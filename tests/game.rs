@@ -0,0 +1,590 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use block_explorer::checksum;
+use block_explorer::config::GameConfig;
+use block_explorer::game_manager::{
+    Category, ErrorCode, GameManager, MessageToClient, Phase, PlayerCommand, PlayerMessage,
+};
+use block_explorer::metrics;
+use block_explorer::protocol::PROTOCOL_VERSION;
+use block_explorer::server::ServerStats;
+use block_explorer::vector::Vector2D;
+use common::{expect_message, expect_state, TestServer};
+use tokio::time::{self, Duration};
+
+fn has_player(message: &MessageToClient, id: u32) -> bool {
+    match message {
+        MessageToClient::State { players, .. } => players.iter().any(|player| player.id == id),
+        _ => false,
+    }
+}
+
+#[tokio::test]
+async fn joined_player_appears_in_state() {
+    let server = TestServer::start().await;
+    let (mut client, id) = server.join("alice").await;
+
+    let state = expect_state(&mut client, |message| has_player(message, id)).await;
+
+    if let MessageToClient::State { players, .. } = state {
+        let player = players.iter().find(|player| player.id == id).unwrap();
+        assert_eq!(player.name, "alice");
+    }
+}
+
+#[tokio::test]
+async fn rename_is_broadcast_to_other_players() {
+    let server = TestServer::start().await;
+    let (mut alice, alice_id) = server.join("alice").await;
+    let (mut bob, _) = server.join("bob").await;
+
+    alice.rename("  carol\n").await.unwrap();
+
+    let renamed = expect_message(&mut bob, |message| {
+        matches!(message, MessageToClient::PlayerRenamed { .. })
+    })
+    .await;
+
+    match renamed {
+        MessageToClient::PlayerRenamed { id, name } => {
+            assert_eq!(id, alice_id);
+            assert_eq!(name, "carol");
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn disconnected_player_is_removed_from_state() {
+    let server = TestServer::start().await;
+    let (alice, alice_id) = server.join("alice").await;
+    let (mut bob, _) = server.join("bob").await;
+
+    expect_state(&mut bob, |message| has_player(message, alice_id)).await;
+    alice.close().await.unwrap();
+
+    expect_message(&mut bob, |message| match message {
+        MessageToClient::StateDelta {
+            removed_players, ..
+        } => removed_players.contains(&alice_id),
+        _ => false,
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn disconnect_is_broadcast_as_player_left() {
+    let server = TestServer::start().await;
+    let (alice, alice_id) = server.join("alice").await;
+    let (mut bob, _) = server.join("bob").await;
+
+    expect_state(&mut bob, |message| has_player(message, alice_id)).await;
+    alice.close().await.unwrap();
+
+    expect_message(
+        &mut bob,
+        |message| matches!(message, MessageToClient::PlayerLeft { id } if *id == alice_id),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn join_is_followed_by_welcome() {
+    let server = TestServer::start().await;
+    let (mut client, id) = server.join("dave").await;
+
+    let welcome = expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Welcome { .. })
+    })
+    .await;
+
+    if let MessageToClient::Welcome {
+        protocol_version,
+        width,
+        height,
+        player,
+        ..
+    } = welcome
+    {
+        assert_eq!(protocol_version, PROTOCOL_VERSION);
+        assert_eq!((width, height), (800.0, 600.0));
+        let player = player.expect("the welcome after joining has the player");
+        assert_eq!(player.id, id);
+        assert_eq!(player.name, "dave");
+    }
+}
+
+#[tokio::test]
+async fn second_join_is_rejected() {
+    let server = TestServer::start().await;
+    let (mut client, id) = server.join("erin").await;
+
+    client.join("erin again").await.unwrap();
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Error { .. })
+    })
+    .await;
+
+    // Another join makes the server send a full state right away
+    let _other = server.join("ivan").await;
+    let state = expect_state(&mut client, |message| has_player(message, id)).await;
+    if let MessageToClient::State { players, .. } = state {
+        assert_eq!(players.iter().filter(|player| player.id == id).count(), 1);
+        assert_eq!(
+            players.iter().find(|player| player.id == id).unwrap().name,
+            "erin"
+        );
+    }
+}
+
+#[tokio::test]
+async fn move_before_join_is_rejected() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    client
+        .move_towards(Vector2D::new(10.0, 10.0))
+        .await
+        .unwrap();
+    expect_message(&mut client, |message| {
+        matches!(
+            message,
+            MessageToClient::Error { code: ErrorCode::NotJoined, command_ref: Some(command), .. }
+                if command == "Move"
+        )
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn renaming_during_the_cooldown_is_rate_limited() {
+    let server = TestServer::start().await;
+    let (mut client, _) = server.join("grace").await;
+
+    client.rename("grace2").await.unwrap();
+    client.rename("grace3").await.unwrap();
+    expect_message(&mut client, |message| {
+        matches!(
+            message,
+            MessageToClient::Error {
+                code: ErrorCode::RateLimited,
+                ..
+            }
+        )
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn cheats_are_rejected_outside_sandbox_rooms() {
+    let server = TestServer::start().await;
+    let (mut client, _) = server.join("frank").await;
+
+    client
+        .send(PlayerCommand::SetMass { mass: 100000.0 })
+        .await
+        .unwrap();
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Error { .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn motd_is_sent_on_join() {
+    let server = TestServer::start_with_config(GameConfig {
+        motd: Some(String::from("welcome to the arena")),
+        ..GameConfig::default()
+    })
+    .await;
+    let (mut client, _) = server.join("heidi").await;
+
+    expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::Announcement { text, .. } if text == "welcome to the arena")
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn spectator_is_told_when_their_target_leaves() {
+    let server = TestServer::start().await;
+    let (alice, alice_id) = server.join("alice").await;
+    let mut spectator = server.connect().await;
+
+    spectator
+        .send(PlayerCommand::Spectate {
+            target_id: alice_id,
+        })
+        .await
+        .unwrap();
+    expect_message(&mut spectator, |message| {
+        matches!(message, MessageToClient::Spectating { target: Some(id) } if *id == alice_id)
+    })
+    .await;
+
+    alice.close().await.unwrap();
+    expect_message(&mut spectator, |message| {
+        matches!(message, MessageToClient::Spectating { target: None })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn night_falls_after_the_day() {
+    let server = TestServer::start_with_config(GameConfig {
+        day_seconds: 0,
+        night_seconds: 60,
+        ..GameConfig::default()
+    })
+    .await;
+    let (mut client, _) = server.join("judy").await;
+
+    expect_message(&mut client, |message| {
+        matches!(
+            message,
+            MessageToClient::PhaseChanged { phase: Phase::Night, vision_radius } if *vision_radius == 500.0
+        )
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn connections_are_counted_in_metrics() {
+    let server = TestServer::start().await;
+    let (mut client, id) = server.join("alice").await;
+    expect_state(&mut client, |message| has_player(message, id)).await;
+
+    let metrics = server.state.connection_metrics.render();
+    assert!(metrics.contains("game_connections_total 1\n"));
+    assert!(metrics.contains("game_joins_total 1\n"));
+    assert!(!metrics.contains("game_messages_received_total 0\n"));
+    assert!(!metrics.contains("game_messages_sent_total 0\n"));
+}
+
+#[test]
+fn keyframe_requests_wait_for_the_ack() {
+    let mut game_manager = common::game_manager();
+    let (socket, mut messages) = tokio::sync::mpsc::channel(16);
+    game_manager.players_sockets.insert(1, socket);
+    game_manager.tick = 10;
+
+    let request = |game_manager: &mut GameManager, command: PlayerCommand| {
+        game_manager.execute_player_command(PlayerMessage { id: 1, command });
+    };
+
+    request(&mut game_manager, PlayerCommand::RequestKeyframe);
+    let keyframe: MessageToClient =
+        serde_json::from_str(&messages.try_recv().unwrap().text).unwrap();
+    assert!(matches!(keyframe, MessageToClient::State { tick: 10, .. }));
+
+    request(&mut game_manager, PlayerCommand::RequestKeyframe);
+    assert!(messages.try_recv().is_err());
+
+    request(&mut game_manager, PlayerCommand::AckKeyframe { tick: 10 });
+    request(&mut game_manager, PlayerCommand::RequestKeyframe);
+    assert!(messages.try_recv().is_ok());
+}
+
+#[tokio::test]
+async fn downsampled_connections_get_fewer_states() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    client
+        .send(PlayerCommand::Join {
+            name: String::from("mobile"),
+            token: None,
+            skin: None,
+            update_rate: Some(10),
+        })
+        .await
+        .unwrap();
+    let id = match expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::JoinSuccess { .. })
+    })
+    .await
+    {
+        MessageToClient::JoinSuccess { id } => id,
+        _ => unreachable!(),
+    };
+    expect_state(&mut client, |message| has_player(message, id)).await;
+
+    for _ in 0..3 {
+        client
+            .move_towards(Vector2D::new(1000.0, 1000.0))
+            .await
+            .unwrap();
+        let tick = match expect_message(&mut client, |message| {
+            matches!(
+                message,
+                MessageToClient::State { .. } | MessageToClient::StateDelta { .. }
+            )
+        })
+        .await
+        {
+            MessageToClient::State { tick, .. } | MessageToClient::StateDelta { tick, .. } => tick,
+            _ => unreachable!(),
+        };
+        assert_eq!(tick % 10, 0);
+    }
+}
+
+#[tokio::test]
+async fn time_sync_echoes_the_client_time() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    client
+        .send(PlayerCommand::TimeSyncRequest { client_time: 42.5 })
+        .await
+        .unwrap();
+
+    let response = expect_message(&mut client, |message| {
+        matches!(message, MessageToClient::TimeSyncResponse { .. })
+    })
+    .await;
+    match response {
+        MessageToClient::TimeSyncResponse {
+            client_time,
+            server_receive_time,
+            server_send_time,
+            tick_time,
+            ..
+        } => {
+            assert_eq!(client_time, 42.5);
+            assert!(server_receive_time <= server_send_time);
+            assert!(tick_time <= server_receive_time);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn unsubscribed_categories_are_not_sent() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let is_state = |message: &MessageToClient| message.category() == Some(Category::State);
+    let time_sync =
+        |message: &MessageToClient| matches!(message, MessageToClient::TimeSyncResponse { .. });
+
+    client
+        .send(PlayerCommand::Unsubscribe {
+            categories: vec![Category::State],
+        })
+        .await
+        .unwrap();
+    client
+        .send(PlayerCommand::TimeSyncRequest { client_time: 0.0 })
+        .await
+        .unwrap();
+    // Frames the writer had taken before the unsubscription come before this reply
+    expect_message(&mut client, time_sync).await;
+
+    client.join("dashboard").await.unwrap();
+    client
+        .move_towards(Vector2D::new(1000.0, 1000.0))
+        .await
+        .unwrap();
+    time::sleep(Duration::from_millis(200)).await;
+    client
+        .send(PlayerCommand::TimeSyncRequest { client_time: 0.0 })
+        .await
+        .unwrap();
+
+    expect_message(&mut client, |message| {
+        assert!(!is_state(message), "got {:?}", message);
+        time_sync(message)
+    })
+    .await;
+}
+
+#[test]
+fn players_serialize_as_their_public_view() {
+    let mut player = common::player(1, 10.0, 20.0, 15.0);
+    player.account = Some(String::from("alice"));
+    player.eats = 3;
+
+    let serialized = serde_json::to_value(&player).unwrap();
+    assert_eq!(serialized, serde_json::to_value(player.public()).unwrap());
+    assert!(serialized.get("account").is_none());
+}
+
+#[tokio::test]
+async fn private_state_only_goes_to_the_owner() {
+    let server = TestServer::start().await;
+    let (mut alice, alice_id) = server.join("alice").await;
+    let (_bob, bob_id) = server.join("bob").await;
+
+    let message = expect_message(&mut alice, |message| {
+        if let MessageToClient::PrivateState { player, .. } = message {
+            assert_ne!(player.id, bob_id);
+        }
+        matches!(message, MessageToClient::PrivateState { .. })
+    })
+    .await;
+    match message {
+        MessageToClient::PrivateState { player, .. } => {
+            assert_eq!(player.id, alice_id);
+            assert_eq!(player.rename_cooldown_seconds, 0.0);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn connections_watch_the_arena_before_joining() {
+    // No scheduled keyframe arrives during the test, the state has to come with the connection
+    let server = TestServer::start_with_config(GameConfig {
+        keyframe_ticks: 100_000,
+        ..GameConfig::default()
+    })
+    .await;
+    let (_alice, alice_id) = server.join("alice").await;
+
+    let mut watcher = server.connect().await;
+    expect_message(&mut watcher, |message| {
+        matches!(message, MessageToClient::Welcome { player: None, .. })
+    })
+    .await;
+    expect_state(&mut watcher, |message| has_player(message, alice_id)).await;
+}
+
+#[test]
+fn snapshots_rebuild_the_whole_view() {
+    let mut game_manager = common::game_manager();
+    let (socket, mut messages) = tokio::sync::mpsc::channel(16);
+    game_manager.players_sockets.insert(1, socket);
+    game_manager.tick = 10;
+
+    game_manager.execute_player_command(PlayerMessage {
+        id: 1,
+        command: PlayerCommand::RequestSnapshot,
+    });
+
+    let mut received = Vec::new();
+    while let Ok(frame) = messages.try_recv() {
+        received.push(serde_json::from_str::<MessageToClient>(&frame.text).unwrap());
+    }
+    assert!(matches!(
+        received.first(),
+        Some(MessageToClient::Welcome { player: None, .. })
+    ));
+    assert!(matches!(
+        received.last(),
+        Some(MessageToClient::State { tick: 10, .. })
+    ));
+}
+
+async fn stats(server: &TestServer) -> ServerStats {
+    let uri = format!("http://{}/stats", server.addr).parse().unwrap();
+    let response = hyper::Client::new().get(uri).await.unwrap();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn stats_show_the_biggest_player_and_are_cached() {
+    let server = TestServer::start().await;
+    let (_client, _) = server.join("nora").await;
+
+    let info = server.state.rooms[&0].info.clone();
+    while info.biggest_player.lock().unwrap().is_none() {
+        time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let first = stats(&server).await;
+    assert_eq!(first.players_online, 1);
+    assert_eq!(first.rooms, 1);
+    assert_eq!(
+        first.biggest_player.map(|player| player.name).as_deref(),
+        Some("nora")
+    );
+
+    let (_other, _) = server.join("otto").await;
+    let cached = stats(&server).await;
+    assert_eq!(cached.players_online, 1);
+}
+
+#[test]
+fn overloaded_rooms_broadcast_every_few_ticks() {
+    let mut game_manager = common::game_manager();
+    let (channel, mut frames) = tokio::sync::broadcast::channel(16);
+    game_manager.broadcast_channel = channel;
+    game_manager.config.keyframe_ticks = 1;
+    game_manager
+        .info
+        .broadcast_stride
+        .store(3, Ordering::Relaxed);
+
+    for _ in 0..6 {
+        game_manager.send_state();
+    }
+
+    let mut ticks = Vec::new();
+    while let Ok(frame) = frames.try_recv() {
+        if let Ok(MessageToClient::State { tick, .. }) = serde_json::from_str(&frame.text) {
+            ticks.push(tick);
+        }
+    }
+    assert_eq!(ticks, vec![3, 6]);
+
+    let rates = metrics::render_rates(std::iter::once(&*game_manager.info));
+    assert!(rates.contains("game_simulation_rate_hz{room=\"0\"} 100\n"));
+    assert!(rates.contains("game_broadcast_rate_hz{room=\"0\"} 33.3"));
+}
+
+#[test]
+fn clients_can_recompute_the_state_checksum() {
+    let mut game_manager = common::game_manager();
+    game_manager
+        .players
+        .insert(common::player(1, 120.5, 80.25, 12.0));
+    game_manager
+        .players
+        .insert(common::player(2, 300.0, 410.75, 20.5));
+    let (channel, mut frames) = tokio::sync::broadcast::channel(16);
+    game_manager.broadcast_channel = channel;
+
+    game_manager.send_state();
+
+    let frame = frames.try_recv().unwrap();
+    let (checksum, players, food) = match serde_json::from_str(&frame.text).unwrap() {
+        MessageToClient::State {
+            checksum,
+            players,
+            food,
+            ..
+        } => (checksum, players, food),
+        _ => panic!("expected a full state"),
+    };
+    let recomputed = checksum::world(
+        players
+            .iter()
+            .rev()
+            .map(|player| (player.id, player.position, player.radius)),
+        &food,
+    );
+    assert_eq!(recomputed, checksum);
+
+    game_manager.players.as_mut_slice()[0].radius += 1.0;
+    assert_ne!(game_manager.checksum(), checksum);
+}
+
+#[test]
+fn invalid_config_files_are_errors_instead_of_defaults() {
+    let path = std::env::temp_dir().join(format!("config-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    assert!(GameConfig::try_load(path).is_ok());
+
+    std::fs::write(path, r#"{ "decay_rate": "#).unwrap();
+    assert!(GameConfig::try_load(path).is_err());
+
+    std::fs::write(path, r#"{ "decay_rate": 0.5 }"#).unwrap();
+    assert_eq!(GameConfig::try_load(path).unwrap().decay_rate, 0.5);
+    std::fs::remove_file(path).unwrap();
+
+    // Only a missing file falls back to the defaults, not one that can't be read
+    let directory = std::env::temp_dir();
+    assert!(GameConfig::try_load(directory.to_str().unwrap()).is_err());
+}
@@ -0,0 +1,34 @@
+use std::sync::Mutex;
+
+use block_explorer::leaderboard::{Leaderboard, Period};
+use block_explorer::storage;
+
+#[test]
+fn leaderboards_survive_a_reload_and_corrupt_files_are_kept_aside() {
+    let path = std::env::temp_dir().join(format!("leaderboard_{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    let corrupt_path = format!("{}.corrupt", path);
+
+    let leaderboard = Mutex::new(Leaderboard::load(path));
+    {
+        let mut leaderboard = leaderboard.lock().unwrap();
+        leaderboard.record(String::from("first"), 100.0);
+        leaderboard.save();
+        leaderboard.record(String::from("second"), 200.0);
+        leaderboard.save();
+    }
+    // Saving only marks the leaderboard, the flush writes it
+    assert!(!std::path::Path::new(path).exists());
+    storage::flush(&leaderboard);
+
+    let top = Leaderboard::load(path).top(Period::All);
+    assert_eq!(top.len(), 2);
+    assert_eq!(top[0].name, "second");
+
+    std::fs::write(path, "[").unwrap();
+    assert!(Leaderboard::load(path).top(Period::All).is_empty());
+    assert_eq!(std::fs::read_to_string(&corrupt_path).unwrap(), "[");
+
+    std::fs::remove_file(&corrupt_path).unwrap();
+    let _ = std::fs::remove_file(path);
+}
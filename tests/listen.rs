@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use block_explorer::listen::ListenAddress;
+
+fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<ListenAddress, String> {
+    let args: Vec<String> = args.iter().map(|arg| String::from(*arg)).collect();
+    ListenAddress::from_args(&args, |name| {
+        env.iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| String::from(*value))
+    })
+}
+
+#[test]
+fn defaults_to_localhost() {
+    assert_eq!(
+        parse(&[], &[]),
+        Ok(ListenAddress::Tcp("127.0.0.1:3000".parse().unwrap()))
+    );
+}
+
+#[test]
+fn flags_override_the_environment() {
+    assert_eq!(
+        parse(&["--port", "8080"], &[("HOST", "0.0.0.0"), ("PORT", "80")]),
+        Ok(ListenAddress::Tcp("0.0.0.0:8080".parse().unwrap()))
+    );
+}
+
+#[test]
+fn unix_socket_takes_precedence() {
+    assert_eq!(
+        parse(&["--unix-socket", "/tmp/gario.sock", "--port", "8080"], &[]),
+        Ok(ListenAddress::Unix(PathBuf::from("/tmp/gario.sock")))
+    );
+}
+
+#[test]
+fn bad_values_are_errors() {
+    assert!(parse(&["--port", "seventy"], &[]).is_err());
+    assert!(parse(&["--host", "not an ip"], &[]).is_err());
+    assert!(parse(&["--port"], &[]).is_err());
+    assert!(parse(&["--verbose"], &[]).is_err());
+}
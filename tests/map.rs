@@ -0,0 +1,151 @@
+mod common;
+
+use std::sync::Arc;
+
+use block_explorer::game_manager::InternalCommand;
+use block_explorer::map::{Map, MapError};
+use block_explorer::room::GameMode;
+use block_explorer::vector::Vector2D;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::time::{self, Duration, Instant};
+
+const MAP: &str = r#"{
+    "name": "Islands",
+    "width": 1000,
+    "height": 1000,
+    "obstacles": [{ "position": { "x": 500, "y": 500 }, "radius": 50 }],
+    "spawn_zones": [{ "x": 0, "y": 0, "width": 100, "height": 100 }],
+    "food_regions": [{ "x": 800, "y": 800, "width": 200, "height": 200 }],
+    "portals": [{ "entrance": { "position": { "x": 10, "y": 990 }, "radius": 5 }, "exit": { "x": 990, "y": 10 } }]
+}"#;
+
+#[test]
+fn map_file_is_loaded() {
+    let map = Map::from_json(MAP).unwrap();
+
+    assert_eq!(map.name, "Islands");
+    assert_eq!(map.obstacles.len(), 1);
+    assert!(map.virus_regions.is_empty());
+
+    for _ in 0..100 {
+        assert!(map.spawn_zones[0].contains(map.spawn_point().unwrap()));
+        assert!(map.food_regions[0].contains(map.food_point(5.0)));
+    }
+}
+
+#[test]
+fn zones_outside_the_map_are_reported() {
+    let map = MAP.replace(
+        r#""x": 800, "y": 800, "width": 200"#,
+        r#""x": 900, "y": 800, "width": 200"#,
+    );
+
+    match Map::from_json(&map) {
+        Err(MapError::Invalid(message)) => assert!(message.starts_with("food_regions[0]")),
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    let map = MAP.replace("\"obstacles\"", "\"obstacle\"");
+    assert!(matches!(Map::from_json(&map), Err(MapError::Parse(_))));
+}
+
+#[test]
+fn rounds_rotate_through_the_maps() {
+    let mut small = Map::open(400.0, 400.0);
+    small.name = String::from("Small");
+    let mut game_manager =
+        common::game_manager_with(GameMode::FreeForAll, vec![Map::open(800.0, 600.0), small]);
+    game_manager.config.round_seconds = 60;
+    game_manager
+        .players
+        .insert(common::player(1, 100.0, 100.0, 10.0));
+
+    game_manager.round_started_at = Instant::now() - Duration::from_secs(61);
+    game_manager.execute_internal_command(InternalCommand::Update);
+
+    assert_eq!(game_manager.map.name, "Small");
+    assert!(game_manager.players.is_empty());
+    assert!(game_manager
+        .food
+        .iter()
+        .all(|food| food.position.x <= 400.0 && food.position.y <= 400.0));
+
+    game_manager.next_map(Vec::new());
+    assert_eq!(game_manager.map.name, "Open");
+}
+
+#[tokio::test]
+async fn round_results_are_sent_to_the_webhooks() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut game_manager = common::game_manager();
+    game_manager.config.round_seconds = 60;
+    game_manager.config.webhook_urls = vec![format!("http://{}", listener.local_addr().unwrap())];
+    let mut winner = common::player(1, 100.0, 100.0, 20.0);
+    winner.name = String::from("winner");
+    game_manager.players.insert(winner);
+    game_manager
+        .players
+        .insert(common::player(2, 500.0, 500.0, 10.0));
+
+    game_manager.round_started_at = Instant::now() - Duration::from_secs(61);
+    game_manager.execute_internal_command(InternalCommand::Update);
+
+    // The players leaving also post their high scores
+    let event = loop {
+        let (mut socket, _) = time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut request = Vec::new();
+        while !request.ends_with(b"}") {
+            let mut buffer = [0; 1024];
+            let read = socket.read(&mut buffer).await.unwrap();
+            assert!(read > 0);
+            request.extend_from_slice(&buffer[..read]);
+        }
+
+        let request = String::from_utf8(request).unwrap();
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        let event: serde_json::Value = serde_json::from_str(body).unwrap();
+        if event.get("RoundEnded").is_some() {
+            break event;
+        }
+    };
+    let round = &event["RoundEnded"];
+    assert_eq!(round["map"], game_manager.info.maps[0].name.as_str());
+    assert_eq!(round["top_scores"].as_array().unwrap().len(), 2);
+    assert_eq!(round["top_scores"][0]["name"], "winner");
+}
+
+#[test]
+fn cluster_centers_stay_in_the_food_regions() {
+    let map = Map::from_json(MAP).unwrap();
+    let centers = map.cluster_centers(4);
+
+    assert_eq!(centers.len(), 4);
+    assert!(centers
+        .iter()
+        .all(|center| center.x >= 800.0 && center.y >= 800.0));
+}
+
+#[test]
+fn clustered_food_spawns_around_its_patches() {
+    let mut game_manager = common::game_manager();
+    game_manager.config.food_clusters = 1;
+    game_manager.config.food_cluster_radius = 50.0;
+    game_manager.food = Arc::default();
+    let center = Vector2D::new(400.0, 300.0);
+    game_manager.food_clusters = vec![center];
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+
+    assert!(!game_manager.food.is_empty());
+    assert!(game_manager
+        .food
+        .iter()
+        .all(|food| (food.position - center).magnitude() <= 50.001));
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use std::sync::Arc;
+
+use block_explorer::game_manager::{Food, InternalCommand};
+use block_explorer::map::{Map, Rect, TerrainKind, TerrainZone};
+use block_explorer::vector::Vector2D;
+use common::{game_manager, player};
+
+fn map_with(kind: TerrainKind, strength: f32) -> Arc<Map> {
+    let mut map = Map::open(800.0, 600.0);
+    map.terrain.push(TerrainZone {
+        kind,
+        area: Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 200.0,
+            height: 200.0,
+        },
+        strength,
+    });
+    Arc::new(map)
+}
+
+fn distance_moved(time_scale: f32) -> f32 {
+    let mut game_manager = game_manager();
+    game_manager.config.time_scale = time_scale;
+    game_manager.players.insert(player(1, 100.0, 100.0, 10.0));
+
+    game_manager.move_player(1, Vector2D::new(700.0, 100.0));
+    game_manager.players.get(1).unwrap().position.x - 100.0
+}
+
+#[test]
+fn time_scale_multiplies_movement() {
+    let normal = distance_moved(1.0);
+
+    assert!(normal > 0.0);
+    assert!((distance_moved(2.0) - 2.0 * normal).abs() < 0.001);
+    assert_eq!(distance_moved(0.0), 0.0);
+}
+
+#[test]
+fn negative_time_scale_freezes_players() {
+    assert_eq!(distance_moved(-1.0), 0.0);
+}
+
+#[test]
+fn mud_slows_players_down() {
+    let normal = distance_moved(1.0);
+
+    let mut game_manager = game_manager();
+    game_manager.map = map_with(TerrainKind::Mud, 0.5);
+    game_manager.players.insert(player(1, 100.0, 100.0, 10.0));
+    game_manager.move_player(1, Vector2D::new(700.0, 100.0));
+
+    let distance = game_manager.players.get(1).unwrap().position.x - 100.0;
+    assert!((distance - normal / 2.0).abs() < 0.001);
+}
+
+#[test]
+fn danger_zones_drain_mass() {
+    let mut game_manager = game_manager();
+    game_manager.map = map_with(TerrainKind::Danger, 0.5);
+    game_manager.players.insert(player(1, 100.0, 100.0, 40.0));
+    game_manager.players.insert(player(2, 500.0, 500.0, 40.0));
+    game_manager.food = Arc::default();
+
+    game_manager.update();
+
+    assert!(game_manager.players.get(1).unwrap().radius < 40.0);
+    assert_eq!(game_manager.players.get(2).unwrap().radius, 40.0);
+}
+
+#[test]
+fn terrain_strength_is_validated() {
+    let mut map = (*map_with(TerrainKind::Boost, 0.5)).clone();
+    assert!(map.validate().is_err());
+
+    map.terrain[0].strength = 1.5;
+    assert!(map.validate().is_ok());
+}
+
+#[test]
+fn empty_rooms_shrink_and_push_players_inside() {
+    let mut game_manager = game_manager();
+    game_manager.config.min_map_scale = 0.5;
+    game_manager.config.players_for_full_map = 100;
+    game_manager.players.insert(player(1, 10.0, 10.0, 10.0));
+    game_manager.tick = 100;
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+    assert_eq!(game_manager.map_scale, 0.5);
+    let bounds = game_manager.bounds();
+    assert_eq!((bounds.x, bounds.y, bounds.width), (200.0, 150.0, 400.0));
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+    let position = game_manager.players.get(1).unwrap().position;
+    assert!(position.x > 10.0 && position.y > 10.0);
+}
+
+#[test]
+fn wandering_food_bounces_off_the_bounds() {
+    let mut game_manager = game_manager();
+    game_manager.food = Arc::new(vec![Food {
+        id: 9999,
+        position: Vector2D::new(799.5, 300.0),
+        radius: 4.0,
+        velocity: Some(Vector2D::new(100.0, 0.0)),
+    }]);
+    game_manager.tick = 1;
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+
+    let food = game_manager
+        .food
+        .iter()
+        .find(|food| food.id == 9999)
+        .unwrap();
+    assert_eq!(food.position.x, 800.0);
+    assert_eq!(food.velocity.unwrap().x, -100.0);
+}
+
+#[test]
+fn dashes_speed_players_up_for_some_mass() {
+    let normal = distance_moved(1.0);
+    let mut game_manager = game_manager();
+    game_manager.players.insert(player(1, 100.0, 100.0, 20.0));
+    let mass = game_manager.players.get(1).unwrap().mass();
+
+    game_manager.dash_player(1);
+    let dashed_mass = game_manager.players.get(1).unwrap().mass();
+    assert!((dashed_mass - mass * 0.95).abs() < 0.1);
+
+    // A second dash during the cooldown is refused
+    game_manager.dash_player(1);
+    assert_eq!(game_manager.players.get(1).unwrap().mass(), dashed_mass);
+    assert!(game_manager.players.get(1).unwrap().dash_cooldown(1.0) > 4.0);
+
+    game_manager.move_player(1, Vector2D::new(700.0, 100.0));
+    let moved = game_manager.players.get(1).unwrap().position.x - 100.0;
+    // Three times the speed of its size, speed falls with the radius
+    let radius = game_manager.players.get(1).unwrap().radius;
+    assert!((moved - normal * 3.0 * 10.0 / radius).abs() < 0.01);
+}
+
+#[test]
+fn steering_players_move_every_tick() {
+    let normal = distance_moved(1.0);
+    let mut game_manager = game_manager();
+    // Food in its way would make it grow and slow down
+    game_manager.food = Arc::default();
+    game_manager.config.food_spawn_per_tick = 0;
+    game_manager.players.insert(player(1, 100.0, 100.0, 10.0));
+
+    game_manager.steer_player(1, Vector2D::new(0.0, 5.0), 0.5);
+    game_manager.update();
+    game_manager.update();
+    let position = game_manager.players.get(1).unwrap().position;
+    assert!((position.y - 100.0 - normal).abs() < 0.001);
+    assert_eq!(position.x, 100.0);
+
+    // Targeting a point again stops the steering
+    game_manager.move_player(1, position);
+    game_manager.update();
+    assert_eq!(game_manager.players.get(1).unwrap().position, position);
+
+    // Holding a direction stops at the edge of the room
+    game_manager.players.get_mut(1).unwrap().position = Vector2D::new(799.0, 300.0);
+    game_manager.steer_player(1, Vector2D::new(1.0, 0.0), 1.0);
+    for _ in 0..10 {
+        game_manager.update();
+    }
+    assert_eq!(
+        game_manager.players.get(1).unwrap().position,
+        Vector2D::new(800.0, 300.0)
+    );
+}
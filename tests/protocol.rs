@@ -0,0 +1,195 @@
+mod common;
+
+use block_explorer::bans::BanTarget;
+use block_explorer::game_manager::{AdminCommand, MessageToClient, PlayerCommand, Severity};
+use block_explorer::protocol;
+use block_explorer::vector::Vector2D;
+use common::TestServer;
+use serde_json::{json, Value};
+use tokio::time::{self, Duration};
+
+// Enough of JSON Schema for the keywords `protocol::json_schema` uses
+fn check(schema: &Value, value: &Value, definitions: &Value) -> Result<(), String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.trim_start_matches("#/definitions/");
+        return check(&definitions[name], value, definitions);
+    }
+
+    if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        let matching = variants
+            .iter()
+            .filter(|variant| check(variant, value, definitions).is_ok())
+            .count();
+        if matching != 1 {
+            return Err(format!("{} matches {} of the variants", value, matching));
+        }
+        return Ok(());
+    }
+
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            return Err(format!("{} isn't {}", value, constant));
+        }
+        return Ok(());
+    }
+
+    let matches_type = match schema.get("type").and_then(Value::as_str) {
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        Some("string") => value.is_string(),
+        Some("null") => value.is_null(),
+        Some("array") => value.is_array(),
+        Some("object") => value.is_object(),
+        _ => true,
+    };
+    if !matches_type {
+        return Err(format!("{} isn't of type {}", value, schema["type"]));
+    }
+
+    if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+        for item in values {
+            check(items, item, definitions)?;
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let required = schema.get("required").and_then(Value::as_array);
+        for name in required.into_iter().flatten() {
+            if !object.contains_key(name.as_str().unwrap()) {
+                return Err(format!("{} is missing {}", value, name));
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, field) in object {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => check(property, field, definitions)?,
+                None if schema["additionalProperties"] == false => {
+                    return Err(format!("{} has an unknown field {}", value, key));
+                }
+                None => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn assert_matches_schema(type_name: &str, value: &Value) {
+    let schema = protocol::json_schema();
+    let definitions = &schema["definitions"];
+    if let Err(error) = check(&definitions[type_name], value, definitions) {
+        panic!(
+            "{} doesn't match the {} schema: {}",
+            value, type_name, error
+        );
+    }
+}
+
+#[test]
+fn player_commands_match_the_schema() {
+    let commands = vec![
+        PlayerCommand::Move {
+            position: Vector2D::new(1.0, 2.0),
+            client_time: Some(1000.0),
+        },
+        PlayerCommand::Join {
+            name: String::from("player"),
+            token: None,
+            skin: Some(String::from("gold")),
+            update_rate: None,
+        },
+        PlayerCommand::Steer {
+            direction: Vector2D::new(1.0, 0.0),
+            throttle: 0.5,
+        },
+        PlayerCommand::Dash,
+        PlayerCommand::TimeSyncRequest { client_time: 10.0 },
+        PlayerCommand::Admin {
+            command: AdminCommand::Ban {
+                target: BanTarget::Account {
+                    username: String::from("cheater"),
+                },
+                minutes: None,
+                reason: String::from("cheating"),
+            },
+        },
+        PlayerCommand::Admin {
+            command: AdminCommand::Announce {
+                text: String::from("hello"),
+                severity: Severity::Info,
+            },
+        },
+    ];
+
+    for command in commands {
+        assert_matches_schema("PlayerCommand", &serde_json::to_value(&command).unwrap());
+    }
+}
+
+#[test]
+fn commands_leaving_out_defaulted_fields_match_the_schema() {
+    let commands = [
+        json!({ "Move": { "position": { "x": 1, "y": 2 } } }),
+        json!({ "Join": { "name": "player" } }),
+    ];
+
+    for command in commands {
+        serde_json::from_value::<PlayerCommand>(command.clone()).unwrap();
+        assert_matches_schema("PlayerCommand", &command);
+    }
+}
+
+#[test]
+fn defaulted_fields_can_be_left_out_in_typescript() {
+    let typescript = protocol::typescript();
+
+    assert!(typescript.contains("{ Move: { position: Vector2D; client_time?: number } }"));
+    assert!(typescript.contains(
+        "{ Join: { name: string; token?: string; skin?: string; update_rate?: number } }"
+    ));
+    // Optional fields the server sends are always there, as null when empty
+    assert!(typescript.contains("squad: number | null"));
+    assert!(typescript.contains("export interface Vector2D { x: number; y: number }"));
+}
+
+#[tokio::test]
+async fn server_messages_match_the_schema() {
+    let server = TestServer::start().await;
+    let (mut client, _) = server.join("player").await;
+    client
+        .send(PlayerCommand::Move {
+            position: Vector2D::new(400.0, 300.0),
+            client_time: None,
+        })
+        .await
+        .unwrap();
+    client
+        .send(PlayerCommand::TimeSyncRequest { client_time: 10.0 })
+        .await
+        .unwrap();
+    client
+        .send(PlayerCommand::Rename {
+            name: String::from("renamed"),
+        })
+        .await
+        .unwrap();
+
+    // Everything the server sent until the rename, the last of the answers, and a keyframe
+    let mut messages = vec![MessageToClient::JoinSuccess { id: 1 }];
+    let (mut renamed, mut keyframe) = (false, false);
+    while !renamed || !keyframe {
+        let message = time::timeout(Duration::from_secs(2), client.next_message())
+            .await
+            .expect("timed out waiting for a message")
+            .unwrap()
+            .unwrap();
+        renamed |= matches!(message, MessageToClient::PlayerRenamed { .. });
+        keyframe |= matches!(message, MessageToClient::State { .. });
+        messages.push(message);
+    }
+
+    for message in messages {
+        assert_matches_schema("MessageToClient", &serde_json::to_value(&message).unwrap());
+    }
+}
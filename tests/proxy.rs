@@ -0,0 +1,62 @@
+use std::net::IpAddr;
+
+use axum::http::{HeaderMap, HeaderValue};
+use block_explorer::proxy::TrustedProxies;
+
+fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(name, HeaderValue::from_static(value));
+    headers
+}
+
+fn proxies() -> TrustedProxies {
+    TrustedProxies::parse(&[String::from("10.0.0.0/8"), String::from("192.168.1.1")])
+}
+
+#[test]
+fn untrusted_peers_are_taken_at_their_word() {
+    let forwarded = headers("x-forwarded-for", "1.2.3.4");
+    assert_eq!(
+        proxies().client_ip(Some(ip("8.8.8.8")), &forwarded),
+        Some(ip("8.8.8.8"))
+    );
+}
+
+#[test]
+fn forwarded_chain_is_read_from_the_right() {
+    // The client made up the first address, the proxies appended the rest
+    let forwarded = headers("x-forwarded-for", "6.6.6.6, 1.2.3.4, 10.0.0.2");
+    assert_eq!(
+        proxies().client_ip(Some(ip("192.168.1.1")), &forwarded),
+        Some(ip("1.2.3.4"))
+    );
+}
+
+#[test]
+fn forwarded_header_is_understood() {
+    let forwarded = headers(
+        "forwarded",
+        "for=\"[2001:db8::1]:4711\";proto=https, for=10.1.2.3",
+    );
+    assert_eq!(
+        proxies().client_ip(Some(ip("10.0.0.1")), &forwarded),
+        Some(ip("2001:db8::1"))
+    );
+}
+
+#[test]
+fn unix_socket_connections_trust_the_headers() {
+    let forwarded = headers("x-forwarded-for", "1.2.3.4");
+    assert_eq!(
+        TrustedProxies::default().client_ip(None, &forwarded),
+        Some(ip("1.2.3.4"))
+    );
+    assert_eq!(
+        TrustedProxies::default().client_ip(None, &HeaderMap::new()),
+        None
+    );
+}
@@ -0,0 +1,45 @@
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use block_explorer::rate_limit::UpgradeLimiter;
+
+fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+#[test]
+fn attempts_over_the_limit_are_refused_until_the_minute_ends() {
+    let limiter = UpgradeLimiter::new(2);
+    let now = Instant::now();
+
+    assert!(limiter.check(ip("203.0.113.7"), now).is_ok());
+    assert!(limiter.check(ip("203.0.113.7"), now).is_ok());
+    let wait = limiter
+        .check(ip("203.0.113.7"), now + Duration::from_secs(20))
+        .unwrap_err();
+    assert_eq!(wait, Duration::from_secs(40));
+
+    assert!(limiter
+        .check(ip("203.0.113.7"), now + Duration::from_secs(60))
+        .is_ok());
+}
+
+#[test]
+fn addresses_are_limited_separately() {
+    let limiter = UpgradeLimiter::new(1);
+    let now = Instant::now();
+
+    assert!(limiter.check(ip("203.0.113.7"), now).is_ok());
+    assert!(limiter.check(ip("203.0.113.7"), now).is_err());
+    assert!(limiter.check(ip("2001:db8::1"), now).is_ok());
+}
+
+#[test]
+fn zero_disables_the_limit() {
+    let limiter = UpgradeLimiter::new(0);
+    let now = Instant::now();
+
+    for _ in 0..100 {
+        assert!(limiter.check(ip("203.0.113.7"), now).is_ok());
+    }
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use block_explorer::registry::Heartbeat;
+use block_explorer::room::GameMode;
+use common::TestServer;
+
+#[tokio::test]
+async fn heartbeat_reports_the_rooms_and_their_players() {
+    let server = TestServer::start().await;
+    let (_client, _) = server.join("nina").await;
+
+    let heartbeat = Heartbeat::new(
+        "ws://play.example.com/game",
+        Some(String::from("eu-west")),
+        &server.state,
+    );
+
+    assert_eq!(heartbeat.address, "ws://play.example.com/game");
+    assert_eq!(heartbeat.region.as_deref(), Some("eu-west"));
+    assert_eq!(heartbeat.player_count, 1);
+    assert_eq!(heartbeat.max_players, 50);
+    assert_eq!(heartbeat.modes, vec![GameMode::FreeForAll]);
+    assert_eq!(heartbeat.rooms.len(), 1);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use block_explorer::game_manager::InternalCommand;
+use block_explorer::schedule::{Schedule, ScheduledEvent};
+
+// Saturday 2024-01-06 18:00 UTC
+const SATURDAY_EVENING: u64 = 1_704_564_000;
+
+fn weekend(duration_minutes: u64) -> ScheduledEvent {
+    serde_json::from_str(&format!(
+        r#"{{ "name": "Double mass", "start": "0 18 6", "duration_minutes": {} }}"#,
+        duration_minutes
+    ))
+    .unwrap()
+}
+
+#[test]
+fn schedules_fire_on_their_minute() {
+    let schedule = Schedule::parse("0 18 6").unwrap();
+
+    assert!(schedule.fires_at(SATURDAY_EVENING / 60));
+    assert!(!schedule.fires_at(SATURDAY_EVENING / 60 + 1));
+    assert!(!schedule.fires_at(SATURDAY_EVENING / 60 - 24 * 60));
+    assert!(Schedule::parse("0,30 * *")
+        .unwrap()
+        .fires_at(SATURDAY_EVENING / 60 + 30));
+}
+
+#[test]
+fn invalid_schedules_are_rejected() {
+    assert!(Schedule::parse("0 18").is_err());
+    assert!(Schedule::parse("0 24 *").is_err());
+    assert!(Schedule::parse("x * *").is_err());
+    assert!(serde_json::from_str::<ScheduledEvent>(
+        r#"{ "name": "Broken", "start": "61 * *", "duration_minutes": 1 }"#
+    )
+    .is_err());
+}
+
+#[test]
+fn events_last_for_their_duration() {
+    let event = weekend(120);
+
+    assert_eq!(event.mass_multiplier, 1.0);
+    assert!(event.is_active(SATURDAY_EVENING));
+    assert!(event.is_active(SATURDAY_EVENING + 119 * 60));
+    assert!(!event.is_active(SATURDAY_EVENING + 120 * 60));
+    assert!(!event.is_active(SATURDAY_EVENING - 1));
+}
+
+#[test]
+fn rooms_apply_the_events_under_way() {
+    let mut game_manager = common::game_manager();
+    let mut event = weekend(1);
+    event.start = Schedule::parse("* * *").unwrap();
+    event.mass_multiplier = 2.0;
+    event.disable_decay = true;
+    game_manager.config.events = vec![event];
+
+    game_manager.execute_internal_command(InternalCommand::Update);
+
+    assert_eq!(
+        game_manager.active_events,
+        vec![String::from("Double mass")]
+    );
+    assert_eq!(game_manager.mass_multiplier, 2.0);
+    assert!(game_manager.decay_disabled);
+}